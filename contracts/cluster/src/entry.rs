@@ -4,22 +4,24 @@ use alloc::vec::Vec;
 // Import from `core` instead of from `std` since we are in no-std mode
 use core::result::Result;
 
-use ckb_std::ckb_constants::Source::{CellDep, GroupInput, GroupOutput, Output};
+use ckb_std::ckb_constants::Source::{GroupInput, GroupOutput, Input, Output};
 use ckb_std::ckb_types::packed::Script;
-use ckb_std::{
-    ckb_constants::Source,
-    ckb_types::prelude::*,
-    high_level::{load_cell_data, load_cell_type},
-};
+use ckb_std::{ckb_constants::Source, ckb_types::prelude::*, high_level::load_cell_type};
 // Import CKB syscalls and structures
 // https://docs.rs/ckb-std/
 use ckb_std::high_level::{load_script, QueryIter};
 use spore_errors::error::Error;
-use spore_types::generated::spore_types::ClusterData;
-use spore_utils::{find_position_by_type, find_position_by_type_args, verify_type_id};
+use spore_utils::transaction::{
+    verify_created_cluster, verify_destroyed_cluster, verify_transferred_cluster, Contract,
+    Syscalls,
+};
+use spore_utils::{find_position_by_type, load_cluster_data};
 
 use crate::hash::SPORE_EXTENSION_LUA;
 
+const CODE_HASHES: &[(Contract, &[[u8; 32]])] =
+    &[(Contract::SporeExtensionLua, &SPORE_EXTENSION_LUA)];
+
 #[allow(unused)]
 fn process_input(
     index: usize,
@@ -54,43 +56,23 @@ fn process_input(
     Err(Error::InvalidClusterOperation)
 }
 
-fn load_cluster_data(index: usize, source: Source) -> Result<ClusterData, Error> {
-    let raw_data = load_cell_data(index, source)?;
-    let cluster_data = ClusterData::from_compatible_slice(raw_data.as_slice())
-        .map_err(|_| Error::InvalidClusterData)?;
-    Ok(cluster_data)
-}
-
 fn process_creation(index: usize) -> Result<(), Error> {
-    let cluster_data = load_cluster_data(index, Output)?;
-    if cluster_data.name().is_empty() {
-        return Err(Error::EmptyName);
-    }
-    if !verify_type_id(index, Output) {
-        return Err(Error::InvalidClusterID);
-    }
-
-    // Verify if mutant is set
-    if cluster_data.mutant_id().is_some() {
-        let script = load_script().unwrap_or_default();
-        let filter_fn: fn(&[u8; 32]) -> bool = |x| -> bool { SPORE_EXTENSION_LUA.contains(x) };
-        let args: Vec<u8> = script.args().unpack();
-        find_position_by_type_args(&args, CellDep, Some(filter_fn))
-            .ok_or(Error::MutantNotInDeps)?;
-    }
-
+    verify_created_cluster(&Syscalls::new(CODE_HASHES), index)?;
     Ok(())
 }
 
 fn process_transfer() -> Result<(), Error> {
-    // check no field was modified
-    let input_cluster_data = load_cluster_data(0, GroupInput)?;
-    let output_cluster_data = load_cluster_data(0, GroupOutput)?;
-
-    if input_cluster_data.as_slice()[..] != output_cluster_data.as_slice()[..] {
-        return Err(Error::ModifyClusterPermanentField);
-    }
+    let script = load_script()?;
+    let input_index = find_position_by_type(&script, Input).ok_or(Error::IndexOutOfBound)?;
+    let output_index = find_position_by_type(&script, Output).ok_or(Error::IndexOutOfBound)?;
+    verify_transferred_cluster(&Syscalls::new(CODE_HASHES), input_index, output_index)?;
+    Ok(())
+}
 
+fn process_destruction() -> Result<(), Error> {
+    let script = load_script()?;
+    let input_index = find_position_by_type(&script, Input).ok_or(Error::IndexOutOfBound)?;
+    verify_destroyed_cluster(&Syscalls::new(CODE_HASHES), input_index)?;
     Ok(())
 }
 
@@ -119,15 +101,10 @@ pub fn main() -> Result<(), Error> {
 
             let output_index =
                 find_position_by_type(&cluster_in_output[0], Output).unwrap_or_default(); // Once we entered here, it can't be empty, and use 0 as a fallback position
-            return process_creation(output_index);
-        }
-        // can not destroy a cluster cell
-        (1, 0) => {
-            return Err(Error::InvalidClusterOperation);
-        }
-        (1, 1) => {
-            return process_transfer();
+            process_creation(output_index)
         }
+        (1, 0) => process_destruction(),
+        (1, 1) => process_transfer(),
         _ => unreachable!(),
     }
 }
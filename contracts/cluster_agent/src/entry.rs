@@ -7,73 +7,30 @@ use alloc::vec::Vec;
 
 // Import CKB syscalls and structures
 // https://docs.rs/ckb-std/
-use ckb_std::ckb_constants::Source::{CellDep, GroupInput, GroupOutput, Input, Output};
+use ckb_std::ckb_constants::Source::{GroupInput, GroupOutput, Input, Output};
 use ckb_std::ckb_types::packed::Script;
-use ckb_std::high_level::{load_cell_data, load_cell_lock_hash, load_cell_type, QueryIter};
-use ckb_std::{ckb_types::prelude::*, debug, high_level::load_script};
+use ckb_std::high_level::{load_cell_type, load_script, QueryIter};
 use spore_errors::error::Error;
-use spore_utils::{calc_capacity_sum, find_position_by_type, find_posityion_by_type_hash};
+use spore_utils::find_position_by_type;
+use spore_utils::transaction::{
+    verify_created_agent, verify_transferred_agent, Contract, Syscalls,
+};
 
-const CLUSTER_PROXY_ID_LEN: usize = 32;
+const CODE_HASHES: &[(Contract, &[[u8; 32]])] = &[(
+    Contract::ClusterProxy,
+    &crate::hash::CLUSTER_PROXY_CODE_HASHES,
+)];
 
-fn is_valid_cluster_proxy_cell(script_hash: &[u8; 32]) -> bool {
-    crate::hash::CLUSTER_PROXY_CODE_HASHES.contains(script_hash)
-}
-
-fn process_creation(_index: usize) -> Result<(), Error> {
-    let proxy_type_hash = load_cell_data(0, GroupOutput)?;
-    // check cluster proxy in Deps
-    let cell_dep_index = find_posityion_by_type_hash(proxy_type_hash.as_slice(), CellDep)
-        .ok_or(Error::ProxyCellNotInDep)?;
-    let target_cell_type_hash = load_cell_type(cell_dep_index, CellDep)?.unwrap_or_default();
-    if !is_valid_cluster_proxy_cell(&target_cell_type_hash.code_hash().unpack()) {
-        return Err(Error::RefCellNotClusterProxy);
-    }
-
-    // verify cluster ID
-    let cluster_id = load_cell_data(cell_dep_index, CellDep)?;
-    let script = load_script()?;
-    let script_args: Vec<u8> = script.args().unpack();
-    if script_args.as_slice()[..] != cluster_id.as_slice()[..] {
-        return Err(Error::InvalidAgentArgs);
-    }
-
-    // Condition 1: Check if cluster proxy exist in Inputs & Outputs
-    return if find_posityion_by_type_hash(proxy_type_hash.as_slice(), Input).is_some()
-        && find_posityion_by_type_hash(proxy_type_hash.as_slice(), Output).is_some()
-    {
-        Ok(())
-    } else {
-        // Condition 2: Check for minimal payment
-        let proxy_type_args = load_cell_type(cell_dep_index, CellDep)?
-            .unwrap_or_default()
-            .args()
-            .raw_data();
-        if proxy_type_args.len() > CLUSTER_PROXY_ID_LEN {
-            let minimal_payment_args = proxy_type_args.get(CLUSTER_PROXY_ID_LEN).unwrap_or(&0);
-            debug!("Minimal payment is: {}", minimal_payment_args);
-            let minimal_payment = 10u128.pow(*minimal_payment_args as u32);
-            let lock = load_cell_lock_hash(cell_dep_index, CellDep)?;
-            let input_capacity = calc_capacity_sum(&lock, Input);
-            let output_capacity = calc_capacity_sum(&lock, Output);
-            if input_capacity + minimal_payment < output_capacity {
-                return Err(Error::PaymentNotEnough);
-            }
-        } else {
-            return Err(Error::PaymentMethodNotSupport);
-        }
-        Ok(())
-    };
+fn process_creation(index: usize) -> Result<(), Error> {
+    verify_created_agent(&Syscalls::new(CODE_HASHES), index)?;
+    Ok(())
 }
 
 fn process_transfer() -> Result<(), Error> {
-    let input_agent_data = load_cell_data(0, GroupInput)?;
-    let output_agent_data = load_cell_data(0, GroupOutput)?;
-
-    if input_agent_data.as_slice()[..] != output_agent_data.as_slice()[..] {
-        return Err(Error::ImmutableAgentFieldModification);
-    }
-
+    let script = load_script()?;
+    let input_index = find_position_by_type(&script, Input).ok_or(Error::IndexOutOfBound)?;
+    let output_index = find_position_by_type(&script, Output).ok_or(Error::IndexOutOfBound)?;
+    verify_transferred_agent(&Syscalls::new(CODE_HASHES), input_index, output_index)?;
     Ok(())
 }
 
@@ -96,15 +53,15 @@ pub fn main() -> Result<(), Error> {
         return Err(Error::InvalidAgentOperation);
     }
 
-    return match (agent_in_input.len(), agent_in_output.len()) {
+    match (agent_in_input.len(), agent_in_output.len()) {
         (0, 1) => {
             // Creation
             let output_index =
                 find_position_by_type(&agent_in_output[0], Output).unwrap_or_default(); // Once we entered here, it can't be empty, and use 0 as a fallback position
-            return process_creation(output_index);
+            process_creation(output_index)
         }
         (1, 0) => Ok(()), // There's no limitation to destroy an agent except lock
         (1, 1) => process_transfer(),
         _ => unreachable!(),
-    };
+    }
 }
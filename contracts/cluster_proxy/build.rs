@@ -6,7 +6,7 @@ pub fn concat_code_hashes(var_name: &str, code_hashes: &[[u8; 32]]) -> String {
         "pub const {var_name}: [[u8; 32]; {}] = [",
         code_hashes.len()
     );
-    code_hashes.into_iter().for_each(|v| {
+    code_hashes.iter().for_each(|v| {
         content += &format!("{v:?},");
     });
     content += "];\n";
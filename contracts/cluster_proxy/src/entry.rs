@@ -1,61 +1,28 @@
 use alloc::vec::Vec;
 // Import from `core` instead of from `std` since we are in no-std mode
-use ckb_std::ckb_constants::Source::{CellDep, GroupInput, GroupOutput, Input, Output};
+use ckb_std::ckb_constants::Source::{GroupInput, GroupOutput, Input, Output};
 use ckb_std::ckb_types::packed::Script;
-use ckb_std::high_level::{load_cell_data, load_cell_lock_hash, load_cell_type, QueryIter};
+use ckb_std::high_level::{load_cell_type, load_script, QueryIter};
 use core::result::Result;
 use spore_errors::error::Error;
-use spore_utils::{
-    find_position_by_lock_hash, find_position_by_type, find_position_by_type_args, verify_type_id,
+use spore_utils::find_position_by_type;
+use spore_utils::transaction::{
+    verify_created_proxy, verify_transferred_proxy, Contract, Syscalls,
 };
 
-const CLUSTER_PROXY_ID_LEN: usize = 32;
-
-fn is_valid_cluster_cell(script_hash: &[u8; 32]) -> bool {
-    crate::hash::CLUSTER_CODE_HASHES.contains(script_hash)
-}
+const CODE_HASHES: &[(Contract, &[[u8; 32]])] =
+    &[(Contract::Cluster, &crate::hash::CLUSTER_CODE_HASHES)];
 
 fn process_creation(index: usize) -> Result<(), Error> {
-    let target_cluster_id = load_cell_data(0, GroupOutput)?;
-    // check cluster in Deps
-    let cell_dep_index =
-        find_position_by_type_args(&target_cluster_id, CellDep, Some(is_valid_cluster_cell))
-            .ok_or(Error::ClusterCellNotInDep)?;
-
-    // verify Proxy ID
-    if !verify_type_id(index, Output) {
-        return Err(Error::InvalidProxyID);
-    }
-
-    // Condition 1: Check if cluster exist in Inputs & Outputs
-    return if find_position_by_type_args(&target_cluster_id, Input, Some(is_valid_cluster_cell))
-        .is_some()
-        && find_position_by_type_args(&target_cluster_id, Output, Some(is_valid_cluster_cell))
-            .is_some()
-    {
-        Ok(())
-    } else {
-        // Condition 2: Check if Lock Proxy exist in Inputs & Outputs
-        let cluster_lock_hash = load_cell_lock_hash(cell_dep_index, CellDep)?;
-        find_position_by_lock_hash(&cluster_lock_hash, Output)
-            .ok_or(Error::ClusterOwnershipVerifyFailed)?;
-        find_position_by_lock_hash(&cluster_lock_hash, Input)
-            .ok_or(Error::ClusterOwnershipVerifyFailed)?;
-        Ok(())
-    };
+    verify_created_proxy(&Syscalls::new(CODE_HASHES), index)?;
+    Ok(())
 }
 
 fn process_transfer() -> Result<(), Error> {
-    let input_proxy_type = load_cell_type(0, GroupInput)?.unwrap_or_default();
-    let output_proxy_type = load_cell_type(0, GroupOutput)?.unwrap_or_default();
-
-    // NOTE: We allow minimal payment modification during transfer
-    if input_proxy_type.args().raw_data()[..CLUSTER_PROXY_ID_LEN]
-        != output_proxy_type.args().raw_data()[..CLUSTER_PROXY_ID_LEN]
-    {
-        return Err(Error::ImmutableProxyFieldModification);
-    }
-
+    let script = load_script()?;
+    let input_index = find_position_by_type(&script, Input).ok_or(Error::IndexOutOfBound)?;
+    let output_index = find_position_by_type(&script, Output).ok_or(Error::IndexOutOfBound)?;
+    verify_transferred_proxy(&Syscalls::new(CODE_HASHES), input_index, output_index)?;
     Ok(())
 }
 
@@ -78,15 +45,15 @@ pub fn main() -> Result<(), Error> {
         return Err(Error::InvalidProxyOperation);
     }
 
-    return match (proxy_in_input.len(), proxy_in_output.len()) {
+    match (proxy_in_input.len(), proxy_in_output.len()) {
         (0, 1) => {
             // Creation
             let output_index =
                 find_position_by_type(&proxy_in_output[0], Output).unwrap_or_default(); // Once we entered here, it can't be empty, and use 0 as a fallback position
-            return process_creation(output_index);
+            process_creation(output_index)
         }
         (1, 0) => Ok(()), // There's no limitation to destroy a proxy except lock
         (1, 1) => process_transfer(),
         _ => unreachable!(),
-    };
+    }
 }
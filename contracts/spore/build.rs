@@ -16,7 +16,7 @@ pub fn concat_code_hashes(var_name: &str, code_hashes: &[[u8; 32]]) -> String {
         "pub const {var_name}: [[u8; 32]; {}] = [",
         code_hashes.len()
     );
-    code_hashes.into_iter().for_each(|v| {
+    code_hashes.iter().for_each(|v| {
         content += &format!("{v:?},");
     });
     content += "];\n";
@@ -27,6 +27,8 @@ pub fn main() {
     let compile_mode = env::var("PROFILE").unwrap();
     let cluster_code_hash = load_code_hash("cluster", &compile_mode);
     let cluster_agent_code_hash = load_code_hash("cluster_agent", &compile_mode);
+    let segment_code_hash = load_code_hash("spore_segment", &compile_mode);
+    let extension_lua_code_hash = load_code_hash("spore_extension_lua", &compile_mode);
 
     let mut cluster_code_hashes = vec![cluster_code_hash];
     // this is version v1 of cluster contract in testnet
@@ -39,5 +41,11 @@ pub fn main() {
 
     let mut content = concat_code_hashes("CLUSTER_CODE_HASHES", &cluster_code_hashes);
     content += concat_code_hashes("CLUSTER_AGENT_CODE_HASHES", &[cluster_agent_code_hash]).as_str();
+    content += concat_code_hashes("SPORE_SEGMENT_CODE_HASHES", &[segment_code_hash]).as_str();
+    content += concat_code_hashes(
+        "SPORE_EXTENSION_LUA_CODE_HASHES",
+        &[extension_lua_code_hash],
+    )
+    .as_str();
     fs::write("./src/hash.rs", content).unwrap();
 }
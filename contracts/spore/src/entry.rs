@@ -1,25 +1,41 @@
-use alloc::{format, vec, vec::Vec};
+use alloc::{string::String, vec::Vec};
 use core::ffi::CStr;
 use core::result::Result;
 
-use ckb_std::ckb_constants::Source::{CellDep, GroupInput, GroupOutput, Input, Output};
+use ckb_std::ckb_constants::Source::{self, GroupInput, GroupOutput, Input, Output};
 use ckb_std::ckb_types::core::ScriptHashType;
 use ckb_std::ckb_types::packed::Script;
-use ckb_std::high_level::{load_cell_data_hash, load_cell_lock_hash};
 use ckb_std::{
-    ckb_constants::Source,
     ckb_types::prelude::*,
-    high_level::{load_cell_data, load_cell_type, QueryIter},
+    high_level::{
+        load_cell_data, load_cell_type, load_cell_type_hash, load_script_hash, QueryIter,
+    },
 };
 
 use spore_errors::error::Error;
-use spore_types::generated::spore_types::{ClusterData, SporeData};
-use spore_utils::{
-    calc_capacity_sum, find_position_by_lock_hash, find_position_by_type,
-    find_position_by_type_args, verify_type_id, MIME,
+use spore_types::generated::spore_types::SporeData;
+use spore_utils::extension::{ExtensionArgs, Operation};
+use spore_utils::find_position_by_type;
+use spore_utils::transaction::{
+    resolve_mutants, verify_melted_spore, verify_minted_spore, verify_transferred_spore, Contract,
+    Syscalls,
 };
+use spore_utils::MIME;
 
-use crate::hash::{CLUSTER_AGENT_CODE_HASHES, CLUSTER_CODE_HASHES};
+use crate::hash::{
+    CLUSTER_AGENT_CODE_HASHES, CLUSTER_CODE_HASHES, SPORE_EXTENSION_LUA_CODE_HASHES,
+    SPORE_SEGMENT_CODE_HASHES,
+};
+
+const CODE_HASHES: &[(Contract, &[[u8; 32]])] = &[
+    (Contract::Cluster, &CLUSTER_CODE_HASHES),
+    (Contract::ClusterAgent, &CLUSTER_AGENT_CODE_HASHES),
+    (Contract::SporeSegment, &SPORE_SEGMENT_CODE_HASHES),
+    (
+        Contract::SporeExtensionLua,
+        &SPORE_EXTENSION_LUA_CODE_HASHES,
+    ),
+];
 
 fn load_spore_data(index: usize, source: Source) -> Result<SporeData, Error> {
     let raw_data = load_cell_data(index, source)?;
@@ -29,106 +45,17 @@ fn load_spore_data(index: usize, source: Source) -> Result<SporeData, Error> {
 }
 
 fn process_creation(index: usize) -> Result<(), Error> {
-    let spore_data = load_spore_data(index, Output)?;
-
-    if spore_data.content().is_empty() {
-        return Err(Error::EmptyContent);
-    }
-
-    let content = spore_data.content();
-    let content_arr = content.as_slice();
-
-    if spore_data.content_type().is_empty() {
-        return Err(Error::InvalidContentType);
-    }
-
-    // verify NFT ID
-    if !verify_type_id(index, Output) {
-        return Err(Error::InvalidNFTID);
-    }
-
-    let raw_content_type = spore_data.content_type();
-    let content_type = raw_content_type.unpack();
-
-    let mime = MIME::parse(content_type)?; // content_type validation
-    if content_type[mime.main_type.clone()] == "multipart".as_bytes()[..] {
-        // Check if boundary param exists
-        let boundary_range = mime
-            .get_param(content_type, "boundary")
-            .ok_or(Error::InvalidContentType)?;
-        kmp::kmp_find(
-            format!(
-                "--{}",
-                alloc::str::from_utf8(&content_type[boundary_range]).or(Err(Error::Encoding))?
-            )
-            .as_bytes(),
-            content_arr,
-        )
-        .ok_or(Error::InvalidMultipartContent)?;
-    }
-
-    if spore_data.cluster_id().to_opt().is_some() {
-        // check if cluster cell in deps
-        let cluster_id = spore_data
-            .cluster_id()
-            .to_opt()
-            .unwrap_or_default()
-            .raw_data();
-        let cluster_fn: fn(&[u8; 32]) -> bool = |x| -> bool { CLUSTER_CODE_HASHES.contains(x) };
-        let agent_fn: fn(&[u8; 32]) -> bool = |x| -> bool { CLUSTER_AGENT_CODE_HASHES.contains(x) };
-        let cell_dep_index = find_position_by_type_args(&cluster_id, CellDep, Some(cluster_fn))
-            .ok_or(Error::ClusterCellNotInDep)?;
-
-        let raw_cluster_data = load_cell_data(cell_dep_index, CellDep)?;
-        let cluster_data =
-            ClusterData::from_compatible_slice(&raw_cluster_data).unwrap_or_default(); // the cluster contract guarantees the cluster data will always be correct once created
-        if cluster_data.mutant_id().is_some() {
-            let mutant_id = cluster_data.mutant_id().to_opt().unwrap_or_default();
-            let mutant_verify_passed = mime
-                .mutants
-                .iter()
-                .any(|mutant| mutant == mutant_id.raw_data().as_ref());
-            if !mutant_verify_passed {
-                // required mutant does not applied
-                return Err(Error::ClusterRequiresMutantApplied);
-            }
-        }
-
-        // Condition 1: Check if cluster exist in Inputs & Outputs
-        return if find_position_by_type_args(&cluster_id, Input, Some(cluster_fn)).is_some()
-            && find_position_by_type_args(&cluster_id, Output, Some(cluster_fn)).is_some()
-        {
-            Ok(())
-        }
-        // Condition 2: Check if cluster agent in Inputs & Outputs
-        else if find_position_by_type_args(&cluster_id, Input, Some(agent_fn)).is_some()
-            && find_position_by_type_args(&cluster_id, Output, Some(agent_fn)).is_some()
-        {
-            Ok(())
-        }
-        // Condition 3: Use cluster agent by lock proxy
-        else if let Some(agent_index) =
-            find_position_by_type_args(&cluster_id, CellDep, Some(agent_fn))
-        {
-            let agent_lock_hash = load_cell_lock_hash(agent_index, CellDep)?;
-            find_position_by_lock_hash(&agent_lock_hash, Output)
-                .ok_or(Error::ClusterOwnershipVerifyFailed)?;
-            find_position_by_lock_hash(&agent_lock_hash, Input)
-                .ok_or(Error::ClusterOwnershipVerifyFailed)?;
-            Ok(())
-        } else {
-            // Condition 4: Check if Lock Proxy exist in Inputs & Outputs
-            let cluster_lock_hash = load_cell_lock_hash(cell_dep_index, CellDep)?;
-            find_position_by_lock_hash(&cluster_lock_hash, Output)
-                .ok_or(Error::ClusterOwnershipVerifyFailed)?;
-            find_position_by_lock_hash(&cluster_lock_hash, Input)
-                .ok_or(Error::ClusterOwnershipVerifyFailed)?;
-            Ok(())
-        };
-    }
+    verify_minted_spore(&Syscalls::new(CODE_HASHES), index)?;
 
+    let spore_data = load_spore_data(index, Output)?;
+    let content_type_bytes = spore_data.content_type();
+    let mime = MIME::parse(content_type_bytes.unpack())?;
     if !mime.mutants.is_empty() {
-        verify_extension(&mime, 0, vec![index as u8])?;
+        let spore_id = load_cell_type(index, Output)?.unwrap_or_default().args();
+        let operation = Operation::Mint {
+            output_index: index,
+        };
+        verify_extension(&mime, operation, &spore_id.raw_data(), &spore_data)?;
     }
 
     Ok(())
@@ -136,20 +63,19 @@ fn process_creation(index: usize) -> Result<(), Error> {
 
 fn process_destruction() -> Result<(), Error> {
     //destruction
-    let spore_data = load_spore_data(0, GroupInput)?;
-
-    let content_type_bytes = spore_data.content_type();
-    let content_type = content_type_bytes.unpack();
-    let mime = MIME::parse(content_type)?;
-    if mime.immortal {
-        // true destroy a immortal nft
-        return Err(Error::DestroyImmortalNFT);
-    }
+    let type_script = load_cell_type(0, GroupInput)?.unwrap_or_default();
+    let input_index = find_position_by_type(&type_script, Input).ok_or(Error::IndexOutOfBound)?;
+    let mime = verify_melted_spore(&Syscalls::new(CODE_HASHES), input_index)?;
 
     if !mime.mutants.is_empty() {
-        let type_script = load_cell_type(0, GroupInput)?.unwrap_or_default();
-        let index = find_position_by_type(&type_script, Input).ok_or(Error::IndexOutOfBound)?;
-        verify_extension(&mime, 2, vec![index as u8])?;
+        let spore_data = load_spore_data(0, GroupInput)?;
+        let operation = Operation::Melt { input_index };
+        verify_extension(
+            &mime,
+            operation,
+            &type_script.args().raw_data(),
+            &spore_data,
+        )?;
     }
 
     Ok(())
@@ -157,97 +83,63 @@ fn process_destruction() -> Result<(), Error> {
 
 fn process_transfer() -> Result<(), Error> {
     // found same NFT in output, this is a transfer
-    // check no field was modified
-    let input_data = load_spore_data(0, GroupInput)?;
-    let output_data = load_spore_data(0, GroupOutput)?;
-
-    if input_data.as_slice()[..] != output_data.as_slice()[..] {
-        return Err(Error::ModifySporePermanentField);
-    }
-
-    let content_type_bytes = input_data.content_type();
-    let content_type = content_type_bytes.unpack();
-    let mime = MIME::parse(content_type)?;
+    let type_script = load_cell_type(0, GroupInput)?.unwrap_or_default();
+    let input_index = find_position_by_type(&type_script, Input).ok_or(Error::IndexOutOfBound)?;
+    let output_index = find_position_by_type(&type_script, Output).ok_or(Error::IndexOutOfBound)?;
+    let mime = verify_transferred_spore(&Syscalls::new(CODE_HASHES), input_index, output_index)?;
 
     if !mime.mutants.is_empty() {
-        let type_script = load_cell_type(0, GroupInput)?.unwrap_or_default();
-        let input_index =
-            find_position_by_type(&type_script, Input).ok_or(Error::IndexOutOfBound)?;
-        let output_index =
-            find_position_by_type(&type_script, Output).ok_or(Error::IndexOutOfBound)?;
-        verify_extension(&mime, 1, vec![input_index as u8, output_index as u8])?;
+        let input_data = load_spore_data(0, GroupInput)?;
+        let operation = Operation::Transfer {
+            input_index,
+            output_index,
+        };
+        verify_extension(
+            &mime,
+            operation,
+            &type_script.args().raw_data(),
+            &input_data,
+        )?;
     }
 
     Ok(())
 }
 
-fn verify_extension(mime: &MIME, op: usize, argv: Vec<u8>) -> Result<(), Error> {
-    for mutant in mime.mutants.iter() {
-        let ext_pos = QueryIter::new(load_cell_type, CellDep).position(|script| match script {
-            Some(script) => mutant[..] == script.args().raw_data()[..32],
-            None => false,
-        });
-        match ext_pos {
-            None => return Err(Error::ExtensionCellNotInDep),
-            Some(ext_pos) => {
-                if op == 0 {
-                    check_payment(ext_pos)?;
-                }
-
-                let ext_pos = ext_pos as u8;
-                let code_hash = load_cell_data_hash(ext_pos.into(), CellDep)?;
-                match op {
-                    0 | 2 => {
-                        ckb_std::high_level::exec_cell(
-                            &code_hash,
-                            ScriptHashType::Data1,
-                            &[
-                                CStr::from_bytes_with_nul([b'0', 0].as_slice()).unwrap_or_default(),
-                                CStr::from_bytes_with_nul([b'0' + ext_pos, 0].as_slice())
-                                    .unwrap_or_default(),
-                                CStr::from_bytes_with_nul([b'0' + argv[0], 0].as_slice())
-                                    .unwrap_or_default(),
-                            ],
-                        )?;
-                    }
-                    1 => {
-                        ckb_std::high_level::exec_cell(
-                            &code_hash,
-                            ScriptHashType::Data1,
-                            &[
-                                CStr::from_bytes_with_nul([b'0', 0].as_slice()).unwrap_or_default(),
-                                CStr::from_bytes_with_nul([b'0' + ext_pos, 0].as_slice())
-                                    .unwrap_or_default(),
-                                CStr::from_bytes_with_nul([b'0' + argv[0], 0].as_slice())
-                                    .unwrap_or_default(),
-                                CStr::from_bytes_with_nul([b'0' + argv[1], 0].as_slice())
-                                    .unwrap_or_default(),
-                            ],
-                        )?;
-                    }
-                    _ => unreachable!(),
-                }
-            }
-        }
-    }
-    Ok(())
-}
-
-fn check_payment(ext_pos: usize) -> Result<(), Error> {
-    let ext_script = load_cell_type(ext_pos, CellDep)?.unwrap_or_default();
-    let ext_args = ext_script.args().raw_data();
-    // CAUTION: only check 33 size pattern, leave room for user customizing
-    if ext_args.len() > 32 {
-        // we need a payment
-        let lock = load_cell_lock_hash(ext_pos, CellDep)?;
-
-        let input_capacity = calc_capacity_sum(&lock, Input);
-        let output_capacity = calc_capacity_sum(&lock, Output);
-        let minimal_payment = 10u128.pow(ext_args.get(32).cloned().unwrap_or(0) as u32);
-        if input_capacity + minimal_payment < output_capacity {
-            return Err(Error::ExtensionPaymentNotEnough);
+fn verify_extension(
+    mime: &MIME,
+    operation: Operation,
+    spore_id: &[u8],
+    spore_data: &SporeData,
+) -> Result<(), Error> {
+    let content_type = String::from_utf8(spore_data.content_type().unpack().to_vec())
+        .map_err(|_| Error::InvalidContentType)?;
+    // `exec` never returns, so every mutant is run by one `exec` of the code they share
+    let (source, index) = match operation {
+        Operation::Mint { output_index } => (Output, output_index),
+        Operation::Transfer { input_index, .. } | Operation::Melt { input_index } => {
+            (Input, input_index)
         }
+    };
+    let (mutant_code, mutant_indices) =
+        match resolve_mutants(&Syscalls::new(CODE_HASHES), mime, source, index)? {
+            Some(mutants) => mutants,
+            None => return Ok(()),
+        };
+    let hash_type = match u8::from(mutant_code.hash_type()) {
+        0 => ScriptHashType::Data,
+        1 => ScriptHashType::Type,
+        2 => ScriptHashType::Data1,
+        _ => return Err(Error::InvalidExtensionID),
+    };
+    let argv = ExtensionArgs {
+        operation,
+        mutant_indices,
+        spore_id: spore_id.to_vec(),
+        content_type,
     }
+    .to_argv()?;
+    let argv: Vec<&CStr> = argv.iter().map(|arg| arg.as_c_str()).collect();
+    ckb_std::high_level::exec_cell(mutant_code.code_hash().as_slice(), hash_type, &argv)?;
     Ok(())
 }
 
@@ -270,17 +162,16 @@ pub fn main() -> Result<(), Error> {
 
     match (spore_in_input.len(), spore_in_output.len()) {
         (0, 1) => {
-            // find it's index in Source::Output
-            let output_index =
-                find_position_by_type(&spore_in_output[0], Output).unwrap_or_default(); // Once we entered here, it can't be empty, and use 0 as a fallback position
-            return process_creation(output_index);
-        }
-        (1, 0) => {
-            return process_destruction();
-        }
-        (1, 1) => {
-            return process_transfer();
+            // find it's index in Source::Output, by type hash as every Spore of a batch mint
+            // looks for its own output
+            let type_hash = load_script_hash()?;
+            let output_index = QueryIter::new(load_cell_type_hash, Output)
+                .position(|hash| hash == Some(type_hash))
+                .unwrap_or_default(); // Once we entered here, it can't be empty, and use 0 as a fallback position
+            process_creation(output_index)
         }
+        (1, 0) => process_destruction(),
+        (1, 1) => process_transfer(),
         _ => unreachable!(),
     }
 }
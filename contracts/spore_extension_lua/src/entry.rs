@@ -12,13 +12,17 @@ use alloc::{format, vec, vec::Vec};
 // https://docs.rs/ckb-std/
 use ckb_std::ckb_constants::Source::{CellDep, GroupInput, GroupOutput, Output};
 use ckb_std::ckb_types::packed::Script;
+use ckb_std::ckb_types::prelude::*;
 use ckb_std::debug;
 use ckb_std::dynamic_loading_c_impl::{CKBDLContext, Library, Symbol};
 use ckb_std::env::Arg;
 use ckb_std::high_level::{load_cell_data, load_cell_type, QueryIter};
-use core::ffi::{c_char, c_int, c_ulong, c_void};
+use core::ffi::{c_char, c_int, c_ulong, c_void, CStr};
+use core::marker::PhantomData;
 use spore_errors::error::Error;
-use spore_utils::{find_position_by_type, verify_type_id};
+use spore_types::generated::spore_types::SporeData;
+use spore_utils::extension::{verify_mutant_transfer, ExtensionArgs, MutantArgs, Operation};
+use spore_utils::{find_position_by_type, verify_type_id, MIME};
 
 use crate::error::WrappedError;
 use crate::hash::CKB_LUA_LIB_CODE_HASH;
@@ -30,26 +34,35 @@ type EvaluateLuaInstanceType = unsafe extern "C" fn(
     code_size: usize,
     name: *const c_char,
 ) -> c_int;
+type CloseLuaInstanceType = unsafe extern "C" fn(instance: *mut c_void);
 
-const SPORE_EXT_NORMAL_ARG_LEN: usize = 32;
-const SPORE_EXT_MINIMAL_PAYMENT_ARG_LEN: usize = 33;
+type LuaLibContext = CKBDLContext<[u8; 270 * 1024]>;
 
-struct CKBLuaLib {
+const LUA_HEAP_SIZE: usize = 500 * 1024;
+
+/// The Lua lib loaded once, running every script in a fresh Lua instance
+struct CKBLuaLib<'a> {
     lib: Library,
+    heap: Vec<u8>,
+    // the lib lives in the context, which has to outlive it
+    _context: PhantomData<&'a mut LuaLibContext>,
 }
 
-impl CKBLuaLib {
-    pub fn new() -> Result<Self, Error> {
-        let mut context = unsafe { CKBDLContext::<[u8; 270 * 1024]>::new() };
+impl<'a> CKBLuaLib<'a> {
+    pub fn new(context: &'a mut LuaLibContext) -> Result<Self, Error> {
         #[allow(deprecated)]
         let lib = context
             .load(&CKB_LUA_LIB_CODE_HASH)
             .map_err(|_| Error::FailedToLoadLuaLib)?;
-        Ok(Self { lib })
+        Ok(Self {
+            lib,
+            heap: vec![0u8; LUA_HEAP_SIZE],
+            _context: PhantomData,
+        })
     }
 
     pub fn evaluate_lua_script(
-        &self,
+        &mut self,
         index: usize,
         prefix_code: Option<String>,
     ) -> Result<(), WrappedError> {
@@ -63,14 +76,15 @@ impl CKBLuaLib {
         Ok(())
     }
 
-    fn create_lua_instance(&self) -> Result<*mut c_void, Error> {
+    // a new instance takes the heap over from the previous one, which is never used again
+    fn create_lua_instance(&mut self) -> Result<*mut c_void, Error> {
         match unsafe { self.lib.get(b"lua_create_instance") } {
             Some(create_lua_instance) => {
-                let mut lua_mem = vec![0u8; 500 * 1024];
+                let heap = self.heap.as_mut_ptr();
                 unsafe {
                     let instance = (create_lua_instance as Symbol<CreateLuaInstanceType>)(
-                        lua_mem.as_mut_ptr() as c_ulong,
-                        lua_mem.as_mut_ptr().offset(500 * 1024) as c_ulong,
+                        heap as c_ulong,
+                        heap.add(LUA_HEAP_SIZE) as c_ulong,
                     );
                     if instance.is_null() {
                         return Err(Error::FailedToCreateLuaInstance);
@@ -85,15 +99,15 @@ impl CKBLuaLib {
         }
     }
 
-    pub fn execute_lua_script(&self, code: &Vec<u8>) -> Result<(), WrappedError> {
+    pub fn execute_lua_script(&mut self, code: &[u8]) -> Result<(), WrappedError> {
         let instance = self.create_lua_instance()?;
         let ret = match unsafe { self.lib.get(b"lua_run_code") } {
             Some(lua_run_code) => {
-                let size = code.len().clone();
+                let size = code.len();
                 let ret = unsafe {
                     (lua_run_code as Symbol<EvaluateLuaInstanceType>)(
                         instance,
-                        code.as_ptr() as *const i8,
+                        code.as_ptr() as *const c_char,
                         size,
                         CString::new("SporeExtension").unwrap_or_default().as_ptr(),
                     )
@@ -102,15 +116,22 @@ impl CKBLuaLib {
             }
             None => Err(Error::InvalidLuaLib),
         }?;
+        // the next instance takes the heap over, so this one has to release it first
+        match unsafe { self.lib.get(b"lua_close_instance") } {
+            Some(lua_close_instance) => unsafe {
+                (lua_close_instance as Symbol<CloseLuaInstanceType>)(instance)
+            },
+            None => return Err(Error::InvalidLuaLib.into()),
+        }
 
         if ret == 0 {
-            return Ok(());
+            Ok(())
         }
         // we recommend the error code follows this pattern: -127 <= ret <= -1 or 100 <= ret <= 127
         else if 0 < ret && ret < Error::Unknown as i8 {
-            return Err(Error::InvalidLuaScript.into());
+            Err(Error::InvalidLuaScript.into())
         } else {
-            return Err(WrappedError::LuaError(ret));
+            Err(WrappedError::LuaError(ret))
         }
     }
 }
@@ -123,69 +144,122 @@ fn process_creation(index: usize) -> Result<(), WrappedError> {
         .unwrap_or_default()
         .args()
         .raw_data();
-    match args.len() {
-        SPORE_EXT_NORMAL_ARG_LEN | SPORE_EXT_MINIMAL_PAYMENT_ARG_LEN => {}
-        _ => {
-            return Err(Error::InvalidExtensionArg.into());
-        }
-    }
-    let lua_lib = CKBLuaLib::new()?;
+    MutantArgs::parse(&args)?;
+    let mut context = unsafe { LuaLibContext::new() };
+    let mut lua_lib = CKBLuaLib::new(&mut context)?;
 
     let prefix_code = "local spore_ext_mode = 0\n".to_owned();
     lua_lib.evaluate_lua_script(index, Some(prefix_code))?;
     Ok(())
 }
 
-fn process_transfer() -> Result<(), WrappedError> {
-    let input_data = load_cell_data(0, GroupInput)?;
-    let output_data = load_cell_data(0, GroupOutput)?;
-    if input_data.len() != output_data.len()
-        && input_data
-            .iter()
-            .zip(output_data.iter())
-            .all(|(i, o)| i == o)
-    {
-        return Err(Error::ModifyExtensionPermanentField.into());
+fn process_transfer(index: usize) -> Result<(), WrappedError> {
+    let args = load_cell_type(index, Output)?
+        .unwrap_or_default()
+        .args()
+        .raw_data();
+    verify_mutant_transfer(&MutantArgs::parse(&args)?)?;
+    // an upgraded code is evaluated as a newly created one
+    if load_cell_data(0, GroupInput)? != load_cell_data(0, GroupOutput)? {
+        let mut context = unsafe { LuaLibContext::new() };
+        let mut lua_lib = CKBLuaLib::new(&mut context)?;
+        let prefix_code = "local spore_ext_mode = 0\n".to_owned();
+        lua_lib.evaluate_lua_script(index, Some(prefix_code))?;
     }
     Ok(())
 }
 
-fn execute_code_create(extension_index: usize, target_index: usize) -> Result<(), WrappedError> {
-    let mut code_base =
-        format!("local spore_ext_mode = 1\nlocal spore_output_index = {target_index}\n")
-            .as_bytes()
-            .to_vec();
-    let mut ext_code = load_cell_data(extension_index, CellDep)?;
-    code_base.append(&mut ext_code);
-    let lua_lib = CKBLuaLib::new()?;
-    lua_lib.execute_lua_script(&code_base)
+/// Locals of the operation, prepended to every mutant
+fn mutant_prelude(operation: Operation) -> String {
+    match operation {
+        Operation::Mint { output_index } => {
+            debug!("Spore Creation with extension!");
+            format!("local spore_ext_mode = 1\nlocal spore_output_index = {output_index}\n")
+        }
+        Operation::Transfer {
+            input_index,
+            output_index,
+        } => {
+            debug!("Spore Transfer with extension!");
+            format!(
+                "local spore_ext_mode = 2\nlocal spore_input_index = {input_index}\nlocal spore_output_index = {output_index}\n"
+            )
+        }
+        Operation::Melt { input_index } => {
+            debug!("Spore Destroy with extension!");
+            format!("local spore_ext_mode = 3\nlocal spore_input_index = {input_index}\n")
+        }
+    }
 }
 
-fn execute_code_transfer(
-    extension_index: usize,
-    input_index: usize,
-    output_index: usize,
-) -> Result<(), WrappedError> {
-    let mut code_base = format!(
-        "local spore_ext_mode = 2\nlocal spore_input_index = {input_index}\nlocal spore_output_index = {output_index}\n"
-    )
-    .as_bytes()
-    .to_vec();
-    let mut ext_code = load_cell_data(extension_index, CellDep)?;
-    code_base.append(&mut ext_code);
-    let lua_lib = CKBLuaLib::new()?;
-    lua_lib.execute_lua_script(&code_base)
+/// Runs every mutant of the Spore with one load of the Lua lib, each in its own Lua instance
+fn execute_mutants(args: &ExtensionArgs) -> Result<(), WrappedError> {
+    let prelude = mutant_prelude(args.operation);
+    let (spore_index, source) = args.operation.spore_cell();
+    let spore_data = SporeData::from_compatible_slice(&load_cell_data(spore_index, source)?)
+        .map_err(|_| Error::InvalidNFTData)?;
+    // mutants are listed in the order of `mutant[]`, with the index of the code each runs
+    let mime = MIME::parse_existing(args.content_type.as_bytes())?;
+    if mime.mutants.len() != args.mutant_indices.len() {
+        return Err(Error::InvalidExtensionArgv.into());
+    }
+    let mut context = unsafe { LuaLibContext::new() };
+    let mut lua_lib = CKBLuaLib::new(&mut context)?;
+    for (&mutant_index, mutant_id) in args.mutant_indices.iter().zip(mime.mutants.iter()) {
+        let mut code = prelude.clone();
+        code.push_str(&spore_context(args, &mime, &spore_data, mutant_id));
+        let mut code = code.into_bytes();
+        code.append(&mut load_cell_data(mutant_index, CellDep)?);
+        lua_lib.execute_lua_script(&code)?;
+    }
+    Ok(())
 }
 
-fn execute_code_destroy(extension_index: usize, input_index: usize) -> Result<(), WrappedError> {
-    let mut code_base =
-        format!("local spore_ext_mode = 3\nlocal spore_input_index = {input_index}\n")
-            .as_bytes()
-            .to_vec();
-    let mut ext_code = load_cell_data(extension_index, CellDep)?;
-    code_base.append(&mut ext_code);
-    let lua_lib = CKBLuaLib::new()?;
-    lua_lib.execute_lua_script(&code_base)
+/// Bytes as a Lua string literal, anything but printable ASCII escaped as `\ddd`
+fn lua_string(bytes: &[u8]) -> String {
+    let mut literal = String::from("\"");
+    for &byte in bytes {
+        match byte {
+            b' '..=b'~' if byte != b'"' && byte != b'\\' => literal.push(byte as char),
+            _ => literal.push_str(&format!("\\{:03}", byte)),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// The `spore` global table of the Spore being operated, IDs are raw bytes
+fn spore_context(
+    args: &ExtensionArgs,
+    mime: &MIME,
+    spore_data: &SporeData,
+    mutant_id: &[u8],
+) -> String {
+    let params: String = mime
+        .params()
+        .iter()
+        .map(|(name, value)| {
+            format!(
+                "[{}] = {}, ",
+                lua_string(name.as_bytes()),
+                lua_string(value.as_bytes())
+            )
+        })
+        .collect();
+    let cluster_id = match spore_data.cluster_id().to_opt() {
+        Some(cluster_id) => lua_string(&cluster_id.raw_data()),
+        None => "nil".to_owned(),
+    };
+    format!(
+        "spore = {{ id = {}, content_type = {{ main_type = {}, sub_type = {}, params = {{ {}}} }}, content_size = {}, cluster_id = {}, mutant_id = {} }}\n",
+        lua_string(&args.spore_id),
+        lua_string(mime.main_type.as_bytes()),
+        lua_string(mime.sub_type.as_bytes()),
+        params,
+        spore_data.content().raw_data().len(),
+        cluster_id,
+        lua_string(mutant_id),
+    )
 }
 
 pub fn main(argv: &[Arg]) -> Result<(), WrappedError> {
@@ -207,7 +281,7 @@ pub fn main(argv: &[Arg]) -> Result<(), WrappedError> {
             return Err(Error::MultipleSpend.into());
         }
 
-        return match (extension_in_input.len(), extension_in_output.len()) {
+        match (extension_in_input.len(), extension_in_output.len()) {
             (0, 1) => {
                 // find it's index in Source::Output
                 let output_index =
@@ -215,58 +289,16 @@ pub fn main(argv: &[Arg]) -> Result<(), WrappedError> {
                 process_creation(output_index)
             }
             (1, 1) => {
-                return process_transfer();
+                let output_index =
+                    find_position_by_type(&extension_in_output[0], Output).unwrap_or_default();
+                process_transfer(output_index)
             }
             _ => Err(Error::InvalidExtensionOperation.into()), // Can not destroy a extension cell(for safety)
-        };
+        }
     } else {
         // execution mode
-        match argv[0].to_bytes() {
-            &[48] => {
-                // 0, CREATE SPORE
-                debug!("Spore Creation with extension!");
-                let spore_extension_index = argv[1]
-                    .to_string_lossy()
-                    .parse::<usize>()
-                    .map_err(|_| Error::InvalidLuaParameters)?;
-                let target_index = argv[2]
-                    .to_string_lossy()
-                    .parse::<usize>()
-                    .map_err(|_| Error::InvalidLuaParameters)?;
-                execute_code_create(spore_extension_index, target_index)?;
-            }
-            &[49] => {
-                // 1, TRANSFER SPORE
-                debug!("Spore Transfer with extension!");
-                let spore_extension_index = argv[1]
-                    .to_string_lossy()
-                    .parse::<usize>()
-                    .map_err(|_| Error::InvalidLuaParameters)?;
-                let input_index = argv[2]
-                    .to_string_lossy()
-                    .parse::<usize>()
-                    .map_err(|_| Error::InvalidLuaParameters)?;
-                let output_index = argv[3]
-                    .to_string_lossy()
-                    .parse::<usize>()
-                    .map_err(|_| Error::InvalidLuaParameters)?;
-                execute_code_transfer(spore_extension_index, input_index, output_index)?;
-            }
-            &[50] => {
-                // 2, DESTROY SPORE
-                debug!("Spore Destroy with extension!");
-                let spore_extension_index = argv[1]
-                    .to_string_lossy()
-                    .parse::<usize>()
-                    .map_err(|_| Error::InvalidLuaParameters)?;
-                let input_index = argv[2]
-                    .to_string_lossy()
-                    .parse::<usize>()
-                    .map_err(|_| Error::InvalidLuaParameters)?;
-                execute_code_destroy(spore_extension_index, input_index)?;
-            }
-            _ => return Err(Error::InvalidExtensionOperation.into()),
-        }
-        Ok(())
+        let argv: Vec<&CStr> = argv.iter().map(|arg| &**arg).collect();
+        let args = ExtensionArgs::parse(&argv)?;
+        execute_mutants(&args)
     }
 }
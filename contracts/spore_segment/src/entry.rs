@@ -0,0 +1,9 @@
+use core::result::Result;
+use spore_errors::error::Error;
+use spore_utils::transaction::{verify_created_segments, Syscalls};
+
+pub fn main() -> Result<(), Error> {
+    // segments are immutable and indestructible, an output with the data of an input is a transfer
+    verify_created_segments(&Syscalls::new(&[]))?;
+    Ok(())
+}
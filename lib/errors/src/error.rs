@@ -1,6 +1,7 @@
 use ckb_std::error::SysError;
 
 /// Error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(i8)]
 pub enum Error {
     IndexOutOfBound = 1,
@@ -11,6 +12,9 @@ pub enum Error {
     // common
     ClusterCellNotInDep,
     ClusterOwnershipVerifyFailed,
+    RoyaltyNotPaid,
+    InvalidPriceWitness,
+    PriceNotPaid,
 
     // spore_extension_lua errors
     ModifyExtensionPermanentField = 10,
@@ -24,6 +28,9 @@ pub enum Error {
     InvalidLuaParameters,
     FailedToLoadLuaLib,
     FailedToCreateLuaInstance,
+    InvalidExtensionArgv,
+    MutantUpgradeNotAuthorized,
+    PinnedMutantCodeNotInDep,
 
     // cluster_proxy errors
     InvalidProxyOperation = 30,
@@ -38,6 +45,9 @@ pub enum Error {
     PaymentNotEnough,
     PaymentMethodNotSupport,
     RefCellNotClusterProxy,
+    InvalidAgentData,
+    AgentExpired,
+    AgentRevoked,
 
     // cluster errors
     InvalidClusterOperation = 50,
@@ -46,6 +56,10 @@ pub enum Error {
     InvalidClusterID,
     InvalidClusterData,
     MutantNotInDeps,
+    DestroyOpenCluster,
+    ReopenClosedCluster,
+    ContentTypeNotAllowed,
+    ClusterRequiresRoyalty,
 
     // spore errors
     ModifySporePermanentField = 60,
@@ -61,6 +75,13 @@ pub enum Error {
     ExtensionCellNotInDep,
     ExtensionPaymentNotEnough,
     ClusterRequiresMutantApplied,
+    ClusterClosed,
+    ClusterSupplyExceeded,
+    ClusterMintedCountMismatch,
+    AgentQuotaExceeded,
+    AgentQuotaMismatch,
+    MultipartPartsExceeded,
+    MutantCodeMismatch, // mutants of a Spore run by different code
 
     // mime errors
     Illformed = 80,
@@ -69,6 +90,20 @@ pub enum Error {
     InvalidParams,
     InvalidParamValue,
     MutantIDNotValid,
+    MutantCodeNotValid,
+    InvalidMeltPolicy,
+    InvalidRoyalty,
+    MutantNotAllowed,
+
+    // off-chain and segmented content errors
+    InvalidContentRef = 90,
+    UnsupportedDigestAlgorithm,
+    InvalidSegmentedContent,
+    InvalidSegmentData,
+    InvalidSegmentID,
+    SegmentNotFound,
+    DuplicateSegment,
+    SegmentDigestMismatch,
 
     Unknown,
 }
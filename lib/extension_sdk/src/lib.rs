@@ -0,0 +1,99 @@
+//! Native Spore mutants, RISC-V binaries run by the spore contract without a Lua VM
+//!
+//! A native mutant cell has the mutant binary as its type script, with a Type ID args, and its
+//! type hash is the ID Spores apply the mutant by. The binary runs with no argv as the type script
+//! of the mutant cell, and through `exec` with the argv of `spore_utils::extension` whenever a Spore
+//! applying the mutant is minted, transferred or melted.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::ffi::CStr;
+
+use ckb_std::ckb_constants::Source::{CellDep, GroupInput, GroupOutput, Output};
+use ckb_std::ckb_types::prelude::*;
+use ckb_std::env::Arg;
+use ckb_std::high_level::{load_cell_data, load_cell_type, load_script, QueryIter};
+use spore_types::generated::spore_types::SporeData;
+use spore_utils::extension::verify_mutant_transfer;
+use spore_utils::{find_position_by_type, verify_type_id};
+
+pub use spore_errors::error::Error;
+pub use spore_utils::extension::{ExtensionArgs, MutantArgs, Operation, MUTANT_ID_LEN};
+
+/// Why a native mutant binary is running
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Invocation {
+    /// As the type script of its mutant cell
+    MutantCell,
+    /// From the spore contract, on a Spore operation
+    Spore(ExtensionArgs),
+}
+
+impl Invocation {
+    pub fn from_argv(argv: &[Arg]) -> Result<Self, Error> {
+        if argv.is_empty() {
+            return Ok(Invocation::MutantCell);
+        }
+        let argv: Vec<&CStr> = argv.iter().map(|arg| &**arg).collect();
+        ExtensionArgs::parse(&argv).map(Invocation::Spore)
+    }
+}
+
+/// Mutant cells are created with a Type ID, keep their data on transfer unless upgradable and
+/// can't be melted
+pub fn verify_mutant_cell() -> Result<(), Error> {
+    let inputs = QueryIter::new(load_cell_type, GroupInput).count();
+    let outputs = QueryIter::new(load_cell_type, GroupOutput).count();
+    if inputs > 1 {
+        return Err(Error::ExtensionMultipleSpend);
+    }
+    if outputs > 1 {
+        return Err(Error::ConflictExtensionCreation);
+    }
+    match (inputs, outputs) {
+        (0, 1) => {
+            let script = load_script()?;
+            let index = find_position_by_type(&script, Output).ok_or(Error::IndexOutOfBound)?;
+            if !verify_type_id(index, Output) {
+                return Err(Error::InvalidExtensionID);
+            }
+            MutantArgs::parse(&script.args().raw_data()).map(|_| ())
+        }
+        (1, 1) => verify_mutant_transfer(&MutantArgs::parse(&load_script()?.args().raw_data())?),
+        _ => Err(Error::InvalidExtensionOperation),
+    }
+}
+
+/// Data of the Spore operated, see `Operation::spore_cell`
+pub fn load_spore_data(args: &ExtensionArgs) -> Result<SporeData, Error> {
+    let (index, source) = args.operation.spore_cell();
+    let raw_data = load_cell_data(index, source)?;
+    SporeData::from_compatible_slice(&raw_data).map_err(|_| Error::InvalidNFTData)
+}
+
+/// Data of a mutant, left for native mutants to keep their settings in
+pub fn load_mutant_data(mutant_index: usize) -> Result<Vec<u8>, Error> {
+    Ok(load_cell_data(mutant_index, CellDep)?)
+}
+
+/// Runs a native mutant from its `program_entry`, checking the mutant cell or applying `rule`
+/// to a Spore operation once for each mutant run by this binary, along with the CellDeps index of
+/// its data, the mutant cell or a cell keeping its pinned data. Rules should fail with codes the
+/// spore contract doesn't use, from -127 to -1 or from 100 to 127.
+pub fn run(argv: &[Arg], mut rule: impl FnMut(&ExtensionArgs, usize) -> Result<(), i8>) -> i8 {
+    let result = match Invocation::from_argv(argv) {
+        Ok(Invocation::MutantCell) => verify_mutant_cell().map_err(|err| err as i8),
+        Ok(Invocation::Spore(args)) => args
+            .mutant_indices
+            .iter()
+            .try_for_each(|&mutant_index| rule(&args, mutant_index)),
+        Err(err) => Err(err as i8),
+    };
+    match result {
+        Ok(()) => 0,
+        Err(code) => code,
+    }
+}
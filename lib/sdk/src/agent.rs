@@ -0,0 +1,128 @@
+use ckb_types::{
+    bytes::Bytes,
+    core::TransactionView,
+    packed::{CellOutput, Script},
+    prelude::*,
+};
+
+pub use spore_utils::rules::AgentData;
+use spore_utils::{payment::Payment, rules::AGENT_QUOTA_OFFSET};
+
+use crate::{Error, Funding, LiveCell, ScriptInfo, Skeleton};
+
+/// Creates a cluster agent from a cluster proxy, paying the price set in the proxy args
+#[derive(Debug, Clone)]
+pub struct ClusterAgentCreate {
+    agent_script: ScriptInfo,
+    proxy_script: ScriptInfo,
+    proxy: LiveCell,
+    lock: Script,
+    expiry: u64,
+    quota: Option<u64>,
+    consume_proxy: bool,
+    payment_inputs: Vec<LiveCell>,
+    payment_outputs: Vec<(CellOutput, Bytes)>,
+}
+
+impl ClusterAgentCreate {
+    pub fn new(
+        agent_script: ScriptInfo,
+        proxy_script: ScriptInfo,
+        proxy: LiveCell,
+        lock: Script,
+    ) -> Self {
+        Self {
+            agent_script,
+            proxy_script,
+            proxy,
+            lock,
+            expiry: 0,
+            quota: None,
+            consume_proxy: false,
+            payment_inputs: Vec::new(),
+            payment_outputs: Vec::new(),
+        }
+    }
+
+    /// Only allowed along with `consume_proxy`
+    pub fn expiry(mut self, since: u64) -> Self {
+        self.expiry = since;
+        self
+    }
+
+    /// Only allowed along with `consume_proxy`
+    pub fn quota(mut self, quota: u64) -> Self {
+        self.quota = Some(quota);
+        self
+    }
+
+    /// Spends the proxy as its owner instead of paying for the agent
+    pub fn consume_proxy(mut self) -> Self {
+        self.consume_proxy = true;
+        self
+    }
+
+    /// Cells paying a proxy priced in xUDT, which can't be assembled from capacity alone
+    pub fn udt_payment(mut self, inputs: Vec<LiveCell>, outputs: Vec<(CellOutput, Bytes)>) -> Self {
+        self.payment_inputs = inputs;
+        self.payment_outputs = outputs;
+        self
+    }
+
+    pub fn build(self, funding: Funding) -> Result<TransactionView, Error> {
+        let mut skeleton = Skeleton::new(funding);
+        let proxy_type = self.proxy.type_script()?;
+        let cluster_id = self.proxy.data.clone();
+
+        if self.consume_proxy {
+            skeleton.input(self.proxy.clone());
+            skeleton.output(self.proxy.output.clone(), self.proxy.data.clone());
+            skeleton.cell_dep(self.proxy_script.cell_dep.clone());
+        } else {
+            // only the cluster owner, consuming the proxy, can limit the agent
+            if self.expiry != 0 || self.quota.is_some() {
+                return Err(Error::InvalidCellData);
+            }
+            let proxy_args = proxy_type.args().raw_data();
+            let payment = Payment::parse_proxy(proxy_args.get(32..).unwrap_or_default())
+                .map_err(|_| Error::PaymentMethodNotSupport)?;
+            match payment {
+                Payment::Xudt { .. } => {
+                    if self.payment_outputs.is_empty() {
+                        return Err(Error::PaymentMethodNotSupport);
+                    }
+                    self.payment_inputs
+                        .into_iter()
+                        .for_each(|cell| skeleton.input(cell));
+                    self.payment_outputs
+                        .into_iter()
+                        .for_each(|(output, data)| skeleton.output(output, data));
+                }
+                payment => {
+                    let payment = CellOutput::new_builder()
+                        .lock(self.proxy.output.lock())
+                        .capacity(payment.capacity().pack())
+                        .build();
+                    skeleton.output(payment, Bytes::new());
+                }
+            }
+        }
+        skeleton.cell_dep(self.proxy.cell_dep());
+
+        let agent_data = AgentData {
+            proxy_type_hash: proxy_type.calc_script_hash().unpack().0,
+            expiry: self.expiry,
+            quota: self.quota,
+        };
+        let agent = CellOutput::new_builder()
+            .lock(self.lock)
+            .type_(Some(self.agent_script.script(&cluster_id)).pack())
+            .build();
+        // only existing agents keep the legacy data of the proxy type hash alone
+        let mut agent_data = agent_data.to_bytes();
+        agent_data.resize(agent_data.len().max(AGENT_QUOTA_OFFSET), 0);
+        skeleton.output(agent, agent_data.into());
+        skeleton.cell_dep(self.agent_script.cell_dep);
+        skeleton.build()
+    }
+}
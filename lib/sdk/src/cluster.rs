@@ -0,0 +1,252 @@
+use ckb_types::{
+    core::TransactionView,
+    packed::{CellOutput, Script},
+    prelude::*,
+};
+use spore_types::generated::spore_types::{BytesOpt, BytesVec, BytesVecOpt, ClusterDataV2};
+
+use crate::{Error, Funding, LiveCell, ScriptInfo, Skeleton};
+
+pub use spore_utils::rules::Royalty;
+
+/// Creates a cluster
+#[derive(Debug, Clone)]
+pub struct ClusterCreate {
+    cluster_script: ScriptInfo,
+    lock: Script,
+    name: Vec<u8>,
+    description: Vec<u8>,
+    mutant: Option<LiveCell>,
+    required_mutants: Vec<LiveCell>,
+    allowed_mutants: Option<Vec<LiveCell>>,
+    extension_lua: Option<ScriptInfo>,
+    max_supply: Option<u64>,
+    allowed_content_types: Option<Vec<String>>,
+    royalty: Option<Royalty>,
+}
+
+impl ClusterCreate {
+    pub fn new(cluster_script: ScriptInfo, lock: Script, name: &str, description: &str) -> Self {
+        Self {
+            cluster_script,
+            lock,
+            name: name.as_bytes().to_vec(),
+            description: description.as_bytes().to_vec(),
+            mutant: None,
+            required_mutants: Vec::new(),
+            allowed_mutants: None,
+            extension_lua: None,
+            max_supply: None,
+            allowed_content_types: None,
+            royalty: None,
+        }
+    }
+
+    /// Requires every Spore minted into the cluster to apply the mutant
+    pub fn mutant(mut self, mutant: LiveCell) -> Self {
+        self.mutant = Some(mutant);
+        self
+    }
+
+    /// Adds a mutant to `required_mutants`, which every Spore minted into the cluster applies
+    pub fn required_mutant(mut self, mutant: LiveCell) -> Self {
+        self.required_mutants.push(mutant);
+        self
+    }
+
+    /// Restricts the mutants of Spores minted into the cluster to the required ones and these
+    pub fn allowed_mutants(mut self, mutants: Vec<LiveCell>) -> Self {
+        self.allowed_mutants = Some(mutants);
+        self
+    }
+
+    /// The Spore Lua extension, telling listed Lua mutants from native ones, which are all listed
+    /// mutants without it
+    pub fn extension_lua(mut self, extension_lua: ScriptInfo) -> Self {
+        self.extension_lua = Some(extension_lua);
+        self
+    }
+
+    pub fn max_supply(mut self, max_supply: u64) -> Self {
+        self.max_supply = Some(max_supply);
+        self
+    }
+
+    /// Restricts Spores minted into the cluster to content-types like `image/png` or `image/*`
+    pub fn allowed_content_types(mut self, content_types: &[&str]) -> Self {
+        self.allowed_content_types = Some(content_types.iter().map(|s| s.to_string()).collect());
+        self
+    }
+
+    /// Royalty paid on transfers by Spores minted into the cluster, which declare `royalty-to=cluster`
+    pub fn royalty(mut self, royalty: Royalty) -> Self {
+        self.royalty = Some(royalty);
+        self
+    }
+
+    pub fn build(self, funding: Funding) -> Result<TransactionView, Error> {
+        let mut skeleton = Skeleton::new(funding);
+        let mut mutant_id = BytesOpt::default();
+        if let Some(mutant) = self.mutant {
+            let mutant_args = mutant.type_args()?;
+            let id = mutant_args.get(..32).ok_or(Error::InvalidCellData)?;
+            mutant_id = BytesOpt::new_builder().set(Some(id.into())).build();
+            skeleton.cell_dep(mutant.cell_dep());
+        }
+        let extension_lua = self.extension_lua.as_ref();
+        let required_mutants = match self.required_mutants.is_empty() {
+            true => None,
+            false => Some(mutant_ids(
+                &mut skeleton,
+                &self.required_mutants,
+                extension_lua,
+            )?),
+        };
+        let allowed_mutants = match &self.allowed_mutants {
+            Some(mutants) => Some(mutant_ids(&mut skeleton, mutants, extension_lua)?),
+            None => None,
+        };
+        let cluster_data = ClusterDataV2::new_builder()
+            .name(self.name.as_slice().into())
+            .description(self.description.as_slice().into())
+            .mutant_id(mutant_id)
+            .max_supply(self.max_supply.into())
+            .allowed_content_types(allowed_content_types(self.allowed_content_types))
+            .royalty(
+                BytesOpt::new_builder()
+                    .set(
+                        self.royalty
+                            .map(|royalty| royalty.to_bytes().as_slice().into()),
+                    )
+                    .build(),
+            )
+            .required_mutants(BytesVecOpt::new_builder().set(required_mutants).build())
+            .allowed_mutants(BytesVecOpt::new_builder().set(allowed_mutants).build())
+            .build();
+
+        let cluster_id = skeleton.next_type_id()?;
+        let cluster = CellOutput::new_builder()
+            .lock(self.lock)
+            .type_(Some(self.cluster_script.script(&cluster_id)).pack())
+            .build();
+        skeleton.output(cluster, cluster_data.as_bytes());
+        skeleton.cell_dep(self.cluster_script.cell_dep);
+        skeleton.build()
+    }
+}
+
+// IDs of listed mutants, which the cluster contract looks up in CellDep
+fn mutant_ids(
+    skeleton: &mut Skeleton,
+    mutants: &[LiveCell],
+    extension_lua: Option<&ScriptInfo>,
+) -> Result<BytesVec, Error> {
+    let mut ids = Vec::new();
+    for mutant in mutants {
+        let id = match extension_lua {
+            Some(extension_lua) => mutant.mutant_id(extension_lua)?,
+            None => mutant.type_script()?.calc_script_hash().unpack().0,
+        };
+        ids.push(id.as_slice().into());
+        skeleton.cell_dep(mutant.cell_dep());
+    }
+    Ok(BytesVec::new_builder().set(ids).build())
+}
+
+fn allowed_content_types(content_types: Option<Vec<String>>) -> BytesVecOpt {
+    let content_types = content_types.map(|content_types| {
+        BytesVec::new_builder()
+            .set(
+                content_types
+                    .iter()
+                    .map(|content_type| content_type.as_bytes().into())
+                    .collect(),
+            )
+            .build()
+    });
+    BytesVecOpt::new_builder().set(content_types).build()
+}
+
+/// Updates the description or the lock of a cluster, or closes it
+#[derive(Debug, Clone)]
+pub struct ClusterUpdate {
+    cluster_script: ScriptInfo,
+    cluster: LiveCell,
+    lock: Option<Script>,
+    description: Option<Vec<u8>>,
+    close: bool,
+}
+
+impl ClusterUpdate {
+    pub fn new(cluster_script: ScriptInfo, cluster: LiveCell) -> Self {
+        Self {
+            cluster_script,
+            cluster,
+            lock: None,
+            description: None,
+            close: false,
+        }
+    }
+
+    pub fn lock(mut self, lock: Script) -> Self {
+        self.lock = Some(lock);
+        self
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = Some(description.as_bytes().to_vec());
+        self
+    }
+
+    pub fn close(mut self) -> Self {
+        self.close = true;
+        self
+    }
+
+    pub fn build(self, funding: Funding) -> Result<TransactionView, Error> {
+        let mut skeleton = Skeleton::new(funding);
+        let cluster_data = self.cluster.cluster_data()?;
+        let mut builder = cluster_data.as_builder();
+        if let Some(description) = self.description {
+            builder = builder.description(description.as_slice().into());
+        }
+        if self.close {
+            builder = builder.closed(true.into());
+        }
+
+        let mut output = self.cluster.output.clone();
+        if let Some(lock) = self.lock {
+            output = output.as_builder().lock(lock).build();
+        }
+        skeleton.input(self.cluster);
+        skeleton.output(output, builder.build().as_bytes());
+        skeleton.cell_dep(self.cluster_script.cell_dep);
+        skeleton.build()
+    }
+}
+
+/// Destroys a closed cluster, its capacity goes back to the change lock
+#[derive(Debug, Clone)]
+pub struct ClusterDestroy {
+    cluster_script: ScriptInfo,
+    cluster: LiveCell,
+}
+
+impl ClusterDestroy {
+    pub fn new(cluster_script: ScriptInfo, cluster: LiveCell) -> Self {
+        Self {
+            cluster_script,
+            cluster,
+        }
+    }
+
+    pub fn build(self, funding: Funding) -> Result<TransactionView, Error> {
+        if !bool::from(self.cluster.cluster_data()?.closed()) {
+            return Err(Error::ClusterNotClosed);
+        }
+        let mut skeleton = Skeleton::new(funding);
+        skeleton.input(self.cluster);
+        skeleton.cell_dep(self.cluster_script.cell_dep);
+        skeleton.build()
+    }
+}
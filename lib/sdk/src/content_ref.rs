@@ -0,0 +1,65 @@
+//! Off-chain media referenced by Spores under `application/x-spore-ref`
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use ckb_hash::new_blake2b;
+use ckb_types::prelude::*;
+use spore_types::generated::spore_types::SporeData;
+use spore_utils::content_ref::parse_content_ref;
+pub use spore_utils::content_ref::{ContentRef, CONTENT_REF_DIGEST_LEN};
+use spore_utils::MIME;
+
+use crate::{Error, LiveCell};
+
+pub const CONTENT_REF_CONTENT_TYPE: &str = "application/x-spore-ref;hash=blake2b";
+
+/// The content of a Spore referencing media by its digest, see `file_digest`
+pub fn content_ref(digest: &[u8; CONTENT_REF_DIGEST_LEN], uri: &str) -> Vec<u8> {
+    [digest.as_slice(), uri.as_bytes()].concat()
+}
+
+/// The blake2b digest of a local file, read in chunks
+pub fn file_digest(path: impl AsRef<Path>) -> Result<[u8; CONTENT_REF_DIGEST_LEN], Error> {
+    let mut file = File::open(path).map_err(|err| Error::ContentUnreadable(err.kind()))?;
+    let mut blake2b = new_blake2b();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let len = file
+            .read(&mut buffer)
+            .map_err(|err| Error::ContentUnreadable(err.kind()))?;
+        if len == 0 {
+            break;
+        }
+        blake2b.update(&buffer[..len]);
+    }
+    let mut digest = [0; CONTENT_REF_DIGEST_LEN];
+    blake2b.finalize(&mut digest);
+    Ok(digest)
+}
+
+/// Checks a local copy of the media referenced by a Spore content against its digest
+pub fn verify_local_content(
+    content_type: &[u8],
+    content: &[u8],
+    path: impl AsRef<Path>,
+) -> Result<(), Error> {
+    let mime = MIME::parse(content_type).map_err(|_| Error::InvalidCellData)?;
+    let content_ref = parse_content_ref(&mime, content).map_err(|_| Error::InvalidCellData)?;
+    if file_digest(path)? != content_ref.digest {
+        return Err(Error::ContentDigestMismatch);
+    }
+    Ok(())
+}
+
+/// Same as `verify_local_content`, for a live Spore cell
+pub fn verify_spore_content(spore: &LiveCell, path: impl AsRef<Path>) -> Result<(), Error> {
+    let spore_data =
+        SporeData::from_compatible_slice(&spore.data).map_err(|_| Error::InvalidCellData)?;
+    verify_local_content(
+        spore_data.content_type().unpack(),
+        spore_data.content().unpack(),
+        path,
+    )
+}
@@ -0,0 +1,266 @@
+//! Off-chain builders assembling Spore Protocol transactions ready for signing
+//!
+//! Payments, melt refunds and royalties are paid in outputs of their own. An amount below the
+//! occupied capacity of such a cell is raised to it, so the receiver gets more than asked rather
+//! than an output the chain would refuse.
+
+use ckb_types::{
+    bytes::Bytes,
+    core::{Capacity, ScriptHashType, TransactionBuilder, TransactionView},
+    packed::{Byte32, CellDep, CellInput, CellOutput, OutPoint, Script},
+    prelude::*,
+};
+use spore_types::generated::spore_types::ClusterDataV2;
+use spore_utils::{calc_type_id, parse_cluster_data};
+
+pub mod agent;
+pub mod cluster;
+pub mod content_ref;
+pub mod mutant;
+pub mod proxy;
+pub mod segment;
+pub mod spore;
+pub mod validator;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    NoInput, // type ids are derived from the first input
+    InsufficientCapacity,
+    InvalidCellData,
+    MissingTypeScript,
+    InvalidMutantArgs,
+    ClusterClosed,
+    ClusterNotClosed,
+    ClusterSupplyExceeded,
+    AgentQuotaExceeded,
+    PaymentMethodNotSupport,
+    ContentUnreadable(std::io::ErrorKind),
+    ContentDigestMismatch,
+    InvalidSegments,
+    MissingRefundLock,  // the lock of a melt policy isn't given
+    MissingRoyaltyLock, // the lock of a royalty, or the cluster setting it, isn't given
+    MissingCluster,     // the cluster of an agent, a lock proxy or `melt-to=cluster` isn't given
+}
+
+/// A deployed contract, referenced by its code hash and the cell dep carrying its binary
+#[derive(Debug, Clone)]
+pub struct ScriptInfo {
+    pub code_hash: Byte32,
+    pub hash_type: ScriptHashType,
+    pub cell_dep: CellDep,
+}
+
+impl ScriptInfo {
+    pub fn new(code_hash: Byte32, hash_type: ScriptHashType, cell_dep: CellDep) -> Self {
+        Self {
+            code_hash,
+            hash_type,
+            cell_dep,
+        }
+    }
+
+    pub fn script(&self, args: &[u8]) -> Script {
+        Script::new_builder()
+            .code_hash(self.code_hash.clone())
+            .hash_type(self.hash_type.into())
+            .args(args.pack())
+            .build()
+    }
+}
+
+/// A live cell with its data, the block hash is only required by time-limited cluster agents
+#[derive(Debug, Clone)]
+pub struct LiveCell {
+    pub out_point: OutPoint,
+    pub output: CellOutput,
+    pub data: Bytes,
+    pub block_hash: Option<Byte32>,
+}
+
+impl LiveCell {
+    pub fn new(out_point: OutPoint, output: CellOutput, data: Bytes) -> Self {
+        Self {
+            out_point,
+            output,
+            data,
+            block_hash: None,
+        }
+    }
+
+    pub fn block_hash(mut self, block_hash: Byte32) -> Self {
+        self.block_hash = Some(block_hash);
+        self
+    }
+
+    pub fn type_script(&self) -> Result<Script, Error> {
+        self.output.type_().to_opt().ok_or(Error::MissingTypeScript)
+    }
+
+    pub fn type_args(&self) -> Result<Bytes, Error> {
+        Ok(self.type_script()?.args().raw_data())
+    }
+
+    pub fn cell_dep(&self) -> CellDep {
+        CellDep::new_builder()
+            .out_point(self.out_point.clone())
+            .build()
+    }
+
+    /// ID of the mutant of this cell, the start of the type args of a Lua mutant run by
+    /// `extension_lua` or the type hash of a native one
+    pub fn mutant_id(&self, extension_lua: &ScriptInfo) -> Result<[u8; 32], Error> {
+        let type_script = self.type_script()?;
+        if type_script.code_hash() != extension_lua.code_hash {
+            return Ok(type_script.calc_script_hash().unpack().0);
+        }
+        let args = type_script.args().raw_data();
+        args.get(..32)
+            .and_then(|id| id.try_into().ok())
+            .ok_or(Error::InvalidCellData)
+    }
+
+    pub(crate) fn cluster_data(&self) -> Result<ClusterDataV2, Error> {
+        parse_cluster_data(&self.data).map_err(|_| Error::InvalidCellData)
+    }
+}
+
+/// Cells paying for the transaction, the capacity left after the fee goes to the change lock
+#[derive(Debug, Clone)]
+pub struct Funding {
+    pub cells: Vec<LiveCell>,
+    pub change_lock: Script,
+    pub fee: u64,
+}
+
+impl Funding {
+    pub fn new(cells: Vec<LiveCell>, change_lock: Script, fee: u64) -> Self {
+        Self {
+            cells,
+            change_lock,
+            fee,
+        }
+    }
+}
+
+pub(crate) fn occupied_capacity(output: &CellOutput, data: &[u8]) -> u64 {
+    Capacity::bytes(data.len())
+        .and_then(|data_capacity| output.occupied_capacity(data_capacity))
+        .map(|capacity| capacity.as_u64())
+        .unwrap_or(u64::MAX)
+}
+
+/// Transaction under construction, shared by all builders
+pub(crate) struct Skeleton {
+    funding: Funding,
+    inputs: Vec<LiveCell>,
+    witnesses: Vec<Bytes>,
+    outputs: Vec<(CellOutput, Bytes)>,
+    cell_deps: Vec<CellDep>,
+    header_deps: Vec<Byte32>,
+}
+
+impl Skeleton {
+    pub(crate) fn new(funding: Funding) -> Self {
+        Self {
+            funding,
+            inputs: Vec::new(),
+            witnesses: Vec::new(),
+            outputs: Vec::new(),
+            cell_deps: Vec::new(),
+            header_deps: Vec::new(),
+        }
+    }
+
+    pub(crate) fn input(&mut self, cell: LiveCell) {
+        self.input_with_witness(cell, Bytes::new());
+    }
+
+    pub(crate) fn input_with_witness(&mut self, cell: LiveCell, witness: Bytes) {
+        self.inputs.push(cell);
+        self.witnesses.push(witness);
+    }
+
+    /// Pushes an output, raising its capacity to the occupied capacity if needed, which overpays
+    /// payments smaller than a cell
+    pub(crate) fn output(&mut self, output: CellOutput, data: Bytes) {
+        let occupied = occupied_capacity(&output, &data);
+        let capacity: u64 = output.capacity().unpack();
+        let output = output
+            .as_builder()
+            .capacity(capacity.max(occupied).pack())
+            .build();
+        self.outputs.push((output, data));
+    }
+
+    pub(crate) fn cell_dep(&mut self, cell_dep: CellDep) {
+        if !self.cell_deps.contains(&cell_dep) {
+            self.cell_deps.push(cell_dep);
+        }
+    }
+
+    pub(crate) fn header_dep(&mut self, block_hash: Byte32) {
+        if !self.header_deps.contains(&block_hash) {
+            self.header_deps.push(block_hash);
+        }
+    }
+
+    /// Type ID of the next output, every input has to be pushed before
+    pub(crate) fn next_type_id(&self) -> Result<[u8; 32], Error> {
+        let first_input = self
+            .inputs
+            .first()
+            .or(self.funding.cells.first())
+            .ok_or(Error::NoInput)?;
+        let first_input = CellInput::new(first_input.out_point.clone(), 0);
+        Ok(calc_type_id(first_input.as_slice(), self.outputs.len()))
+    }
+
+    pub(crate) fn build(mut self) -> Result<TransactionView, Error> {
+        let mut inputs = self.inputs;
+        inputs.append(&mut self.funding.cells);
+
+        let input_capacity: u64 = inputs
+            .iter()
+            .map(|cell| Unpack::<u64>::unpack(&cell.output.capacity()))
+            .sum();
+        let output_capacity: u64 = self
+            .outputs
+            .iter()
+            .map(|(output, _)| Unpack::<u64>::unpack(&output.capacity()))
+            .sum();
+        let change = input_capacity
+            .checked_sub(output_capacity)
+            .and_then(|change| change.checked_sub(self.funding.fee))
+            .ok_or(Error::InsufficientCapacity)?;
+        if change > 0 {
+            let change_output = CellOutput::new_builder()
+                .lock(self.funding.change_lock)
+                .capacity(change.pack())
+                .build();
+            if change < occupied_capacity(&change_output, &[]) {
+                return Err(Error::InsufficientCapacity);
+            }
+            self.outputs.push((change_output, Bytes::new()));
+        }
+
+        let mut witnesses = self.witnesses;
+        witnesses.resize(inputs.len(), Bytes::new());
+        let witnesses: Vec<_> = witnesses.iter().map(|witness| witness.pack()).collect();
+        let inputs = inputs
+            .into_iter()
+            .map(|cell| CellInput::new(cell.out_point, 0));
+        let (outputs, outputs_data): (Vec<_>, Vec<_>) = self
+            .outputs
+            .into_iter()
+            .map(|(output, data)| (output, data.pack()))
+            .unzip();
+        Ok(TransactionBuilder::default()
+            .inputs(inputs)
+            .outputs(outputs)
+            .outputs_data(outputs_data)
+            .cell_deps(self.cell_deps)
+            .header_deps(self.header_deps)
+            .witnesses(witnesses)
+            .build())
+    }
+}
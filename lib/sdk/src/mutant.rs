@@ -0,0 +1,74 @@
+use ckb_types::{
+    bytes::Bytes,
+    core::TransactionView,
+    packed::{CellDep, CellOutput, Script},
+    prelude::*,
+};
+
+use spore_utils::extension::NO_PAYMENT;
+use spore_utils::payment::{to_descriptors, Payment};
+
+use crate::{Error, Funding, ScriptInfo, Skeleton};
+
+/// Creates a Lua mutant, optionally charging every Spore applying it
+#[derive(Debug, Clone)]
+pub struct MutantCreate {
+    mutant_script: ScriptInfo,
+    lua_lib: CellDep,
+    lock: Script,
+    code: Bytes,
+    payment: Vec<u8>,
+}
+
+impl MutantCreate {
+    pub fn new(mutant_script: ScriptInfo, lua_lib: CellDep, lock: Script, code: &[u8]) -> Self {
+        Self {
+            mutant_script,
+            lua_lib,
+            lock,
+            code: Bytes::copy_from_slice(code),
+            payment: Vec::new(),
+        }
+    }
+
+    /// Charges 10^power shannons on mint
+    pub fn payment(mut self, power: u8) -> Self {
+        self.payment = Vec::from([power]);
+        self
+    }
+
+    /// Charges on mint, transfer and melt, each free if `None`. Powers of ten keep the legacy
+    /// bytes, other payments need a versioned descriptor and a 32-byte upgrade authority field
+    pub fn operation_payments(
+        mut self,
+        mint: Option<Payment>,
+        transfer: Option<Payment>,
+        melt: Option<Payment>,
+    ) -> Self {
+        let payments = [mint, transfer, melt];
+        self.payment = payments
+            .iter()
+            .map(|payment| match payment {
+                Some(Payment::PowerOfTen(power)) => Some(*power),
+                Some(_) => None,
+                None => Some(NO_PAYMENT),
+            })
+            .collect::<Option<Vec<u8>>>()
+            .unwrap_or_else(|| [&[0u8; 32][..], &to_descriptors(&payments)].concat());
+        self
+    }
+
+    pub fn build(self, funding: Funding) -> Result<TransactionView, Error> {
+        let mut skeleton = Skeleton::new(funding);
+        let mut mutant_args = skeleton.next_type_id()?.to_vec();
+        mutant_args.extend(self.payment);
+        let mutant = CellOutput::new_builder()
+            .lock(self.lock)
+            .type_(Some(self.mutant_script.script(&mutant_args)).pack())
+            .build();
+        skeleton.output(mutant, self.code);
+        skeleton.cell_dep(self.mutant_script.cell_dep);
+        skeleton.cell_dep(self.lua_lib);
+        skeleton.build()
+    }
+}
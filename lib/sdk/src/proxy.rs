@@ -0,0 +1,73 @@
+use ckb_types::{
+    core::TransactionView,
+    packed::{CellOutput, Script},
+    prelude::*,
+};
+
+pub use spore_utils::payment::Payment;
+
+use crate::{Error, Funding, LiveCell, ScriptInfo, Skeleton};
+
+/// Creates a cluster proxy, as the cluster owner or through a lock proxy of the cluster
+#[derive(Debug, Clone)]
+pub struct ClusterProxyCreate {
+    proxy_script: ScriptInfo,
+    cluster_script: ScriptInfo,
+    cluster: LiveCell,
+    lock: Script,
+    payment: Option<Payment>,
+    lock_proxy: Option<LiveCell>,
+}
+
+impl ClusterProxyCreate {
+    pub fn new(
+        proxy_script: ScriptInfo,
+        cluster_script: ScriptInfo,
+        cluster: LiveCell,
+        lock: Script,
+    ) -> Self {
+        Self {
+            proxy_script,
+            cluster_script,
+            cluster,
+            lock,
+            payment: None,
+            lock_proxy: None,
+        }
+    }
+
+    pub fn payment(mut self, payment: Payment) -> Self {
+        self.payment = Some(payment);
+        self
+    }
+
+    pub fn lock_proxy(mut self, lock_proxy: LiveCell) -> Self {
+        self.lock_proxy = Some(lock_proxy);
+        self
+    }
+
+    pub fn build(self, funding: Funding) -> Result<TransactionView, Error> {
+        let mut skeleton = Skeleton::new(funding);
+        let cluster_id = self.cluster.type_args()?;
+        skeleton.cell_dep(self.cluster.cell_dep());
+
+        let authority = self.lock_proxy.unwrap_or_else(|| {
+            skeleton.cell_dep(self.cluster_script.cell_dep.clone());
+            self.cluster.clone()
+        });
+        skeleton.input(authority.clone());
+        skeleton.output(authority.output, authority.data);
+
+        let mut proxy_args = skeleton.next_type_id()?.to_vec();
+        if let Some(payment) = self.payment {
+            proxy_args.extend(payment.to_args());
+        }
+        let proxy = CellOutput::new_builder()
+            .lock(self.lock)
+            .type_(Some(self.proxy_script.script(&proxy_args)).pack())
+            .build();
+        skeleton.output(proxy, cluster_id);
+        skeleton.cell_dep(self.proxy_script.cell_dep);
+        skeleton.build()
+    }
+}
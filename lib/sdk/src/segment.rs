@@ -0,0 +1,143 @@
+//! Content too large for a single cell, stored in segment cells over several transactions
+
+use std::ops::Range;
+
+use ckb_hash::blake2b_256;
+use ckb_types::{
+    bytes::Bytes,
+    core::TransactionView,
+    packed::{CellOutput, Script},
+    prelude::*,
+};
+use spore_errors::error::Error as Rule;
+pub use spore_utils::segment::{Segment, SegmentedContent};
+use spore_utils::MIME;
+
+use crate::{Error, Funding, LiveCell, ScriptInfo, Skeleton};
+
+pub const SEGMENTED_CONTENT_TYPE: &str = "application/x-spore-segments;hash=blake2b";
+
+/// A content split into chunks, one per segment cell
+#[derive(Debug, Clone)]
+pub struct SegmentPlan {
+    content_digest: [u8; 32],
+    chunks: Vec<Vec<u8>>,
+}
+
+impl SegmentPlan {
+    pub fn new(content: &[u8], chunk_size: usize) -> Self {
+        Self {
+            content_digest: blake2b_256(content),
+            chunks: content
+                .chunks(chunk_size.max(1))
+                .map(|chunk| chunk.to_vec())
+                .collect(),
+        }
+    }
+
+    pub fn total(&self) -> u32 {
+        self.chunks.len() as u32
+    }
+
+    /// The content of a Spore committing to the segments, under `SEGMENTED_CONTENT_TYPE`
+    pub fn spore_content(&self, segments_id: &[u8]) -> Vec<u8> {
+        let mut content = [segments_id, &self.content_digest].concat();
+        self.chunks
+            .iter()
+            .for_each(|chunk| content.extend_from_slice(&blake2b_256(chunk)));
+        content
+    }
+
+    fn segment_data(&self, index: u32) -> Vec<u8> {
+        Segment {
+            index,
+            total: self.total(),
+            content_digest: &self.content_digest,
+            chunk: &self.chunks[index as usize],
+        }
+        .to_bytes()
+    }
+}
+
+/// Creates a range of the segments of a plan, each segment has to follow one already created
+#[derive(Debug, Clone)]
+pub struct SegmentCreate {
+    segment_script: ScriptInfo,
+    lock: Script,
+    plan: SegmentPlan,
+    range: Range<u32>,
+    segments: Vec<LiveCell>,
+}
+
+impl SegmentCreate {
+    pub fn new(
+        segment_script: ScriptInfo,
+        lock: Script,
+        plan: SegmentPlan,
+        range: Range<u32>,
+    ) -> Self {
+        Self {
+            segment_script,
+            lock,
+            plan,
+            range,
+            segments: Vec::new(),
+        }
+    }
+
+    /// A segment created before, the range completing the content needs all of them
+    pub fn segment(mut self, segment: LiveCell) -> Self {
+        self.segments.push(segment);
+        self
+    }
+
+    pub fn build(self, funding: Funding) -> Result<TransactionView, Error> {
+        if self.range.is_empty() || self.range.end > self.plan.total() {
+            return Err(Error::InvalidSegments);
+        }
+        let mut skeleton = Skeleton::new(funding);
+        // the first segment sets the segments id, as a Type ID
+        let segments_id = if self.range.start == 0 {
+            skeleton.next_type_id()?.to_vec()
+        } else {
+            let segment = self.segments.first().ok_or(Error::InvalidSegments)?;
+            segment.type_args()?.to_vec()
+        };
+        for index in self.range {
+            let output = CellOutput::new_builder()
+                .lock(self.lock.clone())
+                .type_(Some(self.segment_script.script(&segments_id)).pack())
+                .build();
+            skeleton.output(output, Bytes::from(self.plan.segment_data(index)));
+        }
+        for segment in &self.segments {
+            skeleton.cell_dep(segment.cell_dep());
+        }
+        skeleton.cell_dep(self.segment_script.cell_dep);
+        skeleton.build()
+    }
+}
+
+/// Reassembles the content of a segmented Spore from its segment cells, checking their digests
+pub fn assemble_content(
+    content_type: &[u8],
+    content: &[u8],
+    segments: &[LiveCell],
+) -> Result<Vec<u8>, Error> {
+    let mime = MIME::parse(content_type).map_err(|_| Error::InvalidCellData)?;
+    let segmented = SegmentedContent::parse(&mime, content).map_err(|_| Error::InvalidCellData)?;
+    let segments: Vec<Segment> = segments
+        .iter()
+        .filter(|cell| cell.type_args().ok().as_deref() == Some(segmented.segments_id))
+        .filter_map(|cell| Segment::parse(&cell.data).ok())
+        .collect();
+    let segments = segmented.verify(&segments).map_err(|rule| match rule {
+        Rule::SegmentDigestMismatch => Error::ContentDigestMismatch,
+        _ => Error::InvalidSegments,
+    })?;
+    Ok(segments
+        .iter()
+        .flat_map(|segment| segment.chunk)
+        .copied()
+        .collect())
+}
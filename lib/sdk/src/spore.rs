@@ -0,0 +1,452 @@
+use ckb_types::{
+    bytes::Bytes,
+    core::TransactionView,
+    packed::{self, CellDep, CellOutput, Script, WitnessArgs},
+    prelude::*,
+};
+use spore_types::generated::spore_types::SporeData;
+use spore_types::NativeNFTData;
+use spore_utils::extension::{MutantArgs, OP_MELT, OP_MINT, OP_TRANSFER};
+use spore_utils::payment::Payment;
+use spore_utils::rules::{MeltPolicy, MeltTarget, Royalty, RoyaltyTerms};
+use spore_utils::MIME;
+
+use crate::agent::AgentData;
+use crate::{Error, Funding, LiveCell, ScriptInfo, Skeleton};
+
+/// A cluster agent with the proxy it was created from
+#[derive(Debug, Clone)]
+pub struct AgentRef {
+    pub agent_script: ScriptInfo,
+    pub agent: LiveCell,
+    pub proxy: LiveCell,
+}
+
+#[derive(Debug, Clone)]
+struct ClusterRef {
+    cluster_script: ScriptInfo,
+    cluster: LiveCell,
+    agent: Option<AgentRef>,
+    lock_proxy: Option<LiveCell>,
+}
+
+/// Adds mutant cell deps, and pays mutants asking for a payment in capacity on the operation of
+/// code `op` to their lock, xUDT payments being unsupported
+pub(crate) fn apply_mutants(
+    skeleton: &mut Skeleton,
+    mutants: &[LiveCell],
+    op: u8,
+) -> Result<(), Error> {
+    for mutant in mutants {
+        skeleton.cell_dep(mutant.cell_dep());
+        let args = mutant.type_args()?;
+        let mutant_args = MutantArgs::parse(&args).map_err(|_| Error::InvalidMutantArgs)?;
+        match mutant_args.payment(op) {
+            Some(Payment::Xudt { .. }) => return Err(Error::PaymentMethodNotSupport),
+            Some(payment) => {
+                let payment = CellOutput::new_builder()
+                    .lock(mutant.output.lock())
+                    .capacity(payment.capacity().pack())
+                    .build();
+                skeleton.output(payment, Bytes::new());
+            }
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+/// Mints a Spore, into a cluster if set. The agent and lock proxy can be given in any order,
+/// but only along with the cluster
+#[derive(Debug, Clone)]
+pub struct SporeMint {
+    spore_script: ScriptInfo,
+    lock: Script,
+    content_type: String,
+    content: Vec<u8>,
+    cluster: Option<(ScriptInfo, LiveCell)>,
+    agent: Option<AgentRef>,
+    lock_proxy: Option<LiveCell>,
+    mutants: Vec<LiveCell>,
+    cell_deps: Vec<CellDep>,
+}
+
+impl SporeMint {
+    pub fn new(spore_script: ScriptInfo, lock: Script, content_type: &str, content: &[u8]) -> Self {
+        Self {
+            spore_script,
+            lock,
+            content_type: content_type.to_owned(),
+            content: content.to_vec(),
+            cluster: None,
+            agent: None,
+            lock_proxy: None,
+            mutants: Vec::new(),
+            cell_deps: Vec::new(),
+        }
+    }
+
+    pub fn cluster(mut self, cluster_script: ScriptInfo, cluster: LiveCell) -> Self {
+        self.cluster = Some((cluster_script, cluster));
+        self
+    }
+
+    /// Mints through a cluster agent instead of the cluster owner
+    pub fn agent(mut self, agent: AgentRef) -> Self {
+        self.agent = Some(agent);
+        self
+    }
+
+    /// A cell with the lock of the cluster, or of the agent if set, to be spent as a lock proxy
+    pub fn lock_proxy(mut self, lock_proxy: LiveCell) -> Self {
+        self.lock_proxy = Some(lock_proxy);
+        self
+    }
+
+    pub fn mutant(mut self, mutant: LiveCell) -> Self {
+        self.mutants.push(mutant);
+        self
+    }
+
+    /// Extra cell deps, such as the Lua library run by mutants
+    pub fn cell_dep(mut self, cell_dep: CellDep) -> Self {
+        self.cell_deps.push(cell_dep);
+        self
+    }
+
+    pub fn build(self, funding: Funding) -> Result<TransactionView, Error> {
+        let mut skeleton = Skeleton::new(funding);
+        let cluster_id = match self.cluster {
+            Some((cluster_script, cluster)) => {
+                let cluster = ClusterRef {
+                    cluster_script,
+                    cluster,
+                    agent: self.agent,
+                    lock_proxy: self.lock_proxy,
+                };
+                Some(authorize_cluster(&mut skeleton, &cluster)?)
+            }
+            None if self.agent.is_some() || self.lock_proxy.is_some() => {
+                return Err(Error::MissingCluster)
+            }
+            None => None,
+        };
+        apply_mutants(&mut skeleton, &self.mutants, OP_MINT)?;
+
+        let spore_data = SporeData::from(NativeNFTData {
+            content_type: self.content_type,
+            content: self.content,
+            cluster_id,
+        });
+        let spore_id = skeleton.next_type_id()?;
+        let spore = CellOutput::new_builder()
+            .lock(self.lock)
+            .type_(Some(self.spore_script.script(&spore_id)).pack())
+            .build();
+        skeleton.output(spore, spore_data.as_bytes());
+        skeleton.cell_dep(self.spore_script.cell_dep);
+        self.cell_deps
+            .into_iter()
+            .for_each(|cell_dep| skeleton.cell_dep(cell_dep));
+        skeleton.build()
+    }
+}
+
+/// Picks the cheapest authorization the given cells allow, returning the cluster id
+fn authorize_cluster(skeleton: &mut Skeleton, cluster: &ClusterRef) -> Result<Vec<u8>, Error> {
+    let cluster_id = cluster.cluster.type_args()?.to_vec();
+    let cluster_data = cluster.cluster.cluster_data()?;
+    if bool::from(cluster_data.closed()) {
+        return Err(Error::ClusterClosed);
+    }
+    skeleton.cell_dep(cluster.cluster.cell_dep());
+
+    let max_supply = Option::<u64>::from(cluster_data.max_supply());
+    let agent = match &cluster.agent {
+        Some(agent) => {
+            let agent_data =
+                AgentData::parse(&agent.agent.data).map_err(|_| Error::InvalidCellData)?;
+            Some((agent, agent_data))
+        }
+        None => None,
+    };
+    match (agent, &cluster.lock_proxy) {
+        // Condition 1: the cluster owner mints, a limited cluster has to update its minted counter
+        (_, _) if max_supply.is_some() => {
+            let minted = u64::from(cluster_data.minted()) + 1;
+            if Some(minted) > max_supply {
+                return Err(Error::ClusterSupplyExceeded);
+            }
+            let output_data = cluster_data.as_builder().minted(minted.into()).build();
+            pass_through(skeleton, &cluster.cluster, output_data.as_bytes());
+            skeleton.cell_dep(cluster.cluster_script.cell_dep.clone());
+        }
+        (None, None) => {
+            pass_through(skeleton, &cluster.cluster, cluster.cluster.data.clone());
+            skeleton.cell_dep(cluster.cluster_script.cell_dep.clone());
+        }
+        // Condition 3: use the agent by a lock proxy, unless its quota has to be updated
+        (Some((agent, agent_data)), Some(lock_proxy)) if agent_data.quota.is_none() => {
+            if agent_data.expiry != 0 {
+                if let Some(block_hash) = agent.agent.block_hash.clone() {
+                    skeleton.header_dep(block_hash);
+                }
+            }
+            skeleton.cell_dep(agent.agent.cell_dep());
+            skeleton.cell_dep(agent.proxy.cell_dep());
+            pass_through(skeleton, lock_proxy, lock_proxy.data.clone());
+        }
+        // Condition 2: consume the agent
+        (Some((agent, mut agent_data)), _) => {
+            if let Some(quota) = agent_data.quota.as_mut() {
+                *quota = quota.checked_sub(1).ok_or(Error::AgentQuotaExceeded)?;
+            }
+            if agent_data.expiry != 0 {
+                if let Some(block_hash) = agent.agent.block_hash.clone() {
+                    skeleton.header_dep(block_hash);
+                }
+            }
+            pass_through(skeleton, &agent.agent, agent_data.to_bytes().into());
+            skeleton.cell_dep(agent.agent_script.cell_dep.clone());
+            skeleton.cell_dep(agent.proxy.cell_dep());
+        }
+        // Condition 4: use a lock proxy of the cluster
+        (None, Some(lock_proxy)) => {
+            pass_through(skeleton, lock_proxy, lock_proxy.data.clone());
+        }
+    }
+    Ok(cluster_id)
+}
+
+/// Spends a cell and recreates it with the same lock, type and capacity
+fn pass_through(skeleton: &mut Skeleton, cell: &LiveCell, data: Bytes) {
+    skeleton.input(cell.clone());
+    skeleton.output(cell.output.clone(), data);
+}
+
+/// Transfers a Spore to a new lock, updating its content if declared with `mutable=content`. A
+/// Spore changing hands pays its royalty, and a marketplace trade pays its price to the seller
+#[derive(Debug, Clone)]
+pub struct SporeTransfer {
+    spore_script: ScriptInfo,
+    spore: LiveCell,
+    lock: Script,
+    content: Option<Vec<u8>>,
+    price: Option<u64>,
+    royalty_lock: Option<Script>,
+    cluster: Option<LiveCell>,
+    mutants: Vec<LiveCell>,
+    cell_deps: Vec<CellDep>,
+}
+
+impl SporeTransfer {
+    pub fn new(spore_script: ScriptInfo, spore: LiveCell, lock: Script) -> Self {
+        Self {
+            spore_script,
+            spore,
+            lock,
+            content: None,
+            price: None,
+            royalty_lock: None,
+            cluster: None,
+            mutants: Vec::new(),
+            cell_deps: Vec::new(),
+        }
+    }
+
+    pub fn content(mut self, content: &[u8]) -> Self {
+        self.content = Some(content.to_vec());
+        self
+    }
+
+    /// Price in shannons paid to the current owner, kept in the witness of the Spore
+    pub fn price(mut self, price: u64) -> Self {
+        self.price = Some(price);
+        self
+    }
+
+    /// The lock receiving the royalty of the Spore or of its cluster
+    pub fn royalty_lock(mut self, lock: Script) -> Self {
+        self.royalty_lock = Some(lock);
+        self
+    }
+
+    /// The cluster of a Spore declared with `royalty-to=cluster`
+    pub fn cluster(mut self, cluster: LiveCell) -> Self {
+        self.cluster = Some(cluster);
+        self
+    }
+
+    pub fn mutant(mut self, mutant: LiveCell) -> Self {
+        self.mutants.push(mutant);
+        self
+    }
+
+    /// Extra cell deps, such as the Lua library run by mutants
+    pub fn cell_dep(mut self, cell_dep: CellDep) -> Self {
+        self.cell_deps.push(cell_dep);
+        self
+    }
+
+    fn royalty(
+        &self,
+        skeleton: &mut Skeleton,
+        spore_data: &SporeData,
+    ) -> Result<Option<Royalty>, Error> {
+        let mime = MIME::parse_existing(spore_data.content_type().unpack())
+            .map_err(|_| Error::InvalidCellData)?;
+        match RoyaltyTerms::parse(&mime).map_err(|_| Error::InvalidCellData)? {
+            Some(RoyaltyTerms::Spore(royalty)) => Ok(Some(royalty)),
+            Some(RoyaltyTerms::Cluster) => {
+                let cluster = self.cluster.as_ref().ok_or(Error::MissingRoyaltyLock)?;
+                skeleton.cell_dep(cluster.cell_dep());
+                match cluster.cluster_data()?.royalty().to_opt() {
+                    Some(royalty) => Royalty::parse(&royalty.raw_data())
+                        .map(Some)
+                        .map_err(|_| Error::InvalidCellData),
+                    None => Ok(None),
+                }
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn build(self, funding: Funding) -> Result<TransactionView, Error> {
+        let mut skeleton = Skeleton::new(funding);
+        let spore_data = SporeData::from_compatible_slice(&self.spore.data)
+            .map_err(|_| Error::InvalidCellData)?;
+
+        let seller = self.spore.output.lock();
+        if seller.calc_script_hash() != self.lock.calc_script_hash() {
+            if let Some(royalty) = self.royalty(&mut skeleton, &spore_data)? {
+                let lock = self
+                    .royalty_lock
+                    .clone()
+                    .filter(|lock| lock.calc_script_hash().as_slice() == royalty.recipient)
+                    .ok_or(Error::MissingRoyaltyLock)?;
+                let amount = royalty.amount(self.price.unwrap_or_default()) as u64;
+                let payment = CellOutput::new_builder()
+                    .lock(lock)
+                    .capacity(amount.pack())
+                    .build();
+                skeleton.output(payment, Bytes::new());
+            }
+            if let Some(price) = self.price {
+                let payment = CellOutput::new_builder()
+                    .lock(seller)
+                    .capacity(price.pack())
+                    .build();
+                skeleton.output(payment, Bytes::new());
+            }
+        }
+
+        let spore_data = match self.content {
+            Some(content) => spore_data
+                .as_builder()
+                .content(content.as_slice().into())
+                .build(),
+            None => spore_data,
+        };
+        let witness = match self.price {
+            Some(price) => {
+                let price: packed::Bytes = price.to_le_bytes().to_vec().pack();
+                WitnessArgs::new_builder()
+                    .input_type(packed::BytesOpt::new_builder().set(Some(price)).build())
+                    .build()
+                    .as_bytes()
+            }
+            None => Bytes::new(),
+        };
+        skeleton.input_with_witness(self.spore.clone(), witness);
+        let output = self.spore.output.as_builder().lock(self.lock).build();
+        skeleton.output(output, spore_data.as_bytes());
+        apply_mutants(&mut skeleton, &self.mutants, OP_TRANSFER)?;
+        skeleton.cell_dep(self.spore_script.cell_dep);
+        self.cell_deps
+            .into_iter()
+            .for_each(|cell_dep| skeleton.cell_dep(cell_dep));
+        skeleton.build()
+    }
+}
+
+/// Melts a Spore, its capacity goes back to the change lock except the refund of its melt policy
+#[derive(Debug, Clone)]
+pub struct SporeMelt {
+    spore_script: ScriptInfo,
+    spore: LiveCell,
+    refund_lock: Option<Script>,
+    cluster: Option<LiveCell>,
+    mutants: Vec<LiveCell>,
+    cell_deps: Vec<CellDep>,
+}
+
+impl SporeMelt {
+    pub fn new(spore_script: ScriptInfo, spore: LiveCell) -> Self {
+        Self {
+            spore_script,
+            spore,
+            refund_lock: None,
+            cluster: None,
+            mutants: Vec::new(),
+            cell_deps: Vec::new(),
+        }
+    }
+
+    /// The lock of a Spore declared with `melt-to=<lock hash>`
+    pub fn refund_lock(mut self, lock: Script) -> Self {
+        self.refund_lock = Some(lock);
+        self
+    }
+
+    /// The cluster of a Spore declared with `melt-to=cluster`, refunded to the cluster owner
+    pub fn cluster(mut self, cluster: LiveCell) -> Self {
+        self.cluster = Some(cluster);
+        self
+    }
+
+    pub fn mutant(mut self, mutant: LiveCell) -> Self {
+        self.mutants.push(mutant);
+        self
+    }
+
+    /// Extra cell deps, such as the Lua library run by mutants
+    pub fn cell_dep(mut self, cell_dep: CellDep) -> Self {
+        self.cell_deps.push(cell_dep);
+        self
+    }
+
+    pub fn build(self, funding: Funding) -> Result<TransactionView, Error> {
+        let mut skeleton = Skeleton::new(funding);
+        let spore_data = SporeData::from_compatible_slice(&self.spore.data)
+            .map_err(|_| Error::InvalidCellData)?;
+        let mime = MIME::parse_existing(spore_data.content_type().unpack())
+            .map_err(|_| Error::InvalidCellData)?;
+        if let Some(policy) = MeltPolicy::parse(&mime).map_err(|_| Error::InvalidCellData)? {
+            let lock = match policy.target {
+                MeltTarget::Lock(lock_hash) => self
+                    .refund_lock
+                    .filter(|lock| lock.calc_script_hash().as_slice() == lock_hash)
+                    .ok_or(Error::MissingRefundLock)?,
+                MeltTarget::ClusterOwner => {
+                    let cluster = self.cluster.ok_or(Error::MissingCluster)?;
+                    skeleton.cell_dep(cluster.cell_dep());
+                    cluster.output.lock()
+                }
+            };
+            let capacity: u64 = self.spore.output.capacity().unpack();
+            let refund = CellOutput::new_builder()
+                .lock(lock)
+                .capacity((policy.refund(capacity) as u64).pack())
+                .build();
+            skeleton.output(refund, Bytes::new());
+        }
+
+        skeleton.input(self.spore);
+        apply_mutants(&mut skeleton, &self.mutants, OP_MELT)?;
+        skeleton.cell_dep(self.spore_script.cell_dep);
+        self.cell_deps
+            .into_iter()
+            .for_each(|cell_dep| skeleton.cell_dep(cell_dep));
+        skeleton.build()
+    }
+}
@@ -0,0 +1,420 @@
+//! Pre-validation of resolved transactions against the rules of the Spore contracts, so a
+//! failure can be explained before broadcasting. The transaction-level rules are the ones the
+//! contracts run, from `spore_utils::transaction`; mutant code is not executed.
+
+use ckb_types::{
+    core::{HeaderView, TransactionView},
+    packed::{Byte32, CellOutput, OutPoint, Script, WitnessArgs},
+    prelude::*,
+};
+pub use spore_errors::error::Error as Rule;
+use spore_types::generated::spore_types::SporeData;
+use spore_utils::transaction::{self, Cells, Contract, Failure, HeaderInfo, Source};
+use spore_utils::MIME;
+
+use crate::{LiveCell, ScriptInfo};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellSource {
+    Input,
+    Output,
+    CellDep,
+}
+
+/// A failed rule, as the error code the contract returns, and the cell and field it is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub rule: Rule,
+    pub source: CellSource,
+    pub index: usize,
+    pub field: &'static str,
+}
+
+impl Diagnostic {
+    pub fn new(rule: Rule, source: CellSource, index: usize, field: &'static str) -> Self {
+        Self {
+            rule,
+            source,
+            index,
+            field,
+        }
+    }
+}
+
+/// A transaction with the cells it spends and depends on
+#[derive(Debug, Clone)]
+pub struct ResolvedTransaction {
+    pub transaction: TransactionView,
+    /// cells in the order of the inputs, with block hashes for agents having an expiry
+    pub inputs: Vec<LiveCell>,
+    /// cells in the order of the cell deps, dep groups expanded
+    pub cell_deps: Vec<LiveCell>,
+    /// headers of the header deps
+    pub headers: Vec<HeaderView>,
+}
+
+impl From<Failure> for Diagnostic {
+    fn from(failure: Failure) -> Self {
+        let source = match failure.source {
+            Source::Input | Source::GroupInput => CellSource::Input,
+            Source::Output | Source::GroupOutput => CellSource::Output,
+            Source::CellDep | Source::HeaderDep => CellSource::CellDep,
+        };
+        Self::new(failure.rule, source, failure.index, failure.field)
+    }
+}
+
+/// Runs the rules of the deployed Spore contracts, recognized by code hash. The v1 cluster
+/// contract is recognized as a cluster too, as the Spore contract does
+#[derive(Debug, Clone)]
+pub struct Validator {
+    spore: ScriptInfo,
+    cluster: ScriptInfo,
+    cluster_proxy: ScriptInfo,
+    cluster_agent: ScriptInfo,
+    /// the Lua extension, looked up by the legacy `mutant_id` of a cluster. Mutants of Spores are
+    /// found by their ID whatever their code
+    spore_extension_lua: ScriptInfo,
+    spore_segment: ScriptInfo,
+}
+
+impl Validator {
+    pub fn new(
+        spore: ScriptInfo,
+        cluster: ScriptInfo,
+        cluster_proxy: ScriptInfo,
+        cluster_agent: ScriptInfo,
+        spore_extension_lua: ScriptInfo,
+        spore_segment: ScriptInfo,
+    ) -> Self {
+        Self {
+            spore,
+            cluster,
+            cluster_proxy,
+            cluster_agent,
+            spore_extension_lua,
+            spore_segment,
+        }
+    }
+
+    /// Returns the first failed rule of every script group, empty if the transaction passes
+    pub fn validate(&self, tx: &ResolvedTransaction) -> Vec<Diagnostic> {
+        let tx_hash = tx.transaction.hash();
+        let outputs: Vec<LiveCell> = tx
+            .transaction
+            .outputs_with_data_iter()
+            .enumerate()
+            .map(|(index, (output, data))| {
+                LiveCell::new(OutPoint::new(tx_hash.clone(), index as u32), output, data)
+            })
+            .collect();
+        let cells = |script| TxCells {
+            validator: self,
+            tx,
+            outputs: &outputs,
+            script,
+        };
+        cells(Script::default())
+            .script_groups()
+            .into_iter()
+            .filter_map(|group| cells(group.script.clone()).verify_group(&group).err())
+            .collect()
+    }
+
+    fn is_contract(&self, script: &Script, contract: Contract) -> bool {
+        let code_hash = script.code_hash();
+        match contract {
+            Contract::Cluster => {
+                code_hash == self.cluster.code_hash
+                    || code_hash.as_slice() == transaction::CLUSTER_V1_CODE_HASH
+            }
+            Contract::ClusterProxy => code_hash == self.cluster_proxy.code_hash,
+            Contract::ClusterAgent => code_hash == self.cluster_agent.code_hash,
+            Contract::SporeSegment => code_hash == self.spore_segment.code_hash,
+            Contract::SporeExtensionLua => code_hash == self.spore_extension_lua.code_hash,
+        }
+    }
+}
+
+struct ScriptGroup {
+    script: Script,
+    inputs: Vec<usize>,
+    outputs: Vec<usize>,
+}
+
+/// Cells of a resolved transaction as seen by the script of one group
+struct TxCells<'a> {
+    validator: &'a Validator,
+    tx: &'a ResolvedTransaction,
+    outputs: &'a [LiveCell],
+    script: Script,
+}
+
+/// Converts a script to the one of `spore_utils`, which is built on the types of `ckb-std`
+fn to_utils_script(script: &Script) -> transaction::Script {
+    transaction::Script::new_unchecked(script.as_slice().to_vec().into())
+}
+
+fn from_utils_script(script: &transaction::Script) -> Script {
+    Script::new_unchecked(script.as_slice().to_vec().into())
+}
+
+impl<'a> TxCells<'a> {
+    fn cells(&self, source: Source) -> Result<&'a [LiveCell], Rule> {
+        match source {
+            Source::Input => Ok(&self.tx.inputs),
+            Source::Output => Ok(self.outputs),
+            Source::CellDep => Ok(&self.tx.cell_deps),
+            _ => Err(Rule::IndexOutOfBound),
+        }
+    }
+
+    fn cell(&self, index: usize, source: Source) -> Result<&'a LiveCell, Rule> {
+        self.cells(source)?.get(index).ok_or(Rule::IndexOutOfBound)
+    }
+
+    fn types(&self, source: Source) -> impl Iterator<Item = Option<Script>> + 'a {
+        self.cells(source)
+            .unwrap_or_default()
+            .iter()
+            .map(|cell| cell.output.type_().to_opt())
+    }
+
+    /// Type script groups of the Spore contracts, in the order they appear in the transaction
+    fn script_groups(&self) -> Vec<ScriptGroup> {
+        let validator = self.validator;
+        let contracts = [
+            &validator.spore,
+            &validator.cluster,
+            &validator.cluster_proxy,
+            &validator.cluster_agent,
+            &validator.spore_segment,
+        ];
+        let mut groups: Vec<ScriptGroup> = Vec::new();
+        for source in [Source::Input, Source::Output] {
+            for (index, script) in self.types(source).enumerate() {
+                let script = match script {
+                    Some(script) if contracts.iter().any(|c| script.code_hash() == c.code_hash) => {
+                        script
+                    }
+                    _ => continue,
+                };
+                let group = match groups.iter_mut().position(|group| group.script == script) {
+                    Some(position) => &mut groups[position],
+                    None => {
+                        groups.push(ScriptGroup {
+                            script,
+                            inputs: Vec::new(),
+                            outputs: Vec::new(),
+                        });
+                        groups.last_mut().unwrap()
+                    }
+                };
+                match source {
+                    Source::Input => group.inputs.push(index),
+                    _ => group.outputs.push(index),
+                }
+            }
+        }
+        groups
+    }
+
+    fn verify_group(&self, group: &ScriptGroup) -> Result<(), Diagnostic> {
+        let validator = self.validator;
+        let code_hash = group.script.code_hash();
+        if code_hash == validator.spore_segment.code_hash {
+            return Ok(transaction::verify_created_segments(self)?);
+        }
+        let (conflict, multiple_spend) = if code_hash == validator.spore.code_hash {
+            (Rule::ConflictCreation, Rule::MultipleSpend)
+        } else if code_hash == validator.cluster.code_hash {
+            (Rule::InvalidClusterOperation, Rule::InvalidClusterOperation)
+        } else if code_hash == validator.cluster_proxy.code_hash {
+            (Rule::InvalidProxyOperation, Rule::InvalidProxyOperation)
+        } else {
+            (Rule::InvalidAgentOperation, Rule::InvalidAgentOperation)
+        };
+        if group.outputs.len() > 1 {
+            return Err(Diagnostic::new(
+                conflict,
+                CellSource::Output,
+                group.outputs[1],
+                "type",
+            ));
+        }
+        if group.inputs.len() > 1 {
+            return Err(Diagnostic::new(
+                multiple_spend,
+                CellSource::Input,
+                group.inputs[1],
+                "type",
+            ));
+        }
+
+        let input = group.inputs.first().copied();
+        let output = group.outputs.first().copied();
+        if code_hash == validator.spore.code_hash {
+            match (input, output) {
+                (None, Some(index)) => {
+                    transaction::verify_minted_spore(self, index)?;
+                    self.verify_mutants(index)
+                }
+                (Some(index), None) => {
+                    let mime = transaction::verify_melted_spore(self, index)?;
+                    Ok(
+                        transaction::resolve_mutants(self, &mime, Source::Input, index)
+                            .map(drop)?,
+                    )
+                }
+                (Some(input), Some(output)) => {
+                    let mime = transaction::verify_transferred_spore(self, input, output)?;
+                    Ok(
+                        transaction::resolve_mutants(self, &mime, Source::Input, input)
+                            .map(drop)?,
+                    )
+                }
+                _ => Ok(()),
+            }
+        } else if code_hash == validator.cluster.code_hash {
+            match (input, output) {
+                (None, Some(index)) => Ok(transaction::verify_created_cluster(self, index)?),
+                (Some(index), None) => Ok(transaction::verify_destroyed_cluster(self, index)?),
+                (Some(input), Some(output)) => Ok(transaction::verify_transferred_cluster(
+                    self, input, output,
+                )?),
+                _ => Ok(()),
+            }
+        } else if code_hash == validator.cluster_proxy.code_hash {
+            match (input, output) {
+                (None, Some(index)) => Ok(transaction::verify_created_proxy(self, index)?),
+                (Some(input), Some(output)) => {
+                    Ok(transaction::verify_transferred_proxy(self, input, output)?)
+                }
+                _ => Ok(()),
+            }
+        } else {
+            match (input, output) {
+                (None, Some(index)) => Ok(transaction::verify_created_agent(self, index)?),
+                (Some(input), Some(output)) => {
+                    Ok(transaction::verify_transferred_agent(self, input, output)?)
+                }
+                _ => Ok(()),
+            }
+        }
+    }
+
+    /// The mutants of a minted Spore are resolved as the contract does before running them
+    fn verify_mutants(&self, index: usize) -> Result<(), Diagnostic> {
+        let spore_data =
+            SporeData::from_compatible_slice(&self.outputs[index].data).map_err(|_| {
+                Diagnostic::new(Rule::InvalidNFTData, CellSource::Output, index, "data")
+            })?;
+        let mime = MIME::parse(spore_data.content_type().unpack())
+            .map_err(|rule| Diagnostic::new(rule, CellSource::Output, index, "content_type"))?;
+        transaction::resolve_mutants(self, &mime, Source::Output, index)?;
+        Ok(())
+    }
+}
+
+impl<'a> Cells for TxCells<'a> {
+    fn script(&self) -> Result<transaction::Script, Rule> {
+        Ok(to_utils_script(&self.script))
+    }
+
+    fn is_contract(&self, script: &transaction::Script, contract: Contract) -> bool {
+        self.validator
+            .is_contract(&from_utils_script(script), contract)
+    }
+
+    fn type_script(
+        &self,
+        index: usize,
+        source: Source,
+    ) -> Result<Option<transaction::Script>, Rule> {
+        let cell = self.cell(index, source)?;
+        Ok(cell
+            .output
+            .type_()
+            .to_opt()
+            .map(|script| to_utils_script(&script)))
+    }
+
+    fn type_hash(&self, index: usize, source: Source) -> Result<Option<[u8; 32]>, Rule> {
+        let cell = self.cell(index, source)?;
+        Ok(cell
+            .output
+            .type_()
+            .to_opt()
+            .map(|script| to_hash(&script.calc_script_hash())))
+    }
+
+    fn lock_hash(&self, index: usize, source: Source) -> Result<[u8; 32], Rule> {
+        let cell = self.cell(index, source)?;
+        Ok(to_hash(&cell.output.lock().calc_script_hash()))
+    }
+
+    fn capacity(&self, index: usize, source: Source) -> Result<u64, Rule> {
+        Ok(self.cell(index, source)?.output.capacity().unpack())
+    }
+
+    fn data(&self, index: usize, source: Source) -> Result<Vec<u8>, Rule> {
+        Ok(self.cell(index, source)?.data.to_vec())
+    }
+
+    fn data_hash(&self, index: usize, source: Source) -> Result<[u8; 32], Rule> {
+        let cell = self.cell(index, source)?;
+        Ok(to_hash(&CellOutput::calc_data_hash(&cell.data)))
+    }
+
+    fn since(&self, index: usize) -> Result<u64, Rule> {
+        let input = self.tx.transaction.inputs().get(index);
+        Ok(input.ok_or(Rule::IndexOutOfBound)?.since().unpack())
+    }
+
+    fn header(&self, index: usize, source: Source) -> Result<HeaderInfo, Rule> {
+        // the header of a cell is loaded from the header deps by its block hash
+        let header = match source {
+            Source::HeaderDep => self.tx.headers.get(index).ok_or(Rule::IndexOutOfBound)?,
+            source => {
+                let block_hash = self.cell(index, source)?.block_hash.as_ref();
+                self.tx
+                    .headers
+                    .iter()
+                    .find(|header| Some(&header.hash()) == block_hash)
+                    .ok_or(Rule::ItemMissing)?
+            }
+        };
+        Ok(to_header_info(header))
+    }
+
+    fn witness_input_type(&self, index: usize) -> Result<Option<Vec<u8>>, Rule> {
+        let witness = match self.tx.transaction.witnesses().get(index) {
+            Some(witness) => witness,
+            None => return Ok(None),
+        };
+        match WitnessArgs::from_slice(&witness.raw_data()) {
+            Ok(witness_args) => Ok(witness_args
+                .input_type()
+                .to_opt()
+                .map(|input_type| input_type.raw_data().to_vec())),
+            Err(_) => Ok(None),
+        }
+    }
+
+    fn first_input(&self) -> Result<Vec<u8>, Rule> {
+        let input = self.tx.transaction.inputs().get(0);
+        Ok(input.ok_or(Rule::IndexOutOfBound)?.as_slice().to_vec())
+    }
+}
+
+fn to_hash(hash: &Byte32) -> [u8; 32] {
+    hash.as_slice().try_into().unwrap_or_default()
+}
+
+fn to_header_info(header: &HeaderView) -> HeaderInfo {
+    HeaderInfo {
+        number: header.number(),
+        epoch: header.epoch().full_value(),
+        timestamp: header.timestamp(),
+    }
+}
@@ -1,7 +1,9 @@
 #![no_std]
 
 extern crate alloc;
-pub use crate::generated::spore_types::{Bool, Bytes, BytesOpt, SporeData};
+pub use crate::generated::spore_types::{
+    Bool, Bytes, BytesOpt, ClusterData, ClusterDataV2, SporeData, Uint64, Uint64Opt,
+};
 use alloc::string::String;
 use alloc::vec::Vec;
 use molecule::prelude::{Builder, Entity};
@@ -63,6 +65,46 @@ impl From<generated::spore_types::Bool> for bool {
     }
 }
 
+impl From<bool> for generated::spore_types::Bool {
+    fn from(value: bool) -> Bool {
+        Bool::new_unchecked(Vec::from([value as u8]).into())
+    }
+}
+
+impl From<generated::spore_types::Uint64> for u64 {
+    fn from(value: generated::spore_types::Uint64) -> u64 {
+        u64::from_le_bytes(value.as_slice().try_into().unwrap_or_default())
+    }
+}
+
+impl From<u64> for generated::spore_types::Uint64 {
+    fn from(value: u64) -> Uint64 {
+        Uint64::new_unchecked(Vec::from(value.to_le_bytes()).into())
+    }
+}
+
+impl From<Option<u64>> for generated::spore_types::Uint64Opt {
+    fn from(value: Option<u64>) -> Uint64Opt {
+        Uint64Opt::new_builder().set(value.map(Into::into)).build()
+    }
+}
+
+impl From<generated::spore_types::Uint64Opt> for Option<u64> {
+    fn from(value: generated::spore_types::Uint64Opt) -> Option<u64> {
+        value.to_opt().map(Into::into)
+    }
+}
+
+impl From<generated::spore_types::ClusterData> for generated::spore_types::ClusterDataV2 {
+    fn from(data: ClusterData) -> Self {
+        ClusterDataV2::new_builder()
+            .name(data.name())
+            .description(data.description())
+            .mutant_id(data.mutant_id())
+            .build()
+    }
+}
+
 impl generated::spore_types::Bytes {
     pub fn unpack(&self) -> &[u8] {
         &self.as_slice()[4..]
@@ -0,0 +1,112 @@
+//! Spore content referencing off-chain media by digest, under `application/x-spore-ref;hash=blake2b`
+
+use spore_errors::error::Error;
+
+use crate::MIME;
+
+pub const CONTENT_REF_DIGEST_LEN: usize = 32;
+
+/// The digest of off-chain media followed by the URI to fetch it from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentRef<'a> {
+    pub digest: &'a [u8],
+    pub uri: &'a str,
+}
+
+pub fn is_content_ref(mime: &MIME) -> bool {
+    mime.main_type == "application" && mime.sub_type == "x-spore-ref"
+}
+
+/// Checks the content of a Spore referencing off-chain media, any other content passes
+pub fn verify_content_ref(mime: &MIME, content: &[u8]) -> Result<(), Error> {
+    if is_content_ref(mime) {
+        parse_content_ref(mime, content)?;
+    }
+    Ok(())
+}
+
+/// Only blake2b digests personalized with `ckb-default-hash` are supported
+pub fn parse_content_ref<'a>(mime: &MIME, content: &'a [u8]) -> Result<ContentRef<'a>, Error> {
+    if !is_content_ref(mime) {
+        return Err(Error::InvalidContentType);
+    }
+    let hash = mime.get_param("hash").ok_or(Error::InvalidContentType)?;
+    if !hash.eq_ignore_ascii_case("blake2b") {
+        return Err(Error::UnsupportedDigestAlgorithm);
+    }
+    if content.len() <= CONTENT_REF_DIGEST_LEN {
+        return Err(Error::InvalidContentRef);
+    }
+    let (digest, uri) = content.split_at(CONTENT_REF_DIGEST_LEN);
+    let uri = core::str::from_utf8(uri).map_err(|_| Error::InvalidContentRef)?;
+    if !is_valid_uri(uri) {
+        return Err(Error::InvalidContentRef);
+    }
+    Ok(ContentRef { digest, uri })
+}
+
+/// A scheme and printable ASCII characters, as of RFC 3986
+fn is_valid_uri(uri: &str) -> bool {
+    let (scheme, _) = match uri.split_once(':') {
+        Some(parts) => parts,
+        None => return false,
+    };
+    scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+        && uri.chars().all(|c| c.is_ascii_graphic())
+}
+
+#[test]
+fn test_content_ref() {
+    let mime = MIME::str_parse("application/x-spore-ref;hash=blake2b").unwrap();
+    let mut content = [7u8; CONTENT_REF_DIGEST_LEN].to_vec();
+    content
+        .extend_from_slice(b"ipfs://bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi");
+    let content_ref = parse_content_ref(&mime, &content).unwrap();
+    assert_eq!(content_ref.digest, &[7u8; CONTENT_REF_DIGEST_LEN]);
+    assert!(content_ref.uri.starts_with("ipfs://"));
+
+    let plain = MIME::str_parse("text/plain").unwrap();
+    assert!(verify_content_ref(&plain, b"").is_ok());
+
+    let mut spaced = [7u8; CONTENT_REF_DIGEST_LEN].to_vec();
+    spaced.extend_from_slice(b"file:///spore media.mp4");
+    let invalid: [(&str, &[u8], Error); 6] = [
+        (
+            "application/x-spore-ref",
+            &content,
+            Error::InvalidContentType,
+        ),
+        (
+            "application/x-spore-ref;hash=sha256",
+            &content,
+            Error::UnsupportedDigestAlgorithm,
+        ),
+        (
+            "application/x-spore-ref;hash=blake2b",
+            &content[..32],
+            Error::InvalidContentRef,
+        ),
+        (
+            "application/x-spore-ref;hash=blake2b",
+            &spaced,
+            Error::InvalidContentRef,
+        ),
+        (
+            "application/x-spore-ref;hash=blake2b",
+            &[0u8; 40],
+            Error::InvalidContentRef,
+        ),
+        (
+            "application/x-spore-ref;hash=blake2b",
+            &[b'a'; 40],
+            Error::InvalidContentRef,
+        ),
+    ];
+    for (content_type, content, error) in invalid {
+        let mime = MIME::str_parse(content_type).unwrap();
+        assert_eq!(verify_content_ref(&mime, content).err(), Some(error));
+    }
+}
@@ -0,0 +1,360 @@
+//! Arguments the spore contract passes to mutants through `exec`
+//!
+//! argv: `op | mutant indices | Spore ID | content-type | Spore indices`, with indices in decimal
+//! and the Spore ID in lowercase hex. Mutant indices are comma separated, listing every mutant of
+//! the Spore at once. The Spore indices are the output index on mint, the input and output indices
+//! on transfer, and the input index on melt.
+
+use alloc::ffi::CString;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use ckb_std::ckb_constants::Source::{self, GroupInput, GroupOutput};
+use ckb_std::high_level::{load_cell_data, load_cell_lock_hash, QueryIter};
+use core::ffi::CStr;
+use spore_errors::error::Error;
+
+use crate::payment::{parse_descriptors, Payment};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Mint {
+        output_index: usize,
+    },
+    Transfer {
+        input_index: usize,
+        output_index: usize,
+    },
+    Melt {
+        input_index: usize,
+    },
+}
+
+impl Operation {
+    pub fn code(&self) -> u8 {
+        match self {
+            Operation::Mint { .. } => OP_MINT,
+            Operation::Transfer { .. } => OP_TRANSFER,
+            Operation::Melt { .. } => OP_MELT,
+        }
+    }
+
+    /// The Spore operated, the one after mint and transfer or the one before melt
+    pub fn spore_cell(&self) -> (usize, Source) {
+        match *self {
+            Operation::Mint { output_index } | Operation::Transfer { output_index, .. } => {
+                (output_index, Source::Output)
+            }
+            Operation::Melt { input_index } => (input_index, Source::Input),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtensionArgs {
+    pub operation: Operation,
+    // indices of the mutant cells in CellDeps, all run by the same code
+    pub mutant_indices: Vec<usize>,
+    pub spore_id: Vec<u8>,
+    pub content_type: String,
+}
+
+impl ExtensionArgs {
+    pub fn to_argv(&self) -> Result<Vec<CString>, Error> {
+        let mut argv = Vec::from([
+            self.operation.code().to_string(),
+            self.mutant_indices
+                .iter()
+                .map(|index| index.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+            to_hex(&self.spore_id),
+            self.content_type.clone(),
+        ]);
+        match self.operation {
+            Operation::Mint { output_index } => argv.push(output_index.to_string()),
+            Operation::Transfer {
+                input_index,
+                output_index,
+            } => {
+                argv.push(input_index.to_string());
+                argv.push(output_index.to_string());
+            }
+            Operation::Melt { input_index } => argv.push(input_index.to_string()),
+        }
+        argv.into_iter()
+            .map(|arg| CString::new(arg).map_err(|_| Error::InvalidExtensionArgv))
+            .collect()
+    }
+
+    pub fn parse(argv: &[&CStr]) -> Result<Self, Error> {
+        let arg = |index: usize| -> Result<&str, Error> {
+            let arg = argv.get(index).ok_or(Error::InvalidExtensionArgv)?;
+            arg.to_str().map_err(|_| Error::InvalidExtensionArgv)
+        };
+        let operation = match (arg(0)?, argv.len()) {
+            ("0", 5) => Operation::Mint {
+                output_index: parse_index(arg(4)?)?,
+            },
+            ("1", 6) => Operation::Transfer {
+                input_index: parse_index(arg(4)?)?,
+                output_index: parse_index(arg(5)?)?,
+            },
+            ("2", 5) => Operation::Melt {
+                input_index: parse_index(arg(4)?)?,
+            },
+            _ => return Err(Error::InvalidExtensionOperation),
+        };
+        Ok(ExtensionArgs {
+            operation,
+            mutant_indices: arg(1)?
+                .split(',')
+                .map(parse_index)
+                .collect::<Result<_, _>>()?,
+            spore_id: from_hex(arg(2)?)?,
+            content_type: arg(3)?.to_string(),
+        })
+    }
+}
+
+pub const MUTANT_ID_LEN: usize = 32;
+
+// args with payment descriptors start with a 32-byte authority after the ID
+const DESCRIPTORS_OFFSET: usize = MUTANT_ID_LEN + 32;
+// the shortest descriptors, a version and three empty payments, so their args are longer than the
+// longest legacy layout, `id | 3 payments | upgrade authority` of 67 bytes
+const MIN_DESCRIPTORS_ARGS_LEN: usize = DESCRIPTORS_OFFSET + 4;
+
+pub const OP_MINT: u8 = 0;
+pub const OP_TRANSFER: u8 = 1;
+pub const OP_MELT: u8 = 2;
+
+/// Payment byte of an operation the mutant doesn't charge for
+pub const NO_PAYMENT: u8 = 0xff;
+
+/// Type args of a mutant cell, where an upgrade authority is the lock hash allowed to update the
+/// mutant's data and payments go to the mutant's lock, in one of the layouts:
+/// - `id | payment? | upgrade authority?`, the payment being a byte charged on mint, or three bytes
+///   charged on mint, transfer and melt, a byte `x` asking for `10^x` shannons unless `NO_PAYMENT`
+/// - `id | upgrade authority | payment descriptors`, with an all-zero authority for none and a
+///   versioned descriptor of any `Payment` per operation, see `payment::parse_descriptors`
+///
+/// The layouts are told apart by length alone: 32, 33, 35, 64, 65 and 67 bytes are legacy, 68 and
+/// more have descriptors, and any other length is invalid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MutantArgs<'a> {
+    pub id: &'a [u8],
+    // indexed by operation code
+    pub payments: [Option<Payment>; 3],
+    pub upgrade_authority: Option<&'a [u8]>,
+}
+
+impl<'a> MutantArgs<'a> {
+    pub fn parse(args: &'a [u8]) -> Result<Self, Error> {
+        let payments_len = match args.len() {
+            32 | 64 => 0,
+            33 | 65 => 1,
+            35 | 67 => 3,
+            len if len >= MIN_DESCRIPTORS_ARGS_LEN => return Self::parse_descriptors(args),
+            _ => return Err(Error::InvalidExtensionArg),
+        };
+        let payments_end = MUTANT_ID_LEN + payments_len;
+        let mut payments = [None; 3];
+        for (payment, &byte) in payments.iter_mut().zip(&args[MUTANT_ID_LEN..payments_end]) {
+            if byte != NO_PAYMENT {
+                *payment = Some(Payment::PowerOfTen(byte));
+            }
+        }
+        let upgrade_authority = match args.len() - payments_end {
+            0 => None,
+            _ => Some(&args[payments_end..]),
+        };
+        Ok(MutantArgs {
+            id: &args[..MUTANT_ID_LEN],
+            payments,
+            upgrade_authority,
+        })
+    }
+
+    fn parse_descriptors(args: &'a [u8]) -> Result<Self, Error> {
+        let authority = &args[MUTANT_ID_LEN..DESCRIPTORS_OFFSET];
+        Ok(MutantArgs {
+            id: &args[..MUTANT_ID_LEN],
+            payments: parse_descriptors(&args[DESCRIPTORS_OFFSET..])
+                .map_err(|_| Error::InvalidExtensionArg)?,
+            upgrade_authority: Some(authority).filter(|hash| hash.iter().any(|&byte| byte != 0)),
+        })
+    }
+
+    /// Payment charged on the operation of code `op`
+    pub fn payment(&self, op: u8) -> Option<Payment> {
+        self.payments.get(op as usize).copied().flatten()
+    }
+}
+
+/// Mutant cells keep their data on transfer, unless an upgradable one is updated with its
+/// upgrade authority in Inputs
+pub fn verify_mutant_transfer(args: &MutantArgs) -> Result<(), Error> {
+    if load_cell_data(0, GroupInput)? == load_cell_data(0, GroupOutput)? {
+        return Ok(());
+    }
+    let authority = args
+        .upgrade_authority
+        .ok_or(Error::ModifyExtensionPermanentField)?;
+    if !QueryIter::new(load_cell_lock_hash, Source::Input).any(|hash| hash[..] == authority[..]) {
+        return Err(Error::MutantUpgradeNotAuthorized);
+    }
+    Ok(())
+}
+
+fn parse_index(arg: &str) -> Result<usize, Error> {
+    if arg.is_empty() || !arg.bytes().all(|c| c.is_ascii_digit()) {
+        return Err(Error::InvalidExtensionArgv);
+    }
+    arg.parse().map_err(|_| Error::InvalidExtensionArgv)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>, Error> {
+    let pairs = hex.as_bytes().chunks_exact(2);
+    if !pairs.remainder().is_empty() || !hex.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return Err(Error::InvalidExtensionArgv);
+    }
+    pairs
+        .map(|pair| {
+            let pair = core::str::from_utf8(pair).map_err(|_| Error::InvalidExtensionArgv)?;
+            u8::from_str_radix(pair, 16).map_err(|_| Error::InvalidExtensionArgv)
+        })
+        .collect()
+}
+
+#[test]
+fn test_extension_args() {
+    let args = ExtensionArgs {
+        operation: Operation::Transfer {
+            input_index: 12,
+            output_index: 300,
+        },
+        mutant_indices: Vec::from([1024, 3]),
+        spore_id: [0xab; 32].to_vec(),
+        content_type: "text/plain;mutant[]=\"ü\"".to_string(),
+    };
+    let argv = args.to_argv().unwrap();
+    assert_eq!(argv[0].to_str(), Ok("1"));
+    assert_eq!(argv[1].to_str(), Ok("1024,3"));
+    assert_eq!(argv[2].to_bytes(), "ab".repeat(32).as_bytes());
+    assert_eq!(argv[5].to_str(), Ok("300"));
+    let argv: Vec<&CStr> = argv.iter().map(|arg| arg.as_c_str()).collect();
+    assert_eq!(ExtensionArgs::parse(&argv), Ok(args));
+
+    let mint = ExtensionArgs {
+        operation: Operation::Mint { output_index: 10 },
+        mutant_indices: Vec::from([0]),
+        spore_id: Vec::new(),
+        content_type: String::new(),
+    };
+    let argv = mint.to_argv().unwrap();
+    let argv: Vec<&CStr> = argv.iter().map(|arg| arg.as_c_str()).collect();
+    assert_eq!(ExtensionArgs::parse(&argv), Ok(mint));
+    assert_eq!(
+        ExtensionArgs::parse(&argv[..4]),
+        Err(Error::InvalidExtensionOperation)
+    );
+
+    let invalid: [[&[u8]; 5]; 6] = [
+        [b"2\0", b"+1\0", b"ab\0", b"text/plain\0", b"1\0"],
+        [b"2\0", b"\0", b"ab\0", b"text/plain\0", b"1\0"],
+        [b"2\0", b"1,\0", b"ab\0", b"text/plain\0", b"1\0"],
+        [b"2\0", b"1\0", b"a\0", b"text/plain\0", b"1\0"],
+        [b"2\0", b"1\0", b"zz\0", b"text/plain\0", b"1\0"],
+        [b"2\0", b"1\0", b"+a\0", b"text/plain\0", b"1\0"],
+    ];
+    for argv in invalid {
+        let argv: Vec<&CStr> = argv
+            .iter()
+            .map(|arg| CStr::from_bytes_with_nul(arg).unwrap())
+            .collect();
+        assert_eq!(
+            ExtensionArgs::parse(&argv),
+            Err(Error::InvalidExtensionArgv)
+        );
+    }
+
+    let authority = [7u8; 32];
+    let args = [[1u8; 32].as_slice(), &[3], &authority].concat();
+    let mutant = MutantArgs::parse(&args).unwrap();
+    assert_eq!(mutant.payments, [Some(Payment::PowerOfTen(3)), None, None]);
+    assert_eq!(mutant.upgrade_authority, Some(&authority[..]));
+    let upgradable = [[1u8; 32], authority].concat();
+    let mutant = MutantArgs::parse(&upgradable).unwrap();
+    assert_eq!(mutant.payment(OP_MINT), None);
+    assert_eq!(mutant.upgrade_authority, Some(&authority[..]));
+    assert_eq!(
+        MutantArgs::parse(&args[..33]).unwrap().upgrade_authority,
+        None
+    );
+    assert_eq!(
+        MutantArgs::parse(&args[..40]),
+        Err(Error::InvalidExtensionArg)
+    );
+
+    let args = [[1u8; 32].as_slice(), &[NO_PAYMENT, 5, 0], &authority].concat();
+    let mutant = MutantArgs::parse(&args).unwrap();
+    assert_eq!(mutant.payment(OP_MINT), None);
+    assert_eq!(mutant.payment(OP_TRANSFER), Some(Payment::PowerOfTen(5)));
+    assert_eq!(mutant.payment(OP_MELT), Some(Payment::PowerOfTen(0)));
+    assert_eq!(mutant.upgrade_authority, Some(&authority[..]));
+    let mutant = MutantArgs::parse(&args[..35]).unwrap();
+    assert_eq!(
+        mutant.payments,
+        [
+            None,
+            Some(Payment::PowerOfTen(5)),
+            Some(Payment::PowerOfTen(0))
+        ]
+    );
+    assert_eq!(mutant.upgrade_authority, None);
+
+    let payments = [
+        Some(Payment::Exact(1_500)),
+        None,
+        Some(Payment::Xudt {
+            type_hash: [9; 32],
+            amount: 20,
+        }),
+    ];
+    let descriptors = crate::payment::to_descriptors(&payments);
+    let args = [[1u8; 32].as_slice(), &authority, &descriptors].concat();
+    let mutant = MutantArgs::parse(&args).unwrap();
+    assert_eq!(mutant.payments, payments);
+    assert_eq!(mutant.upgrade_authority, Some(&authority[..]));
+    let args = [[1u8; 32].as_slice(), &[0; 32], &descriptors].concat();
+    assert_eq!(MutantArgs::parse(&args).unwrap().upgrade_authority, None);
+    assert_eq!(
+        MutantArgs::parse(&args[..args.len() - 1]),
+        Err(Error::InvalidExtensionArg)
+    );
+
+    // legacy layouts and descriptors never share a length
+    let free = crate::payment::to_descriptors(&[None; 3]);
+    let args = [[1u8; 32].as_slice(), &authority, &free].concat();
+    assert_eq!(args.len(), MIN_DESCRIPTORS_ARGS_LEN);
+    for len in 0..=args.len() {
+        let valid = matches!(len, 32 | 33 | 35 | 64 | 65 | 67 | MIN_DESCRIPTORS_ARGS_LEN);
+        assert_eq!(
+            MutantArgs::parse(&args[..len]).is_ok(),
+            valid,
+            "{len} bytes"
+        );
+    }
+    let mutant = MutantArgs::parse(&args).unwrap();
+    assert_eq!(mutant.payments, [None; 3]);
+    assert_eq!(mutant.upgrade_authority, Some(&authority[..]));
+    // 65 bytes is always a payment byte and an authority
+    let mutant = MutantArgs::parse(&args[..65]).unwrap();
+    assert_eq!(mutant.payments, [Some(Payment::PowerOfTen(7)), None, None]);
+    assert_eq!(mutant.upgrade_authority.map(<[u8]>::len), Some(32));
+}
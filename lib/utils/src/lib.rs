@@ -11,10 +11,19 @@ use ckb_std::high_level::{
     load_cell, load_cell_data, load_cell_lock_hash, load_cell_type, load_cell_type_hash,
     load_input, QueryIter,
 };
+use spore_errors::error::Error;
+use spore_types::generated::spore_types::{ClusterData, ClusterDataV2};
 
 pub use mime::MIME;
 
+pub mod content_ref;
+pub mod extension;
 mod mime;
+pub mod multipart;
+pub mod payment;
+pub mod rules;
+pub mod segment;
+pub mod transaction;
 
 pub fn verify_type_id(index: usize, source: Source) -> bool {
     let first_input = match load_input(0, Source::Input) {
@@ -126,3 +135,63 @@ pub fn calc_capacity_sum(lock_hash: &[u8; 32], source: Source) -> u128 {
         .map(|cell| cell.capacity().unpack() as u128)
         .sum()
 }
+
+pub fn parse_cluster_data(raw_data: &[u8]) -> Result<ClusterDataV2, Error> {
+    // versions are told apart by their exact layout, so data of another layout is refused instead
+    // of being read as an older version, and ClusterData is upgraded with default values of the
+    // newer fields
+    ClusterDataV2::from_slice(raw_data)
+        .or_else(|_| ClusterData::from_slice(raw_data).map(Into::into))
+        .map_err(|_| Error::InvalidClusterData)
+}
+
+pub fn load_cluster_data(index: usize, source: Source) -> Result<ClusterDataV2, Error> {
+    let raw_data = load_cell_data(index, source)?;
+    parse_cluster_data(raw_data.as_slice())
+}
+
+#[test]
+fn test_cluster_data_versions() {
+    use alloc::vec::Vec;
+    use spore_types::generated::spore_types::{Bool, Bytes};
+
+    let name: Bytes = "cluster".as_bytes().into();
+    let v1 = ClusterData::new_builder().name(name.clone()).build();
+    let upgraded = parse_cluster_data(v1.as_slice()).unwrap();
+    assert_eq!(upgraded.name().as_slice(), name.as_slice());
+    assert!(!bool::from(upgraded.closed()));
+    let v2 = ClusterDataV2::new_builder()
+        .name(name.clone())
+        .closed(Bool::from(true))
+        .build();
+    assert_eq!(
+        parse_cluster_data(v2.as_slice()).unwrap().as_slice(),
+        v2.as_slice()
+    );
+
+    // a table with the first fields of ClusterDataV2 is neither version, not a closed ClusterData
+    let fields = [
+        v1.name().as_slice().to_vec(),
+        v1.description().as_slice().to_vec(),
+        v1.mutant_id().as_slice().to_vec(),
+        v2.closed().as_slice().to_vec(),
+    ];
+    let header_size = 4 * (fields.len() + 1);
+    let mut table = Vec::new();
+    let mut offset = header_size;
+    for field in &fields {
+        table.extend((offset as u32).to_le_bytes());
+        offset += field.len();
+    }
+    table.splice(0..0, (offset as u32).to_le_bytes());
+    fields.iter().for_each(|field| table.extend(field));
+    assert!(ClusterData::from_compatible_slice(&table).is_ok());
+    assert_eq!(
+        parse_cluster_data(&table).err(),
+        Some(Error::InvalidClusterData)
+    );
+    assert_eq!(
+        parse_cluster_data(&v2.as_slice()[..v2.as_slice().len() - 1]).err(),
+        Some(Error::InvalidClusterData)
+    );
+}
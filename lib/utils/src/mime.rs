@@ -1,26 +1,30 @@
 use alloc::ffi::CString;
+#[cfg(test)]
+use alloc::format;
 use alloc::str;
+use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 use ckb_std::debug;
 use ckb_std::high_level::decode_hex;
 use spore_errors::error::Error;
 
-type RangePair = core::ops::Range<usize>;
+// RFC 6838 limits type and subtype names to 127 characters
+const MAX_NAME_LEN: usize = 127;
 
-#[derive(Debug, Clone)]
-enum ParamType {
-    Generic(RangePair),
-    Immortal(RangePair),
-    Mutant(RangePair),
-}
+const TSPECIALS: &str = "()<>@,;:\\\"/[]?=";
 
+/// Content-type as of RFC 2045, with type, subtype and parameter names lowercased and
+/// parameter values unescaped
 #[derive(Debug, Clone)]
 pub struct MIME {
-    pub main_type: RangePair,
-    pub sub_type: RangePair,
-    params: Vec<(RangePair, RangePair)>,
+    pub main_type: String,
+    pub sub_type: String,
+    params: Vec<(String, String)>,
     pub mutants: Vec<[u8; 32]>,
+    // data hashes of the mutants' code, pinned in the order of `mutants`
+    pub mutant_codes: Vec<[u8; 32]>,
     pub immortal: bool,
+    pub mutable_content: bool,
 }
 
 impl MIME {
@@ -31,198 +35,258 @@ impl MIME {
         }
         .trim_start()
         .trim_end();
-        Self::str_parse(&content_type)
+        Self::str_parse(content_type)
+    }
+
+    /// Content-type of a Spore already created, which may predate the grammar of `parse`: those
+    /// are read the way they were minted, so they can still be transferred and melted. Only
+    /// their mutants and immortality meant something then, other params are left out
+    pub fn parse_existing(raw_content_type: &[u8]) -> Result<MIME, Error> {
+        Self::parse(raw_content_type).or_else(|error| {
+            let content_type = str::from_utf8(raw_content_type).map_err(|_| Error::Illformed)?;
+            legacy_parse(content_type.trim()).map_err(|_| error)
+        })
     }
 
     pub fn str_parse(content_type: &str) -> Result<Self, Error> {
         debug!("Content type is: {}", content_type);
-        // main_type.len() + '/' + sub_type.len() + '+' +
-        let (main_type, right) = match content_type.find('/') {
-            Some(pos) => (0usize..pos, pos..content_type.len()),
-            _ => return Err(Error::Illformed),
-        };
+        let (main_type, right) = content_type.split_once('/').ok_or(Error::Illformed)?;
+        let main_type = parse_name(main_type).ok_or(Error::InvaliMainType)?;
+        let sub_end = right
+            .find(|c: char| c == ';' || is_ows(c))
+            .unwrap_or(right.len());
+        let sub_type = parse_name(&right[..sub_end]).ok_or(Error::InvalidSubType)?;
 
-        if !is_restricted_name(&content_type[main_type.clone()]) {
-            return Err(Error::InvaliMainType);
+        let mut mime_type = MIME {
+            main_type,
+            sub_type,
+            params: Vec::new(),
+            mutants: Vec::new(),
+            mutant_codes: Vec::new(),
+            immortal: false,
+            mutable_content: false,
+        };
+        let mut right = &right[sub_end..];
+        loop {
+            right = right.trim_start_matches(is_ows);
+            if right.is_empty() {
+                break;
+            }
+            right = right
+                .strip_prefix(';')
+                .ok_or(Error::InvalidParams)?
+                .trim_start_matches(is_ows);
+            // a trailing semicolon is tolerated
+            if right.is_empty() {
+                break;
+            }
+            let (name, value, quoted, rest) = parse_param(right)?;
+            // as for the Spores minted before this grammar, only `immortal=true` as written
+            // makes a Spore immortal
+            mime_type.immortal |= name == "immortal" && value == "true" && !quoted;
+            mime_type.push_param(name.to_ascii_lowercase(), value)?;
+            right = rest;
         }
-
-        if !content_type[right.clone()].chars().any(is_restricted_char) {
-            return Err(Error::Illformed);
+        // pinning covers every mutant
+        if !mime_type.mutant_codes.is_empty()
+            && mime_type.mutant_codes.len() != mime_type.mutants.len()
+        {
+            return Err(Error::MutantCodeNotValid);
         }
 
-        let sub_end = content_type[right.clone()].find(';').unwrap_or(right.len()) + main_type.end;
-        let sub_type = main_type.end + 1..sub_end;
-
-        let mut vec = Vec::new();
-        let _right_part = &content_type[sub_end..];
-        let mut offset = sub_end;
-        let mut mutants = Vec::new();
-        let mut immortal = false;
-        while let Some((name_range, value_range, new_offset)) = parse_param(content_type, offset)? {
-            match name_range {
-                ParamType::Mutant(name_range) => {
-                    vec.push((name_range, value_range.clone()));
-                    let value = &content_type[value_range];
-                    for mutant_id in value.split(',') {
-                        // hexed mutant id doesn't have a prefix '0x'
-                        let mutant_id_hex = mutant_id.trim_matches(is_ows);
-                        if mutant_id_hex.len() != 64 {
-                            return Err(Error::MutantIDNotValid);
-                        }
-                        let mutant_id_c_str =
-                            CString::new(mutant_id_hex).map_err(|_| Error::MutantIDNotValid)?;
-                        let mutant_id: [u8; 32] = decode_hex(mutant_id_c_str.as_c_str())
-                            .map_err(|_| Error::MutantIDNotValid)?
-                            .try_into()
-                            .unwrap();
-                        mutants.push(mutant_id);
-                    }
-                }
-                ParamType::Generic(name_range) => {
-                    vec.push((name_range, value_range));
-                }
-                ParamType::Immortal(name_range) => {
-                    immortal = &content_type[value_range.clone()] == "true";
-                    vec.push((name_range, value_range));
-                }
+        Ok(mime_type)
+    }
+
+    fn push_param(&mut self, name: String, value: String) -> Result<(), Error> {
+        if self.get_param(&name).is_some() {
+            return Err(Error::InvalidParams);
+        }
+        match name.as_str() {
+            "mutant[]" => {
+                self.mutants = parse_hash_list(&value).ok_or(Error::MutantIDNotValid)?;
             }
-            offset = new_offset;
+            "mutant-code[]" => {
+                self.mutant_codes = parse_hash_list(&value).ok_or(Error::MutantCodeNotValid)?;
+            }
+            "mutable" => {
+                // other values are refused on mint, see `verify_spore_creation`
+                self.mutable_content = value == "content";
+            }
+            _ => (),
         }
-
-        let mime_type = MIME {
-            main_type: main_type,
-            sub_type: sub_type,
-            params: vec,
-            mutants,
-            immortal,
-        };
-
-        Ok(mime_type)
+        self.params.push((name, value));
+        Ok(())
     }
 
-    pub fn params(&self) -> &Vec<(RangePair, RangePair)> {
+    pub fn params(&self) -> &Vec<(String, String)> {
         &self.params
     }
 
-    pub fn mut_params(&mut self) -> &mut Vec<(RangePair, RangePair)> {
-        &mut self.params
+    /// Value of a parameter, names are case-insensitive
+    pub fn get_param(&self, param: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(param))
+            .map(|(_, value)| value.as_str())
     }
 
-    pub fn get_param(&self, content_type: &[u8], param: &str) -> Option<RangePair> {
-        for (param_range, value_range) in self.params.iter() {
-            if content_type[param_range.clone()] == param.as_bytes()[..] {
-                return Some(value_range.clone());
-            }
-        }
-        None
+    pub fn verify_param(&self, param: &str, value: &str) -> bool {
+        self.get_param(param) == Some(value)
     }
+}
 
-    pub fn verify_param(&self, content_type: &[u8], param: &str, value: &[u8]) -> bool {
-        for (param_range, value_range) in self.params.iter() {
-            if content_type[param_range.clone()] == param.as_bytes()[..] {
-                return content_type[value_range.clone()] == value[..];
-            }
-        }
-        false
+/// Type or subtype name, lowercased
+fn parse_name(s: &str) -> Option<String> {
+    if s.is_empty() || s.len() > MAX_NAME_LEN || !s.chars().all(is_token_char) {
+        return None;
     }
+    Some(s.to_ascii_lowercase())
 }
 
-pub fn is_restricted_name(s: &str) -> bool {
-    s.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '*') && is_restricted_str(s)
+/// RFC 2045 token characters: visible US-ASCII except tspecials
+pub fn is_token_char(c: char) -> bool {
+    c.is_ascii_graphic() && !TSPECIALS.contains(c)
 }
 
-pub fn is_restricted_name_patched(s: &str) -> bool {
-    s == "mutant[]" || is_restricted_name(s)
+/// Text allowed in a quoted-string, with UTF-8 beyond US-ASCII as of RFC 6532
+fn is_qtext(c: char) -> bool {
+    c == '\t' || (c != '"' && c != '\\' && !c.is_ascii_control())
 }
 
-pub fn is_restricted_value_char(c: char) -> bool {
-    c.is_ascii_alphanumeric()
-        || matches!(
-            c,
-            '!' | '#' | '$' | '&' | '-' | '^' | '_' | '.' | '+' | '%' | '*' | '\'' | ','
-        )
+pub const fn is_ows(c: char) -> bool {
+    c == ' ' || c == '\t'
 }
 
-pub fn is_restricted_str(s: &str) -> bool {
-    s.chars().all(is_restricted_char)
+/// Parses `name=value` at the start of `s`, returning the name as written, the unescaped value,
+/// whether it was quoted and what follows the value
+fn parse_param(s: &str) -> Result<(&str, String, bool, &str), Error> {
+    let (name, right) = s.split_once('=').ok_or(Error::InvalidParams)?;
+    // `[` and `]` are tspecials, `mutant[]` is kept for compatibility
+    if name.is_empty() || (!is_list_param(name) && !name.chars().all(is_token_char)) {
+        return Err(Error::InvalidParams);
+    }
+
+    if let Some(quoted) = right.strip_prefix('"') {
+        let (value, len) = parse_quoted_value(quoted)?;
+        return Ok((name, value, true, &quoted[len..]));
+    }
+    // a list of mutant ids or code hashes is comma-separated, even unquoted
+    let value_len = right
+        .find(|c: char| !(is_token_char(c) || (c == ',' && is_list_param(name))))
+        .unwrap_or(right.len());
+    if value_len == 0 {
+        return Err(Error::InvalidParamValue);
+    }
+    Ok((
+        name,
+        right[..value_len].to_string(),
+        false,
+        &right[value_len..],
+    ))
 }
 
-pub fn is_restricted_char(c: char) -> bool {
-    c.is_ascii_alphanumeric()
-        || matches!(
-            c,
-            '!' | '#' | '$' | '&' | '-' | '^' | '_' | '.' | '+' | '%' | '*' | '\''
-        )
+fn is_list_param(name: &str) -> bool {
+    name.eq_ignore_ascii_case("mutant[]") || name.eq_ignore_ascii_case("mutant-code[]")
 }
 
-pub const fn is_ows(c: char) -> bool {
-    c == ' ' || c == '\t'
+/// Comma-separated 32-byte hashes in hex, without a `0x` prefix
+pub(crate) fn parse_hash_list(value: &str) -> Option<Vec<[u8; 32]>> {
+    value
+        .split(',')
+        .map(|hash| {
+            let hash = hash.trim_matches(is_ows);
+            if hash.len() != 64 {
+                return None;
+            }
+            let hash = CString::new(hash).ok()?;
+            decode_hex(hash.as_c_str()).ok()?.try_into().ok()
+        })
+        .collect()
 }
 
-fn parse_param(
-    source: &str,
-    offset: usize,
-) -> Result<Option<(ParamType, RangePair, usize)>, Error> {
-    if offset >= source.len() {
-        return Ok(None);
+/// Content-type as parsed before the RFC 2045 grammar, with its mutants and immortality only
+fn legacy_parse(content_type: &str) -> Result<MIME, Error> {
+    let slash = content_type.find('/').ok_or(Error::Illformed)?;
+    let main_type = &content_type[..slash];
+    if !main_type.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '*')
+        || !main_type.chars().all(is_legacy_char)
+    {
+        return Err(Error::InvaliMainType);
     }
-    let s = &source[offset..];
-    let (lhs, rhs) = match s.split_once(';') {
-        Some((lhs, rhs)) if lhs.chars().all(is_ows) && rhs.chars().all(is_ows) => return Ok(None),
-        Some((lhs, rhs)) if lhs.chars().all(is_ows) => (lhs, rhs),
-        _ if s.chars().all(is_ows) => return Ok(None),
-        _ => return Err(Error::InvalidParams),
-    };
-
-    let (name, value) = match rhs.split_once('=') {
-        Some((name, value_maybe)) => match value_maybe.split_once(';') {
-            None => (name, value_maybe),
-            Some((value_maybe_lhs, _)) => (name, value_maybe_lhs),
-        },
-        _ => return Err(Error::InvalidParams),
-    };
-
-    let key_trimmed = name.trim_start_matches(is_ows).len();
-    let key_start = lhs.len() + 1 + name.len() - key_trimmed;
-    let key_range = key_start + offset..key_start + offset + key_trimmed;
-    if !is_restricted_name_patched(&source[key_range.clone()]) {
-        return Err(Error::InvalidParams);
+    let right = &content_type[slash..];
+    if !right.chars().any(is_legacy_char) {
+        return Err(Error::Illformed);
     }
-    let key = match &source[key_range.clone()] {
-        "immortal" => ParamType::Immortal(key_range.clone()),
-        "mutant[]" => ParamType::Mutant(key_range.clone()),
-        _ => ParamType::Generic(key_range.clone()),
+    let sub_end = slash + right.find(';').unwrap_or(right.len());
+
+    let mut mime_type = MIME {
+        main_type: main_type.to_string(),
+        sub_type: content_type[slash + 1..sub_end].to_string(),
+        params: Vec::new(),
+        mutants: Vec::new(),
+        mutant_codes: Vec::new(),
+        immortal: false,
+        mutable_content: false,
     };
-    let value_start = key_range.end + 1;
-    if let Some(value) = value.strip_prefix('\"') {
-        let value_end = value_start + parse_quoted_value(value)? + 1;
-        let value_range = value_start..value_end;
-        Ok(Some((key.clone(), value_range.clone(), value_end)))
-    } else {
-        let value_end = value_start
-            + value
+    let mut rest = &content_type[sub_end..];
+    loop {
+        let params = match rest.split_once(';') {
+            Some((lhs, params)) if lhs.chars().all(is_ows) => params,
+            _ if rest.chars().all(is_ows) => break,
+            _ => return Err(Error::InvalidParams),
+        };
+        if params.chars().all(is_ows) {
+            break;
+        }
+        let (name, after_name) = params.split_once('=').ok_or(Error::InvalidParams)?;
+        let name = name.trim_start_matches(is_ows);
+        let is_legacy_name = name.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '*')
+            && name.chars().all(is_legacy_char);
+        if name != "mutant[]" && !is_legacy_name {
+            return Err(Error::InvalidParams);
+        }
+        // the value stops at the next semicolon, quoted or not
+        let value = after_name
+            .split_once(';')
+            .map_or(after_name, |(value, _)| value);
+        let value_len = match value.strip_prefix('"') {
+            Some(quoted) => legacy_quoted_len(quoted)? + 1,
+            None => value
                 .chars()
-                .take_while(|&c| is_restricted_value_char(c))
+                .take_while(|&c| is_legacy_char(c) || c == ',')
                 .map(char::len_utf8)
-                .sum::<usize>();
-        let value_range = value_start..value_end;
-        Ok(Some((key.clone(), value_range.clone(), value_end)))
+                .sum(),
+        };
+        let value = &value[..value_len];
+        match name {
+            "immortal" => mime_type.immortal = value == "true",
+            "mutant[]" => {
+                let mutants = parse_hash_list(value).ok_or(Error::MutantIDNotValid)?;
+                mime_type.mutants.extend(mutants);
+            }
+            _ => (),
+        }
+        rest = &after_name[value_len..];
     }
+    Ok(mime_type)
+}
+
+fn is_legacy_char(c: char) -> bool {
+    c.is_ascii_alphanumeric()
+        || matches!(
+            c,
+            '!' | '#' | '$' | '&' | '-' | '^' | '_' | '.' | '+' | '%' | '*' | '\''
+        )
 }
 
-pub fn parse_quoted_value(s: &str) -> Result<usize, Error> {
-    let mut len = 0;
+/// Length of a legacy quoted value following its opening quote, including the closing quote
+fn legacy_quoted_len(s: &str) -> Result<usize, Error> {
     let mut escaped = false;
-    for c in s.chars() {
-        len += c.len_utf8();
+    for (index, c) in s.char_indices() {
         match c {
-            _ if escaped => {
-                escaped = false;
-            }
-            '\\' => {
-                escaped = true;
-            }
-            '"' => return Ok(len),
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return Ok(index + 1),
             '\n' => return Err(Error::InvalidParamValue),
             _ => (),
         }
@@ -230,30 +294,263 @@ pub fn parse_quoted_value(s: &str) -> Result<usize, Error> {
     Err(Error::InvalidParamValue)
 }
 
+/// Unescapes a quoted-string following its opening quote, returning the value and the length
+/// consumed including the closing quote
+pub fn parse_quoted_value(s: &str) -> Result<(String, usize), Error> {
+    let mut value = String::new();
+    let mut chars = s.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => return Ok((value, index + 1)),
+            '\\' => match chars.next() {
+                Some((_, escaped)) if is_qtext(escaped) || escaped == '"' || escaped == '\\' => {
+                    value.push(escaped)
+                }
+                _ => return Err(Error::InvalidParamValue),
+            },
+            c if is_qtext(c) => value.push(c),
+            _ => return Err(Error::InvalidParamValue),
+        }
+    }
+    Err(Error::InvalidParamValue)
+}
+
 #[test]
 fn test_basic() {
+    let mutants = "image/png;immortal=true;mutant[]=\
+        0000000000000000000000000000000000000000000000000000000000000002,\
+        0000000000000000000000000000000000000000000000000000000000000003";
     assert!(MIME::str_parse("image/png").is_ok());
     assert!(MIME::str_parse("image/png;immortal=true").is_ok());
-    assert!(MIME::str_parse("image/png;immortal=true;mutant[]=2,3,4,5").is_ok());
-    assert!(MIME::str_parse("image/png;immortal=true;mutant[]=2,3,4,5")
+    assert!(MIME::str_parse(mutants).is_ok());
+    assert!(MIME::str_parse(mutants)
         .map_err(|_| "mutant verify_param")
         .unwrap()
-        .verify_param(
-            b"image/png;immortal=true;mutant[]=2,3,4,5",
-            "mutant[]",
-            b"2,3,4,5"
-        ));
-    assert!(MIME::str_parse("image/png;immortal=true;mutant[]=2,3,4,5")
+        .verify_param("mutant[]", &mutants[33..]));
+    assert!(MIME::str_parse(mutants)
         .map_err(|_| "mutant verify_param")
         .unwrap()
-        .verify_param(
-            b"image/png;immortal=true;mutant[]=2,3,4,5",
-            "immortal",
-            b"true"
-        ));
+        .verify_param("immortal", "true"));
     assert!(MIME::str_parse("image/").is_err());
     assert!(MIME::str_parse("image/;").is_err());
     assert!(MIME::str_parse("/;").is_err());
     assert!(MIME::str_parse(";").is_err());
     assert!(MIME::str_parse("").is_err());
 }
+
+#[test]
+fn test_mutant_ids() {
+    let mime = MIME::str_parse(
+        "image/png;mutant[]=\
+        0000000000000000000000000000000000000000000000000000000000000002,\
+        0000000000000000000000000000000000000000000000000000000000000003",
+    )
+    .map_err(|_| "mutant ids")
+    .unwrap();
+    assert_eq!(mime.mutants.len(), 2);
+    assert_eq!(
+        MIME::str_parse("image/png;immortal=true;mutant[]=2,3,4,5").err(),
+        Some(Error::MutantIDNotValid)
+    );
+}
+
+#[test]
+fn test_existing_content_types() {
+    let mutant = "02".repeat(32);
+    // content-types minted before the grammar, with their mutants and immortality
+    let legacy = [
+        (
+            format!("image/p@ng;immortal=true;mutant[]={mutant}"),
+            true,
+            1,
+        ),
+        (String::from("image/svg xml; immortal=true"), true, 0),
+        (
+            String::from("image/png;immortal=\"true\";immortal=false"),
+            false,
+            0,
+        ),
+        (
+            String::from("text/plain;mutable=content;mutable=none"),
+            false,
+            0,
+        ),
+    ];
+    for (content_type, immortal, mutants) in legacy {
+        let mime = MIME::parse_existing(content_type.as_bytes())
+            .map_err(|_| "existing content-type")
+            .unwrap();
+        assert_eq!(mime.immortal, immortal, "{}", content_type);
+        assert_eq!(mime.mutants.len(), mutants, "{}", content_type);
+    }
+    // neither way parses it, the error is the one of the grammar
+    assert_eq!(
+        MIME::parse_existing(b"image/png;mutant[]=2").err(),
+        Some(Error::MutantIDNotValid)
+    );
+
+    // only `immortal=true` as written makes a Spore immortal, as it did before
+    for content_type in ["image/png;IMMORTAL=true", "image/png;immortal=\"true\""] {
+        let mime = MIME::str_parse(content_type)
+            .map_err(|_| "immortal as written")
+            .unwrap();
+        assert!(!mime.immortal, "{}", content_type);
+    }
+}
+
+#[test]
+fn test_mutable() {
+    assert!(
+        MIME::str_parse("application/json;mutable=content")
+            .map_err(|_| "mutable content")
+            .unwrap()
+            .mutable_content
+    );
+    assert!(
+        !MIME::str_parse("application/json;immortal=true")
+            .map_err(|_| "immutable content")
+            .unwrap()
+            .mutable_content
+    );
+    // unknown values only make a Spore fail to mint, existing ones stay immutable
+    assert!(
+        !MIME::str_parse("application/json;mutable=content_type")
+            .map_err(|_| "unknown mutable")
+            .unwrap()
+            .mutable_content
+    );
+    assert!(MIME::str_parse("application/json;mutable=").is_err());
+}
+
+#[test]
+fn test_mutant_code() {
+    let id = "02".repeat(32);
+    let code = "0a".repeat(32);
+    let pinned = MIME::str_parse(&format!("text/plain;mutant-code[]={code};mutant[]={id}"))
+        .map_err(|_| "pinned mutant code")
+        .unwrap();
+    assert_eq!(pinned.mutant_codes, [[0x0a; 32]]);
+    let invalid: [(String, Error); 3] = [
+        (
+            format!("text/plain;mutant[]={id},{id};mutant-code[]={code}"),
+            Error::MutantCodeNotValid,
+        ),
+        (
+            format!("text/plain;mutant-code[]={code}"),
+            Error::MutantCodeNotValid,
+        ),
+        (
+            format!("text/plain;mutant[]={id};mutant-code[]=0a"),
+            Error::MutantCodeNotValid,
+        ),
+    ];
+    for (content_type, error) in invalid {
+        assert_eq!(MIME::str_parse(&content_type).err(), Some(error));
+    }
+}
+
+#[test]
+fn test_conformance_corpus() {
+    // examples and grammar of RFC 2045 section 5.1 and RFC 6838 section 4.2
+    let valid = [
+        "text/plain",
+        "TEXT/PLAIN",
+        "text/plain; charset=us-ascii",
+        "text/plain; charset=\"us-ascii\"",
+        "text/plain;charset=us-ascii;format=flowed",
+        "text/plain;\tcharset=us-ascii",
+        "text/plain;",
+        "application/vnd.api+json",
+        "application/x-www-form-urlencoded",
+        "image/svg+xml",
+        "audio/x-custom_type.v1",
+        "application/octet-stream; type=tar; conversions=x-compress",
+        "multipart/mixed; boundary=gc0p4Jq0M2Yt08jU534c0p",
+        "multipart/mixed; boundary=\"simple boundary\"",
+        "message/external-body; access-type=local-file; name=\"/u/nsb/Me.gif\"",
+        "text/plain; name=\"a \\\"quoted\\\" word\"",
+        "text/plain; title=\"\"",
+        "text/plain; title=\"caf\u{e9}\"",
+    ];
+    for content_type in valid {
+        assert!(MIME::str_parse(content_type).is_ok(), "{}", content_type);
+    }
+
+    let long_name = "a".repeat(MAX_NAME_LEN + 1);
+    let invalid = [
+        ("", Error::Illformed),
+        ("text", Error::Illformed),
+        ("text/", Error::InvalidSubType),
+        ("/plain", Error::InvaliMainType),
+        ("text /plain", Error::InvaliMainType),
+        ("text/ plain", Error::InvalidSubType),
+        ("te(xt/plain", Error::InvaliMainType),
+        ("text/pl@in", Error::InvalidSubType),
+        ("text/plain/html", Error::InvalidSubType),
+        ("text/plain charset=us-ascii", Error::InvalidParams),
+        ("text/plain; charset", Error::InvalidParams),
+        ("text/plain; =us-ascii", Error::InvalidParams),
+        ("text/plain; charset =us-ascii", Error::InvalidParams),
+        ("text/plain; charset=", Error::InvalidParamValue),
+        ("text/plain; charset= us-ascii", Error::InvalidParamValue),
+        ("text/plain; charset=us ascii", Error::InvalidParams),
+        ("text/plain; charset=us,ascii", Error::InvalidParams),
+        ("text/plain; a=b;; c=d", Error::InvalidParams),
+        ("text/plain; charset=\"us-ascii", Error::InvalidParamValue),
+        (
+            "text/plain; charset=\"us-ascii\\\"",
+            Error::InvalidParamValue,
+        ),
+        (
+            "text/plain; charset=\"us\nascii\"",
+            Error::InvalidParamValue,
+        ),
+        ("text/plain; charset=\"us-ascii\"x", Error::InvalidParams),
+        (
+            "text/plain; charset=us-ascii; CHARSET=utf-8",
+            Error::InvalidParams,
+        ),
+        (
+            "image/png;immortal=true;immortal=false",
+            Error::InvalidParams,
+        ),
+    ];
+    for (content_type, error) in invalid {
+        assert_eq!(
+            MIME::str_parse(content_type).err(),
+            Some(error),
+            "{}",
+            content_type
+        );
+    }
+    assert!(MIME::str_parse(&alloc::format!("{}/plain", long_name)).is_err());
+    assert!(MIME::str_parse(&alloc::format!("text/{}", long_name)).is_err());
+}
+
+#[test]
+fn test_case_and_unescaping() {
+    let mime = MIME::str_parse("Text/HTML; CharSet=\"UTF-8\"; Title=\"a \\\"b\\\" \\\\ c\"")
+        .map_err(|_| "normalized")
+        .unwrap();
+    assert_eq!(mime.main_type, "text");
+    assert_eq!(mime.sub_type, "html");
+    // names are case-insensitive, values are kept as they are
+    assert_eq!(mime.get_param("charset"), Some("UTF-8"));
+    assert_eq!(mime.get_param("CHARSET"), Some("UTF-8"));
+    assert_eq!(mime.get_param("title"), Some("a \"b\" \\ c"));
+    assert_eq!(
+        mime.params()
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>(),
+        ["charset", "title"]
+    );
+
+    assert_eq!(
+        MIME::str_parse("image/png;IMMORTAL=true")
+            .map_err(|_| "immortal")
+            .unwrap()
+            .get_param("immortal"),
+        Some("true")
+    );
+}
@@ -0,0 +1,299 @@
+//! `multipart/*` content as of RFC 2046 section 5.1.1
+
+use alloc::format;
+use alloc::vec::Vec;
+use spore_errors::error::Error;
+
+use crate::MIME;
+
+// parts of a multipart Spore, nested parts included
+pub const MAX_MULTIPART_PARTS: usize = 32;
+
+/// A body part with its parsed `Content-Type` header
+#[derive(Debug, Clone)]
+pub struct Part<'a> {
+    pub content_type: MIME,
+    pub body: &'a [u8],
+}
+
+/// Checks a multipart content, and the multipart parts it nests
+pub fn verify_multipart(mime: &MIME, content: &[u8]) -> Result<(), Error> {
+    let mut parts_left = MAX_MULTIPART_PARTS;
+    verify_nested_multipart(mime, content, &mut parts_left)
+}
+
+fn verify_nested_multipart(
+    mime: &MIME,
+    content: &[u8],
+    parts_left: &mut usize,
+) -> Result<(), Error> {
+    if mime.main_type != "multipart" {
+        return Ok(());
+    }
+    let boundary = mime
+        .get_param("boundary")
+        .ok_or(Error::InvalidContentType)?;
+    for part in parse_multipart(boundary, content)? {
+        *parts_left = parts_left
+            .checked_sub(1)
+            .ok_or(Error::MultipartPartsExceeded)?;
+        verify_nested_multipart(&part.content_type, part.body, parts_left)?;
+    }
+    Ok(())
+}
+
+/// 1 to 70 characters out of `bchars`, not ending with a space
+pub fn is_valid_boundary(boundary: &str) -> bool {
+    (1..=70).contains(&boundary.len())
+        && !boundary.ends_with(' ')
+        && boundary
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "'()+_,-./:=? ".contains(c))
+}
+
+/// Splits a multipart body into its parts, lines end with CRLF or a bare LF
+pub fn parse_multipart<'a>(boundary: &str, content: &'a [u8]) -> Result<Vec<Part<'a>>, Error> {
+    if !is_valid_boundary(boundary) {
+        return Err(Error::InvalidContentType);
+    }
+    let dash_boundary = format!("--{}", boundary);
+
+    let mut parts = Vec::new();
+    let mut current: Option<PartBuilder> = None;
+    let mut closed = false;
+    for line in lines(content) {
+        let delimiter = match delimiter_kind(line.text, dash_boundary.as_bytes())? {
+            Some(delimiter) => delimiter,
+            // the preamble before the first delimiter is ignored
+            None => {
+                if let Some(part) = current.as_mut() {
+                    part.push_line(&line)?;
+                }
+                continue;
+            }
+        };
+        if let Some(part) = current.take() {
+            if parts.len() == MAX_MULTIPART_PARTS {
+                return Err(Error::MultipartPartsExceeded);
+            }
+            parts.push(part.finish(content, line.start)?);
+        }
+        match delimiter {
+            Delimiter::Next => current = Some(PartBuilder::default()),
+            // the epilogue after the close delimiter is ignored
+            Delimiter::Close => {
+                closed = true;
+                break;
+            }
+        }
+    }
+
+    if !closed || parts.is_empty() {
+        return Err(Error::InvalidMultipartContent);
+    }
+    Ok(parts)
+}
+
+struct Line<'a> {
+    start: usize,
+    end: usize, // line break included
+    text: &'a [u8],
+}
+
+fn lines(content: &[u8]) -> impl Iterator<Item = Line<'_>> {
+    let mut offset = 0;
+    core::iter::from_fn(move || {
+        if offset >= content.len() {
+            return None;
+        }
+        let start = offset;
+        let end = content[start..]
+            .iter()
+            .position(|&c| c == b'\n')
+            .map(|position| start + position + 1)
+            .unwrap_or(content.len());
+        offset = end;
+        let text = content[start..end]
+            .strip_suffix(b"\n")
+            .unwrap_or(&content[start..end]);
+        let text = text.strip_suffix(b"\r").unwrap_or(text);
+        Some(Line { start, end, text })
+    })
+}
+
+enum Delimiter {
+    Next,
+    Close,
+}
+
+fn is_transport_padding(s: &[u8]) -> bool {
+    s.iter().all(|&c| c == b' ' || c == b'\t')
+}
+
+fn delimiter_kind(line: &[u8], dash_boundary: &[u8]) -> Result<Option<Delimiter>, Error> {
+    let rest = match line.strip_prefix(dash_boundary) {
+        Some(rest) => rest,
+        None => return Ok(None),
+    };
+    match rest.strip_prefix(b"--") {
+        Some(padding) if is_transport_padding(padding) => Ok(Some(Delimiter::Close)),
+        None if is_transport_padding(rest) => Ok(Some(Delimiter::Next)),
+        // the boundary can't start any other line
+        _ => Err(Error::InvalidMultipartContent),
+    }
+}
+
+#[derive(Default)]
+struct PartBuilder {
+    headers: Vec<Vec<u8>>,
+    body_start: Option<usize>,
+}
+
+impl PartBuilder {
+    fn push_line(&mut self, line: &Line) -> Result<(), Error> {
+        if self.body_start.is_some() {
+            return Ok(());
+        }
+        match line.text.first() {
+            // headers end with an empty line
+            None => self.body_start = Some(line.end),
+            // folded header
+            Some(b' ' | b'\t') => self
+                .headers
+                .last_mut()
+                .ok_or(Error::InvalidMultipartContent)?
+                .extend_from_slice(line.text),
+            Some(_) => self.headers.push(line.text.to_vec()),
+        }
+        Ok(())
+    }
+
+    fn finish<'a>(self, content: &'a [u8], delimiter_start: usize) -> Result<Part<'a>, Error> {
+        let mut content_type = None;
+        for header in self.headers.iter() {
+            let colon = header
+                .iter()
+                .position(|&c| c == b':')
+                .ok_or(Error::InvalidMultipartContent)?;
+            let name = &header[..colon];
+            if name.is_empty() || !name.iter().all(|c| c.is_ascii_graphic()) {
+                return Err(Error::InvalidMultipartContent);
+            }
+            if !name.eq_ignore_ascii_case(b"content-type") {
+                continue;
+            }
+            if content_type.is_some() {
+                return Err(Error::InvalidMultipartContent);
+            }
+            let mime =
+                MIME::parse(&header[colon + 1..]).map_err(|_| Error::InvalidMultipartContent)?;
+            content_type = Some(mime);
+        }
+        let content_type = content_type.ok_or(Error::InvalidMultipartContent)?;
+
+        // the line break before a delimiter belongs to the delimiter
+        let body = match self.body_start {
+            Some(start) => {
+                let line_break = if content[..delimiter_start].ends_with(b"\r\n") {
+                    2
+                } else {
+                    1
+                };
+                let end = delimiter_start.saturating_sub(line_break).max(start);
+                &content[start..end]
+            }
+            None => &content[delimiter_start..delimiter_start],
+        };
+        Ok(Part { content_type, body })
+    }
+}
+
+#[test]
+fn test_multipart() {
+    let content = b"preamble\r\n\
+        --boundary\r\n\
+        Content-Type: text/plain; charset=utf-8\r\n\
+        \r\n\
+        Hello Spore!\r\n\
+        --boundary  \r\n\
+        content-type:\r\n image/png\r\n\
+        X-Extra: ignored\r\n\
+        \r\n\
+        PNG\r\n\
+        \r\n\
+        --boundary--\r\n\
+        epilogue";
+    let parts = parse_multipart("boundary", content)
+        .map_err(|_| "multipart")
+        .unwrap();
+    assert_eq!(parts.len(), 2);
+    assert_eq!(parts[0].content_type.sub_type, "plain");
+    assert_eq!(parts[0].body, b"Hello Spore!");
+    assert_eq!(parts[1].content_type.main_type, "image");
+    assert_eq!(parts[1].body, b"PNG\r\n");
+
+    let lf_only = b"--b\nContent-Type: text/plain\n\nHello\n--b--";
+    assert!(parse_multipart("b", lf_only).is_ok());
+    let empty_body = b"--b\nContent-Type: text/plain\n--b\nContent-Type: text/plain\n\n--b--\n";
+    let parts = parse_multipart("b", empty_body)
+        .map_err(|_| "empty body")
+        .unwrap();
+    assert!(parts.iter().all(|part| part.body.is_empty()));
+}
+
+#[test]
+fn test_multipart_failure() {
+    let part = "--b\r\nContent-Type: text/plain\r\n\r\nHello\r\n";
+    let invalid: [(&str, &[u8], Error); 10] = [
+        ("", b"", Error::InvalidContentType),
+        ("b ", b"", Error::InvalidContentType),
+        ("b", b"no delimiter at all", Error::InvalidMultipartContent),
+        ("b", part.as_bytes(), Error::InvalidMultipartContent),
+        ("b", b"--b--\r\n", Error::InvalidMultipartContent),
+        ("b", b"--bb\r\n--b--", Error::InvalidMultipartContent),
+        (
+            "b",
+            b"--b\r\n\r\nno content type\r\n--b--",
+            Error::InvalidMultipartContent,
+        ),
+        (
+            "b",
+            b"--b\r\nContent-Type: text/plain\r\nContent-Type: text/html\r\n--b--",
+            Error::InvalidMultipartContent,
+        ),
+        (
+            "b",
+            b"--b\r\nContent-Type: text\r\n--b--",
+            Error::InvalidMultipartContent,
+        ),
+        (
+            "b",
+            b"--b\r\n folded first\r\n--b--",
+            Error::InvalidMultipartContent,
+        ),
+    ];
+    for (boundary, content, error) in invalid {
+        assert_eq!(parse_multipart(boundary, content).err(), Some(error));
+    }
+
+    let parts = part.repeat(MAX_MULTIPART_PARTS + 1) + "--b--";
+    assert_eq!(
+        parse_multipart("b", parts.as_bytes()).err(),
+        Some(Error::MultipartPartsExceeded)
+    );
+    let parts = part.repeat(MAX_MULTIPART_PARTS) + "--b--";
+    assert!(parse_multipart("b", parts.as_bytes()).is_ok());
+
+    // nested parts count towards the same cap
+    let nested = format!(
+        "--outer\r\nContent-Type: multipart/mixed; boundary=b\r\n\r\n{}--b--\r\n--outer--",
+        part.repeat(MAX_MULTIPART_PARTS)
+    );
+    let mime = MIME::str_parse("multipart/mixed; boundary=outer")
+        .map_err(|_| "mime")
+        .unwrap();
+    assert_eq!(
+        verify_multipart(&mime, nested.as_bytes()).err(),
+        Some(Error::MultipartPartsExceeded)
+    );
+}
@@ -0,0 +1,204 @@
+//! Payments to a lock, asked by cluster proxies for agent creation and by mutants on operations
+//!
+//! A payment is the legacy single byte `n` for 10^n shannons, or a tag followed by its amount:
+//! `0 | u64` for exact shannons and `1 | xUDT type hash | u128` for an xUDT amount, little endian.
+
+use alloc::vec::Vec;
+use ckb_std::ckb_constants::Source::{Input, Output};
+use spore_errors::error::Error;
+
+use crate::transaction::{capacity_sum, udt_sum, Cells};
+
+/// Version of the payment descriptors, see `parse_descriptors`
+pub const PAYMENT_DESCRIPTORS_V1: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Payment {
+    /// 10^n shannons, the legacy single byte. Cluster agents used to check it the other way round,
+    /// refusing to overpay and accepting less; the receiver now gets at least 10^n like any payment
+    PowerOfTen(u8),
+    /// exact shannons, tagged 0 and followed by u64 in little endian
+    Exact(u64),
+    /// tagged 1, followed by the xUDT type hash and u128 amount in little endian
+    Xudt { type_hash: [u8; 32], amount: u128 },
+}
+
+impl Payment {
+    pub fn parse(args: &[u8]) -> Result<Self, Error> {
+        match args {
+            [power] => Ok(Self::PowerOfTen(*power)),
+            [0, amount @ ..] => amount
+                .try_into()
+                .map(|amount| Self::Exact(u64::from_le_bytes(amount)))
+                .map_err(|_| Error::PaymentMethodNotSupport),
+            [1, payment @ ..] if payment.len() == 48 => Ok(Self::Xudt {
+                type_hash: payment[..32].try_into().unwrap_or_default(),
+                amount: u128::from_le_bytes(payment[32..].try_into().unwrap_or_default()),
+            }),
+            _ => Err(Error::PaymentMethodNotSupport),
+        }
+    }
+
+    /// Parses the payment following the cluster ID in cluster proxy args. Legacy proxies only had
+    /// their first byte read whatever followed it, so bytes not laid out as a tagged payment still
+    /// ask for 10^n of it
+    pub fn parse_proxy(args: &[u8]) -> Result<Self, Error> {
+        match args {
+            [power, ..] => Ok(Self::parse(args).unwrap_or(Self::PowerOfTen(*power))),
+            [] => Err(Error::PaymentMethodNotSupport),
+        }
+    }
+
+    pub fn to_args(&self) -> Vec<u8> {
+        match self {
+            Self::PowerOfTen(power) => [*power].to_vec(),
+            Self::Exact(amount) => [&[0u8][..], &amount.to_le_bytes()].concat(),
+            Self::Xudt { type_hash, amount } => {
+                [&[1u8][..], &type_hash[..], &amount.to_le_bytes()].concat()
+            }
+        }
+    }
+
+    /// Capacity to pay in shannons, zero for xUDT
+    pub fn capacity(&self) -> u64 {
+        match self {
+            Self::PowerOfTen(power) => 10u64.saturating_pow(*power as u32),
+            Self::Exact(amount) => *amount,
+            Self::Xudt { .. } => 0,
+        }
+    }
+
+    /// Type hash of the xUDT paid, `None` for capacity
+    pub fn udt_type_hash(&self) -> Option<&[u8; 32]> {
+        match self {
+            Self::Xudt { type_hash, .. } => Some(type_hash),
+            _ => None,
+        }
+    }
+
+    /// Checks the receiver gets at least the payment, from its capacity or xUDT amount in Inputs
+    /// and Outputs
+    pub fn verify(&self, input_amount: u128, output_amount: u128) -> Result<(), Error> {
+        let amount = match self {
+            Self::PowerOfTen(power) => 10u128.saturating_pow(*power as u32),
+            Self::Exact(amount) => *amount as u128,
+            Self::Xudt { amount, .. } => *amount,
+        };
+        if output_amount < input_amount.saturating_add(amount) {
+            return Err(Error::PaymentNotEnough);
+        }
+        Ok(())
+    }
+}
+
+/// Checks the cells of `lock_hash` receive the payment in the current transaction
+pub fn verify_payment(
+    cells: &impl Cells,
+    payment: &Payment,
+    lock_hash: &[u8; 32],
+) -> Result<(), Error> {
+    match payment.udt_type_hash() {
+        Some(type_hash) => payment.verify(
+            udt_sum(cells, lock_hash, type_hash, Input),
+            udt_sum(cells, lock_hash, type_hash, Output),
+        ),
+        None => payment.verify(
+            capacity_sum(cells, lock_hash, Input),
+            capacity_sum(cells, lock_hash, Output),
+        ),
+    }
+}
+
+/// Parses `version | (len | payment) * N`, one payment per entry, free if its length is zero
+pub fn parse_descriptors<const N: usize>(bytes: &[u8]) -> Result<[Option<Payment>; N], Error> {
+    let mut rest = match bytes.split_first() {
+        Some((&PAYMENT_DESCRIPTORS_V1, rest)) => rest,
+        _ => return Err(Error::PaymentMethodNotSupport),
+    };
+    let mut payments = [None; N];
+    for payment in payments.iter_mut() {
+        let (len, tail) = rest.split_first().ok_or(Error::PaymentMethodNotSupport)?;
+        let (descriptor, tail) = match tail.get(..*len as usize) {
+            Some(descriptor) => (descriptor, &tail[*len as usize..]),
+            None => return Err(Error::PaymentMethodNotSupport),
+        };
+        if !descriptor.is_empty() {
+            *payment = Some(Payment::parse(descriptor)?);
+        }
+        rest = tail;
+    }
+    if !rest.is_empty() {
+        return Err(Error::PaymentMethodNotSupport);
+    }
+    Ok(payments)
+}
+
+pub fn to_descriptors(payments: &[Option<Payment>]) -> Vec<u8> {
+    let mut bytes = Vec::from([PAYMENT_DESCRIPTORS_V1]);
+    for payment in payments {
+        let descriptor = payment.map(|payment| payment.to_args()).unwrap_or_default();
+        bytes.push(descriptor.len() as u8);
+        bytes.extend(descriptor);
+    }
+    bytes
+}
+
+#[test]
+fn test_payment() {
+    let xudt = Payment::Xudt {
+        type_hash: [9; 32],
+        amount: 1_000,
+    };
+    for payment in [Payment::PowerOfTen(8), Payment::Exact(1_500), xudt] {
+        assert_eq!(Payment::parse(&payment.to_args()), Ok(payment));
+    }
+    // legacy proxy args only have their first byte read
+    assert_eq!(Payment::parse_proxy(&[8, 1, 2]), Ok(Payment::PowerOfTen(8)));
+    assert_eq!(Payment::parse_proxy(&[0, 1, 2]), Ok(Payment::PowerOfTen(0)));
+    assert_eq!(Payment::parse_proxy(&xudt.to_args()), Ok(xudt));
+    assert_eq!(
+        Payment::parse_proxy(&[]),
+        Err(Error::PaymentMethodNotSupport)
+    );
+    assert_eq!(xudt.udt_type_hash(), Some(&[9; 32]));
+    assert_eq!(Payment::Exact(1_500).udt_type_hash(), None);
+    for invalid in [&[][..], &[0, 1, 2], &[1; 40], &[2; 9]] {
+        assert_eq!(Payment::parse(invalid), Err(Error::PaymentMethodNotSupport));
+    }
+
+    // exact payment, overpaying or on top of cells the receiver already had
+    assert_eq!(Payment::Exact(1_500).verify(0, 1_500), Ok(()));
+    assert_eq!(Payment::Exact(1_500).verify(0, 2_000), Ok(()));
+    assert_eq!(Payment::Exact(1_500).verify(500, 2_000), Ok(()));
+    assert_eq!(xudt.verify(0, 1_001), Ok(()));
+    assert_eq!(Payment::PowerOfTen(3).verify(10, 1_010), Ok(()));
+    // underpaying, or the receiver spending its own cells
+    let underpaid = Err(Error::PaymentNotEnough);
+    assert_eq!(Payment::Exact(1_500).verify(0, 1_499), underpaid);
+    assert_eq!(Payment::Exact(1_500).verify(500, 1_999), underpaid);
+    assert_eq!(xudt.verify(1, 1_000), underpaid);
+    assert_eq!(Payment::PowerOfTen(3).verify(0, 999), underpaid);
+    assert_eq!(Payment::PowerOfTen(3).verify(2_000, 1_000), underpaid);
+    assert_eq!(Payment::PowerOfTen(255).verify(0, u128::MAX - 1), underpaid);
+
+    let payments = [Some(Payment::Exact(7)), None, Some(xudt)];
+    let descriptors = to_descriptors(&payments);
+    assert_eq!(descriptors.len(), 1 + 1 + 9 + 1 + 1 + 49);
+    assert_eq!(parse_descriptors::<3>(&descriptors), Ok(payments));
+    assert_eq!(
+        parse_descriptors::<3>(&to_descriptors(&[None; 3])),
+        Ok([None; 3])
+    );
+    let invalid = [
+        &descriptors[1..],
+        &descriptors[..descriptors.len() - 1],
+        &[&descriptors[..], &[0]].concat(),
+        &[PAYMENT_DESCRIPTORS_V1, 2, 0, 0, 0, 0][..],
+    ];
+    for invalid in invalid {
+        assert_eq!(
+            parse_descriptors::<3>(invalid),
+            Err(Error::PaymentMethodNotSupport)
+        );
+    }
+}
@@ -0,0 +1,604 @@
+//! Rules over cell data, shared by the contracts and the off-chain validator of `spore-sdk`
+
+use alloc::vec::Vec;
+use ckb_std::ckb_types::prelude::*;
+use ckb_std::since::{EpochNumberWithFraction, LockValue, Since};
+use spore_errors::error::Error;
+use spore_types::generated::spore_types::{ClusterDataV2, SporeData};
+
+use crate::content_ref::verify_content_ref;
+use crate::extension::MUTANT_ID_LEN;
+use crate::mime::parse_hash_list;
+use crate::multipart::verify_multipart;
+use crate::segment::verify_segmented_content;
+use crate::MIME;
+
+pub const CLUSTER_PROXY_ID_LEN: usize = 32;
+
+// agent data: proxy type hash, expiry in since format, remaining mint quota. Legacy agents only
+// hold the proxy type hash
+pub const LEGACY_AGENT_DATA_LEN: usize = 32;
+pub const AGENT_QUOTA_OFFSET: usize = 40;
+
+/// Checks the content and the content-type of a new Spore, returning the parsed content-type
+pub fn verify_spore_creation(spore_data: &SporeData) -> Result<MIME, Error> {
+    if spore_data.content().is_empty() {
+        return Err(Error::EmptyContent);
+    }
+    if spore_data.content_type().is_empty() {
+        return Err(Error::InvalidContentType);
+    }
+
+    let raw_content_type = spore_data.content_type();
+    let mime = MIME::parse(raw_content_type.unpack())?; // content_type validation
+
+    // only `content` can be declared as mutable for now
+    if mime
+        .get_param("mutable")
+        .is_some_and(|value| value != "content")
+    {
+        return Err(Error::InvalidParamValue);
+    }
+    verify_multipart(&mime, spore_data.content().unpack())?;
+    verify_content_ref(&mime, spore_data.content().unpack())?;
+    verify_segmented_content(&mime, spore_data.content().unpack())?;
+    if let Some(policy) = MeltPolicy::parse(&mime)? {
+        if policy.target == MeltTarget::ClusterOwner && spore_data.cluster_id().is_none() {
+            return Err(Error::InvalidMeltPolicy);
+        }
+    }
+    if RoyaltyTerms::parse(&mime)? == Some(RoyaltyTerms::Cluster)
+        && spore_data.cluster_id().is_none()
+    {
+        return Err(Error::InvalidRoyalty);
+    }
+    Ok(mime)
+}
+
+/// Only `content` of a Spore with `mutable=content` can be updated
+pub fn verify_spore_transfer(
+    input_data: &SporeData,
+    output_data: &SporeData,
+) -> Result<MIME, Error> {
+    let raw_content_type = input_data.content_type();
+    let mime = MIME::parse_existing(raw_content_type.unpack())?;
+
+    if input_data.as_slice()[..] != output_data.as_slice()[..] {
+        let expected_data = input_data
+            .clone()
+            .as_builder()
+            .content(output_data.content())
+            .build();
+        if !mime.mutable_content || expected_data.as_slice()[..] != output_data.as_slice()[..] {
+            return Err(Error::ModifySporePermanentField);
+        }
+        let content = output_data.content();
+        if content.is_empty() {
+            return Err(Error::EmptyContent);
+        }
+        verify_multipart(&mime, content.unpack())?;
+        verify_content_ref(&mime, content.unpack())?;
+        verify_segmented_content(&mime, content.unpack())?;
+    }
+    Ok(mime)
+}
+
+pub fn verify_spore_destruction(spore_data: &SporeData) -> Result<MIME, Error> {
+    let raw_content_type = spore_data.content_type();
+    let mime = MIME::parse_existing(raw_content_type.unpack())?;
+    if mime.immortal {
+        return Err(Error::DestroyImmortalNFT);
+    }
+    Ok(mime)
+}
+
+/// Lock receiving the refund of a melted Spore
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeltTarget {
+    /// `melt-to=<lock hash in hex>`
+    Lock([u8; 32]),
+    /// `melt-to=cluster`, the lock of the cluster cell in CellDeps
+    ClusterOwner,
+}
+
+/// Share of its capacity a Spore refunds when melted, from the `melt-to` and `melt-share`
+/// params, where `melt-share` is a percentage from 1 to 100 and defaults to 100
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MeltPolicy {
+    pub target: MeltTarget,
+    pub share: u8,
+}
+
+impl MeltPolicy {
+    pub fn parse(mime: &MIME) -> Result<Option<Self>, Error> {
+        let share = match mime.get_param("melt-share") {
+            Some(share) => {
+                if share.is_empty() || !share.bytes().all(|c| c.is_ascii_digit()) {
+                    return Err(Error::InvalidMeltPolicy);
+                }
+                match share.parse() {
+                    Ok(share @ 1..=100) => Some(share),
+                    _ => return Err(Error::InvalidMeltPolicy),
+                }
+            }
+            None => None,
+        };
+        let target = match mime.get_param("melt-to") {
+            Some("cluster") => MeltTarget::ClusterOwner,
+            Some(lock_hash) => match parse_hash_list(lock_hash).as_deref() {
+                Some([lock_hash]) => MeltTarget::Lock(*lock_hash),
+                _ => return Err(Error::InvalidMeltPolicy),
+            },
+            None if share.is_some() => return Err(Error::InvalidMeltPolicy),
+            None => return Ok(None),
+        };
+        Ok(Some(Self {
+            target,
+            share: share.unwrap_or(100),
+        }))
+    }
+
+    /// Shannons refunded out of the capacity of the melted Spore, rounded down
+    pub fn refund(&self, capacity: u64) -> u128 {
+        capacity as u128 * self.share as u128 / 100
+    }
+}
+
+/// The refund lock receives its refunds on top of what it puts in, not counting the melted Spores
+pub fn verify_melt_refund(
+    refund: u128,
+    input_amount: u128,
+    output_amount: u128,
+) -> Result<(), Error> {
+    if output_amount < input_amount.saturating_add(refund) {
+        return Err(Error::PaymentNotEnough);
+    }
+    Ok(())
+}
+
+pub const ROYALTY_LEN: usize = 42;
+
+/// Royalty on Spore transfers: `recipient lock hash | basis points (u16) | minimum (u64)` in
+/// little endian, where a transfer pays the larger of the minimum and the basis points of its price
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Royalty {
+    pub recipient: [u8; 32],
+    pub basis_points: u16,
+    pub minimum: u64,
+}
+
+impl Royalty {
+    pub fn parse(data: &[u8]) -> Result<Self, Error> {
+        if data.len() != ROYALTY_LEN {
+            return Err(Error::InvalidRoyalty);
+        }
+        let royalty = Self {
+            recipient: data[..32].try_into().unwrap_or_default(),
+            basis_points: u16::from_le_bytes(data[32..34].try_into().unwrap_or_default()),
+            minimum: u64::from_le_bytes(data[34..].try_into().unwrap_or_default()),
+        };
+        royalty.verify()?;
+        Ok(royalty)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [
+            &self.recipient[..],
+            &self.basis_points.to_le_bytes(),
+            &self.minimum.to_le_bytes(),
+        ]
+        .concat()
+    }
+
+    fn verify(&self) -> Result<(), Error> {
+        if self.basis_points > 10_000 || (self.basis_points == 0 && self.minimum == 0) {
+            return Err(Error::InvalidRoyalty);
+        }
+        Ok(())
+    }
+
+    /// Shannons owed on a transfer traded at `price`, zero for a transfer without a price witness
+    pub fn amount(&self, price: u64) -> u128 {
+        let share = price as u128 * self.basis_points as u128 / 10_000;
+        share.max(self.minimum as u128)
+    }
+}
+
+/// Royalty terms of a Spore, from the `royalty-to`, `royalty-bps` and `royalty-min` params
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoyaltyTerms {
+    /// `royalty-to=<lock hash in hex>`, with `royalty-bps` and `royalty-min` defaulting to 0
+    Spore(Royalty),
+    /// `royalty-to=cluster`, the royalty of the cluster cell in CellDeps
+    Cluster,
+}
+
+impl RoyaltyTerms {
+    pub fn parse(mime: &MIME) -> Result<Option<Self>, Error> {
+        let number = |name: &str| -> Result<Option<u64>, Error> {
+            match mime.get_param(name) {
+                Some(value) if !value.is_empty() && value.bytes().all(|c| c.is_ascii_digit()) => {
+                    value.parse().map(Some).map_err(|_| Error::InvalidRoyalty)
+                }
+                Some(_) => Err(Error::InvalidRoyalty),
+                None => Ok(None),
+            }
+        };
+        let basis_points = number("royalty-bps")?;
+        let minimum = number("royalty-min")?;
+        let recipient = match mime.get_param("royalty-to") {
+            Some("cluster") if basis_points.is_none() && minimum.is_none() => {
+                return Ok(Some(Self::Cluster))
+            }
+            Some(lock_hash) => match parse_hash_list(lock_hash).as_deref() {
+                Some([lock_hash]) => *lock_hash,
+                _ => return Err(Error::InvalidRoyalty),
+            },
+            None if basis_points.is_none() && minimum.is_none() => return Ok(None),
+            None => return Err(Error::InvalidRoyalty),
+        };
+        let royalty = Royalty {
+            recipient,
+            basis_points: basis_points
+                .unwrap_or_default()
+                .try_into()
+                .map_err(|_| Error::InvalidRoyalty)?,
+            minimum: minimum.unwrap_or_default(),
+        };
+        royalty.verify()?;
+        Ok(Some(Self::Spore(royalty)))
+    }
+}
+
+/// Price of a marketplace trade, in shannons paid to the previous owner, kept in little endian in
+/// the `input_type` of the witness of the Spore transferred
+pub fn parse_price_witness(input_type: &[u8]) -> Result<u64, Error> {
+    input_type
+        .try_into()
+        .map(u64::from_le_bytes)
+        .map_err(|_| Error::InvalidPriceWitness)
+}
+
+/// A lock owed royalties or the price of a trade receives them on top of what it puts in, not
+/// counting the Spores transferred
+pub fn verify_transfer_dues(
+    royalty: u128,
+    price: u128,
+    input_amount: u128,
+    output_amount: u128,
+) -> Result<(), Error> {
+    if output_amount < input_amount.saturating_add(royalty).saturating_add(price) {
+        if royalty > 0 {
+            return Err(Error::RoyaltyNotPaid);
+        }
+        return Err(Error::PriceNotPaid);
+    }
+    Ok(())
+}
+
+/// A Spore minted into a cluster with a royalty has to follow it with `royalty-to=cluster`, which
+/// only a cluster with a royalty can be followed with, as a cluster without one can be destroyed
+pub fn verify_cluster_royalty(mime: &MIME, cluster_data: &ClusterDataV2) -> Result<(), Error> {
+    let follows_cluster = RoyaltyTerms::parse(mime)? == Some(RoyaltyTerms::Cluster);
+    match cluster_data.royalty().is_some() {
+        true if !follows_cluster => Err(Error::ClusterRequiresRoyalty),
+        false if follows_cluster => Err(Error::InvalidRoyalty),
+        _ => Ok(()),
+    }
+}
+
+/// `melt-to=cluster` needs a cluster with a royalty, as a cluster without one can be destroyed and
+/// leave the Spore without a refund lock to melt to
+pub fn verify_cluster_melt(mime: &MIME, cluster_data: &ClusterDataV2) -> Result<(), Error> {
+    let melts_to_cluster =
+        MeltPolicy::parse(mime)?.is_some_and(|policy| policy.target == MeltTarget::ClusterOwner);
+    if melts_to_cluster && cluster_data.royalty().is_none() {
+        return Err(Error::InvalidMeltPolicy);
+    }
+    Ok(())
+}
+
+/// A Spore minted into a cluster applies the cluster's required mutants, `mutant_id` included.
+/// If the cluster lists allowed mutants, the Spore applies no mutant out of the two lists
+pub fn verify_cluster_mutant(mime: &MIME, cluster_data: &ClusterDataV2) -> Result<(), Error> {
+    let required: Vec<_> = cluster_data
+        .mutant_id()
+        .to_opt()
+        .into_iter()
+        .chain(
+            cluster_data
+                .required_mutants()
+                .to_opt()
+                .into_iter()
+                .flatten(),
+        )
+        .map(|mutant_id| mutant_id.raw_data())
+        .collect();
+    let applied = |mutant_id: &[u8]| mime.mutants.iter().any(|mutant| mutant[..] == *mutant_id);
+    if !required.iter().all(|mutant_id| applied(mutant_id)) {
+        return Err(Error::ClusterRequiresMutantApplied);
+    }
+    if let Some(allowed) = cluster_data.allowed_mutants().to_opt() {
+        let listed = |mutant: &[u8; 32]| {
+            required.iter().any(|mutant_id| mutant[..] == mutant_id[..])
+                || allowed
+                    .clone()
+                    .into_iter()
+                    .any(|mutant_id| mutant[..] == mutant_id.raw_data()[..])
+        };
+        if !mime.mutants.iter().all(listed) {
+            return Err(Error::MutantNotAllowed);
+        }
+    }
+    Ok(())
+}
+
+/// A Spore minted into a cluster with a content-type allowlist has to match one of its entries
+pub fn verify_cluster_content_type(mime: &MIME, cluster_data: &ClusterDataV2) -> Result<(), Error> {
+    let allowed_content_types = match cluster_data.allowed_content_types().to_opt() {
+        Some(allowed_content_types) => allowed_content_types,
+        None => return Ok(()),
+    };
+    let allowed = allowed_content_types
+        .into_iter()
+        .filter_map(|pattern| parse_content_type_pattern(pattern.raw_data().as_ref()))
+        .any(|pattern| {
+            pattern.main_type == mime.main_type
+                && (pattern.sub_type == "*" || pattern.sub_type == mime.sub_type)
+        });
+    if !allowed {
+        return Err(Error::ContentTypeNotAllowed);
+    }
+    Ok(())
+}
+
+/// `type/subtype` or `type/*`, without params
+pub fn parse_content_type_pattern(pattern: &[u8]) -> Option<MIME> {
+    let pattern = MIME::parse(pattern).ok()?;
+    if pattern.main_type == "*" || !pattern.params().is_empty() {
+        return None;
+    }
+    Some(pattern)
+}
+
+pub fn verify_cluster_creation(cluster_data: &ClusterDataV2) -> Result<(), Error> {
+    if cluster_data.name().is_empty() {
+        return Err(Error::EmptyName);
+    }
+    if let Some(allowed_content_types) = cluster_data.allowed_content_types().to_opt() {
+        if allowed_content_types.is_empty()
+            || allowed_content_types
+                .into_iter()
+                .any(|pattern| parse_content_type_pattern(pattern.raw_data().as_ref()).is_none())
+        {
+            return Err(Error::InvalidClusterData);
+        }
+    }
+    if let Some(royalty) = cluster_data.royalty().to_opt() {
+        Royalty::parse(&royalty.raw_data()).map_err(|_| Error::InvalidClusterData)?;
+    }
+    // required mutants are listed at least once, allowed ones may be none but the required
+    if let Some(required_mutants) = cluster_data.required_mutants().to_opt() {
+        if required_mutants.is_empty() {
+            return Err(Error::InvalidClusterData);
+        }
+    }
+    for mutants in [
+        cluster_data.required_mutants(),
+        cluster_data.allowed_mutants(),
+    ] {
+        if mutants
+            .to_opt()
+            .into_iter()
+            .flatten()
+            .any(|mutant_id| mutant_id.raw_data().len() != MUTANT_ID_LEN)
+        {
+            return Err(Error::InvalidClusterData);
+        }
+    }
+    if u64::from(cluster_data.minted()) != 0 {
+        return Err(Error::InvalidClusterData);
+    }
+    Ok(())
+}
+
+/// Name, mutants, max_supply, allowed_content_types and royalty are permanent, description can be
+/// updated and the cluster can be closed
+pub fn verify_cluster_transfer(
+    input_data: &ClusterDataV2,
+    output_data: &ClusterDataV2,
+) -> Result<(), Error> {
+    let expected_data = input_data
+        .clone()
+        .as_builder()
+        .description(output_data.description())
+        .closed(output_data.closed())
+        .minted(output_data.minted())
+        .build();
+    if expected_data.as_slice()[..] != output_data.as_slice()[..] {
+        return Err(Error::ModifyClusterPermanentField);
+    }
+
+    // the minted counter never goes back, the spore contract checks it matches new Spores
+    let input_minted = u64::from(input_data.minted());
+    let output_minted = u64::from(output_data.minted());
+    let max_supply = Option::<u64>::from(output_data.max_supply()).unwrap_or(u64::MAX);
+    if output_minted < input_minted || output_minted > max_supply {
+        return Err(Error::ModifyClusterPermanentField);
+    }
+
+    if bool::from(input_data.closed()) && !bool::from(output_data.closed()) {
+        return Err(Error::ReopenClosedCluster);
+    }
+    Ok(())
+}
+
+/// Only a closed cluster can be destroyed, Spores already minted are not affected. A cluster with
+/// a royalty is needed to transfer its Spores, so it can't be destroyed
+pub fn verify_cluster_destruction(cluster_data: &ClusterDataV2) -> Result<(), Error> {
+    if !bool::from(cluster_data.closed()) {
+        return Err(Error::DestroyOpenCluster);
+    }
+    if cluster_data.royalty().is_some() {
+        return Err(Error::ClusterRequiresRoyalty);
+    }
+    Ok(())
+}
+
+/// A limited cluster updates its minted counter by the number of Spores minted into it
+pub fn verify_cluster_supply(
+    input_data: &ClusterDataV2,
+    output_data: &ClusterDataV2,
+    minted: u64,
+) -> Result<(), Error> {
+    let max_supply = match Option::<u64>::from(input_data.max_supply()) {
+        Some(max_supply) => max_supply,
+        None => return Ok(()),
+    };
+    let input_minted = u64::from(input_data.minted());
+    let output_minted = u64::from(output_data.minted());
+    if input_minted.checked_add(minted) != Some(output_minted) {
+        return Err(Error::ClusterMintedCountMismatch);
+    }
+    if output_minted > max_supply {
+        return Err(Error::ClusterSupplyExceeded);
+    }
+    Ok(())
+}
+
+pub fn verify_proxy_transfer(input_args: &[u8], output_args: &[u8]) -> Result<(), Error> {
+    // NOTE: We allow minimal payment modification during transfer
+    if input_args.get(..CLUSTER_PROXY_ID_LEN) != output_args.get(..CLUSTER_PROXY_ID_LEN) {
+        return Err(Error::ImmutableProxyFieldModification);
+    }
+    Ok(())
+}
+
+/// Data of a cluster agent: the proxy type hash, optionally followed by an expiry in absolute
+/// since format (0 for none) and a remaining mint quota
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgentData {
+    pub proxy_type_hash: [u8; 32],
+    pub expiry: u64,
+    pub quota: Option<u64>,
+}
+
+impl AgentData {
+    pub fn parse(data: &[u8]) -> Result<Self, Error> {
+        let read_u64 = |offset: usize| -> Option<u64> {
+            data.get(offset..offset + 8)
+                .map(|value| u64::from_le_bytes(value.try_into().unwrap_or_default()))
+        };
+        if ![
+            LEGACY_AGENT_DATA_LEN,
+            AGENT_QUOTA_OFFSET,
+            AGENT_QUOTA_OFFSET + 8,
+        ]
+        .contains(&data.len())
+        {
+            return Err(Error::InvalidAgentData);
+        }
+        let agent_data = Self {
+            proxy_type_hash: data[..32].try_into().unwrap_or_default(),
+            expiry: read_u64(32).unwrap_or_default(),
+            quota: read_u64(AGENT_QUOTA_OFFSET),
+        };
+        let expiry = Since::new(agent_data.expiry);
+        if expiry.as_u64() != 0 && !(expiry.is_absolute() && expiry.flags_is_valid()) {
+            return Err(Error::InvalidAgentData);
+        }
+        Ok(agent_data)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = self.proxy_type_hash.to_vec();
+        if self.expiry != 0 || self.quota.is_some() {
+            data.extend_from_slice(&self.expiry.to_le_bytes());
+        }
+        if let Some(quota) = self.quota {
+            data.extend_from_slice(&quota.to_le_bytes());
+        }
+        data
+    }
+
+    pub fn expiry(&self) -> Option<LockValue> {
+        match self.expiry {
+            0 => None,
+            expiry => Since::new(expiry).extract_lock_value(),
+        }
+    }
+}
+
+/// Only the mint quota of an agent can be decreased
+pub fn verify_agent_transfer(input_data: &[u8], output_data: &[u8]) -> Result<(), Error> {
+    let load_quota = |data: &[u8]| {
+        data.get(AGENT_QUOTA_OFFSET..AGENT_QUOTA_OFFSET + 8)
+            .map(|quota| u64::from_le_bytes(quota.try_into().unwrap_or_default()))
+    };
+    let frozen_len = input_data.len().min(AGENT_QUOTA_OFFSET);
+    if input_data.len() != output_data.len()
+        || input_data[..frozen_len] != output_data[..frozen_len]
+        || load_quota(output_data) > load_quota(input_data)
+    {
+        return Err(Error::ImmutableAgentFieldModification);
+    }
+    Ok(())
+}
+
+/// An agent with a mint quota decreases it by the number of Spores minted through it
+pub fn verify_agent_quota(
+    input_data: &AgentData,
+    output_data: &AgentData,
+    minted: u64,
+) -> Result<(), Error> {
+    let input_quota = match input_data.quota {
+        Some(quota) => quota,
+        None => return Ok(()),
+    };
+    let remaining = input_quota
+        .checked_sub(minted)
+        .ok_or(Error::AgentQuotaExceeded)?;
+    if output_data.quota != Some(remaining) {
+        return Err(Error::AgentQuotaMismatch);
+    }
+    Ok(())
+}
+
+pub fn expiry_reached(expiry: &LockValue, current: &LockValue) -> bool {
+    match (expiry, current) {
+        (LockValue::BlockNumber(expiry), LockValue::BlockNumber(current)) => current >= expiry,
+        (LockValue::Timestamp(expiry), LockValue::Timestamp(current)) => current >= expiry,
+        (
+            LockValue::EpochNumberWithFraction(expiry),
+            LockValue::EpochNumberWithFraction(current),
+        ) => {
+            (current.number(), current.index() * expiry.length())
+                >= (expiry.number(), expiry.index() * current.length())
+        }
+        _ => false,
+    }
+}
+
+/// Whether the absolute since of a consumed cell proves the expiry is reached
+pub fn since_reached(expiry: &LockValue, since: u64) -> bool {
+    let since = Since::new(since);
+    since.is_absolute()
+        && since.flags_is_valid()
+        && since
+            .extract_lock_value()
+            .map(|current| expiry_reached(expiry, &current))
+            .unwrap_or_default()
+}
+
+/// Whether a block with the given number, full epoch value and timestamp reaches the expiry
+pub fn header_reached(expiry: &LockValue, number: u64, epoch: u64, timestamp: u64) -> bool {
+    let current = match expiry {
+        LockValue::BlockNumber(_) => LockValue::BlockNumber(number),
+        LockValue::EpochNumberWithFraction(_) => {
+            LockValue::EpochNumberWithFraction(EpochNumberWithFraction::from_full_value(epoch))
+        }
+        // header timestamps are in milliseconds, as `Since` already converts the since seconds to
+        LockValue::Timestamp(_) => LockValue::Timestamp(timestamp),
+    };
+    expiry_reached(expiry, &current)
+}
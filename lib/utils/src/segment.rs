@@ -0,0 +1,220 @@
+//! Spore content split across segment cells, under `application/x-spore-segments;hash=blake2b`
+//!
+//! A segment cell holds `index (u32 LE) | total (u32 LE) | content digest (32) | chunk`, and the
+//! segments of the same content share a type script with a Type ID args. A segmented Spore
+//! holds `segments id (32) | content digest (32) | blake2b of each chunk (32 each)`.
+
+use alloc::vec::Vec;
+use ckb_std::ckb_types::util::hash::{blake2b_256, new_blake2b};
+use spore_errors::error::Error;
+
+use crate::MIME;
+
+pub const SEGMENT_HEADER_LEN: usize = 40;
+pub const SEGMENT_HASH_LEN: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment<'a> {
+    pub index: u32,
+    pub total: u32,
+    pub content_digest: &'a [u8],
+    pub chunk: &'a [u8],
+}
+
+impl<'a> Segment<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        if data.len() <= SEGMENT_HEADER_LEN {
+            return Err(Error::InvalidSegmentData);
+        }
+        let index = u32::from_le_bytes(data[..4].try_into().unwrap_or_default());
+        let total = u32::from_le_bytes(data[4..8].try_into().unwrap_or_default());
+        if index >= total {
+            return Err(Error::InvalidSegmentData);
+        }
+        Ok(Segment {
+            index,
+            total,
+            content_digest: &data[8..SEGMENT_HEADER_LEN],
+            chunk: &data[SEGMENT_HEADER_LEN..],
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [
+            &self.index.to_le_bytes()[..],
+            &self.total.to_le_bytes(),
+            self.content_digest,
+            self.chunk,
+        ]
+        .concat()
+    }
+
+    /// Whether both segments are parts of the same content
+    pub fn is_sibling(&self, other: &Segment) -> bool {
+        self.total == other.total && self.content_digest == other.content_digest
+    }
+}
+
+/// Orders the segments of a content by index, each index has to be covered exactly once
+/// and the chunks have to match the content digest
+pub fn assemble_segments<'a, 'b>(
+    candidates: &'b [Segment<'a>],
+    total: u32,
+    content_digest: &[u8],
+) -> Result<Vec<&'b Segment<'a>>, Error> {
+    let mut blake2b = new_blake2b();
+    let mut segments = Vec::with_capacity(total as usize);
+    for index in 0..total {
+        let mut found = candidates.iter().filter(|segment| {
+            segment.index == index
+                && segment.total == total
+                && segment.content_digest == content_digest
+        });
+        let segment = found.next().ok_or(Error::SegmentNotFound)?;
+        if found.next().is_some() {
+            return Err(Error::DuplicateSegment);
+        }
+        blake2b.update(segment.chunk);
+        segments.push(segment);
+    }
+    let mut digest = [0u8; 32];
+    blake2b.finalize(&mut digest);
+    if digest[..] != content_digest[..] {
+        return Err(Error::SegmentDigestMismatch);
+    }
+    Ok(segments)
+}
+
+pub fn is_segmented(mime: &MIME) -> bool {
+    mime.main_type == "application" && mime.sub_type == "x-spore-segments"
+}
+
+/// The content of a segmented Spore
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentedContent<'a> {
+    pub segments_id: &'a [u8],
+    pub content_digest: &'a [u8],
+    segment_hashes: &'a [u8],
+}
+
+impl<'a> SegmentedContent<'a> {
+    pub fn parse(mime: &MIME, content: &'a [u8]) -> Result<Self, Error> {
+        if !is_segmented(mime) {
+            return Err(Error::InvalidContentType);
+        }
+        let hash = mime.get_param("hash").ok_or(Error::InvalidContentType)?;
+        if !hash.eq_ignore_ascii_case("blake2b") {
+            return Err(Error::UnsupportedDigestAlgorithm);
+        }
+        let segment_hashes = content.get(64..).unwrap_or_default();
+        if segment_hashes.is_empty()
+            || segment_hashes.len() % SEGMENT_HASH_LEN != 0
+            || segment_hashes.len() / SEGMENT_HASH_LEN > u32::MAX as usize
+        {
+            return Err(Error::InvalidSegmentedContent);
+        }
+        Ok(SegmentedContent {
+            segments_id: &content[..32],
+            content_digest: &content[32..64],
+            segment_hashes,
+        })
+    }
+
+    pub fn total(&self) -> u32 {
+        (self.segment_hashes.len() / SEGMENT_HASH_LEN) as u32
+    }
+
+    pub fn segment_hashes(&self) -> impl Iterator<Item = &'a [u8]> {
+        self.segment_hashes.chunks(SEGMENT_HASH_LEN)
+    }
+
+    /// Checks the committed segments among candidates, returning them ordered by index
+    pub fn verify<'b>(&self, candidates: &'b [Segment<'a>]) -> Result<Vec<&'b Segment<'a>>, Error> {
+        let segments = assemble_segments(candidates, self.total(), self.content_digest)?;
+        if segments
+            .iter()
+            .zip(self.segment_hashes())
+            .any(|(segment, hash)| blake2b_256(segment.chunk)[..] != hash[..])
+        {
+            return Err(Error::SegmentDigestMismatch);
+        }
+        Ok(segments)
+    }
+}
+
+/// Checks the layout of a segmented Spore content, any other content passes
+pub fn verify_segmented_content(mime: &MIME, content: &[u8]) -> Result<(), Error> {
+    if is_segmented(mime) {
+        SegmentedContent::parse(mime, content)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_segments() {
+    let chunks: [&[u8]; 3] = [b"Hello ", b"Spore", b"!"];
+    let content_digest = blake2b_256(chunks.concat());
+    let data: Vec<Vec<u8>> = chunks
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            Segment {
+                index: index as u32,
+                total: 3,
+                content_digest: &content_digest,
+                chunk,
+            }
+            .to_bytes()
+        })
+        .rev()
+        .collect();
+    let segments: Vec<Segment> = data
+        .iter()
+        .map(|data| Segment::parse(data).unwrap())
+        .collect();
+    let ordered = assemble_segments(&segments, 3, &content_digest).unwrap();
+    assert_eq!(ordered[0].chunk, b"Hello ");
+
+    let mime = MIME::str_parse("application/x-spore-segments;hash=blake2b").unwrap();
+    let mut content = [[1u8; 32], content_digest].concat();
+    chunks
+        .iter()
+        .for_each(|chunk| content.extend_from_slice(&blake2b_256(chunk)));
+    let segmented = SegmentedContent::parse(&mime, &content).unwrap();
+    assert_eq!(segmented.total(), 3);
+    assert!(segmented.verify(&segments).is_ok());
+
+    assert_eq!(
+        assemble_segments(&segments[1..], 3, &content_digest).err(),
+        Some(Error::SegmentNotFound)
+    );
+    let duplicated = [segments.clone(), segments[..1].to_vec()].concat();
+    assert_eq!(
+        assemble_segments(&duplicated, 3, &content_digest).err(),
+        Some(Error::DuplicateSegment)
+    );
+    let swapped = Segment {
+        chunk: b"Spore ",
+        ..segments[2].clone()
+    };
+    let tampered = [swapped, segments[1].clone(), segments[0].clone()];
+    assert_eq!(
+        assemble_segments(&tampered, 3, &content_digest).err(),
+        Some(Error::SegmentDigestMismatch)
+    );
+
+    content[70] ^= 1;
+    let segmented = SegmentedContent::parse(&mime, &content).unwrap();
+    assert_eq!(
+        segmented.verify(&segments).err(),
+        Some(Error::SegmentDigestMismatch)
+    );
+    assert_eq!(
+        verify_segmented_content(&mime, &content[..80]).err(),
+        Some(Error::InvalidSegmentedContent)
+    );
+    assert_eq!(
+        Segment::parse(&[[0u8; 4], [0u8; 4]].concat()).err(),
+        Some(Error::InvalidSegmentData)
+    );
+}
@@ -0,0 +1,1383 @@
+//! Transaction-level rules of the Spore contracts, over the cells of the transaction
+//!
+//! The contracts read cells through syscalls with `Syscalls`, and the off-chain validator of
+//! `spore-sdk` over a resolved transaction, so both run the same checks. A `Failure` tells which
+//! cell and field a rule failed on, of which the contracts only return the error code.
+
+use alloc::vec::Vec;
+use ckb_std::ckb_constants::Source::{CellDep, HeaderDep, Input, Output};
+use ckb_std::ckb_types::prelude::*;
+use ckb_std::high_level::{
+    load_cell_capacity, load_cell_data, load_cell_data_hash, load_cell_lock_hash, load_cell_type,
+    load_cell_type_hash, load_header, load_input, load_input_since, load_script, load_witness_args,
+};
+use spore_errors::error::Error;
+use spore_types::generated::spore_types::{BytesVecOpt, ClusterDataV2, SporeData};
+
+pub use ckb_std::ckb_constants::Source;
+pub use ckb_std::ckb_types::packed::Script;
+
+use crate::extension::{MutantArgs, MUTANT_ID_LEN, OP_MELT, OP_MINT, OP_TRANSFER};
+use crate::payment::{verify_payment, Payment};
+use crate::rules::{
+    header_reached, parse_price_witness, since_reached, verify_agent_quota, verify_agent_transfer,
+    verify_cluster_content_type, verify_cluster_creation, verify_cluster_destruction,
+    verify_cluster_melt, verify_cluster_mutant, verify_cluster_royalty, verify_cluster_supply,
+    verify_cluster_transfer, verify_melt_refund, verify_proxy_transfer, verify_spore_creation,
+    verify_spore_destruction, verify_spore_transfer, verify_transfer_dues, AgentData, MeltPolicy,
+    MeltTarget, Royalty, RoyaltyTerms, CLUSTER_PROXY_ID_LEN, LEGACY_AGENT_DATA_LEN,
+};
+use crate::segment::{assemble_segments, is_segmented, Segment, SegmentedContent};
+use crate::{calc_type_id, parse_cluster_data, MIME};
+
+/// Code hash of the v1 cluster contract deployed on testnet, still accepted as a cluster
+pub const CLUSTER_V1_CODE_HASH: [u8; 32] = [
+    89, 141, 121, 61, 239, 239, 54, 226, 238, 186, 84, 169, 180, 81, 48, 228, 202, 146, 130, 46,
+    29, 25, 54, 113, 244, 144, 149, 12, 59, 133, 96, 128,
+];
+
+/// `hash_type` of a script referring to its code by the type hash of the code cell
+const HASH_TYPE_TYPE: u8 = 1;
+
+/// Contracts a Spore contract refers to, recognized by the code hash of a type script. The Spore
+/// contract itself is the running script
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Contract {
+    Cluster,
+    ClusterProxy,
+    ClusterAgent,
+    SporeSegment,
+    SporeExtensionLua,
+}
+
+/// Number, full epoch value and timestamp of a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeaderInfo {
+    pub number: u64,
+    pub epoch: u64,
+    pub timestamp: u64,
+}
+
+/// Cells of the transaction being verified, indexed as by syscalls, failing with
+/// `IndexOutOfBound` past the last cell of a source
+pub trait Cells {
+    /// The running type script
+    fn script(&self) -> Result<Script, Error>;
+    fn is_contract(&self, script: &Script, contract: Contract) -> bool;
+    fn type_script(&self, index: usize, source: Source) -> Result<Option<Script>, Error>;
+    fn type_hash(&self, index: usize, source: Source) -> Result<Option<[u8; 32]>, Error>;
+    fn lock_hash(&self, index: usize, source: Source) -> Result<[u8; 32], Error>;
+    fn capacity(&self, index: usize, source: Source) -> Result<u64, Error>;
+    fn data(&self, index: usize, source: Source) -> Result<Vec<u8>, Error>;
+    fn data_hash(&self, index: usize, source: Source) -> Result<[u8; 32], Error>;
+    /// `since` of an input
+    fn since(&self, index: usize) -> Result<u64, Error>;
+    /// A header dep, or the block of an input or cell dep, which has to be in the header deps
+    fn header(&self, index: usize, source: Source) -> Result<HeaderInfo, Error>;
+    /// `input_type` of the witness at an input index, `None` if missing or not a `WitnessArgs`
+    fn witness_input_type(&self, index: usize) -> Result<Option<Vec<u8>>, Error>;
+    /// The serialized first input, from which type IDs are derived
+    fn first_input(&self) -> Result<Vec<u8>, Error>;
+}
+
+/// Cells read through syscalls, with the code hashes of the contracts known to the running one
+pub struct Syscalls {
+    code_hashes: &'static [(Contract, &'static [[u8; 32]])],
+}
+
+impl Syscalls {
+    pub fn new(code_hashes: &'static [(Contract, &'static [[u8; 32]])]) -> Self {
+        Self { code_hashes }
+    }
+}
+
+impl Cells for Syscalls {
+    fn script(&self) -> Result<Script, Error> {
+        Ok(load_script()?)
+    }
+
+    fn is_contract(&self, script: &Script, contract: Contract) -> bool {
+        let code_hash: [u8; 32] = script.code_hash().unpack();
+        self.code_hashes
+            .iter()
+            .any(|(known, code_hashes)| *known == contract && code_hashes.contains(&code_hash))
+    }
+
+    fn type_script(&self, index: usize, source: Source) -> Result<Option<Script>, Error> {
+        Ok(load_cell_type(index, source)?)
+    }
+
+    fn type_hash(&self, index: usize, source: Source) -> Result<Option<[u8; 32]>, Error> {
+        Ok(load_cell_type_hash(index, source)?)
+    }
+
+    fn lock_hash(&self, index: usize, source: Source) -> Result<[u8; 32], Error> {
+        Ok(load_cell_lock_hash(index, source)?)
+    }
+
+    fn capacity(&self, index: usize, source: Source) -> Result<u64, Error> {
+        Ok(load_cell_capacity(index, source)?)
+    }
+
+    fn data(&self, index: usize, source: Source) -> Result<Vec<u8>, Error> {
+        Ok(load_cell_data(index, source)?)
+    }
+
+    fn data_hash(&self, index: usize, source: Source) -> Result<[u8; 32], Error> {
+        Ok(load_cell_data_hash(index, source)?)
+    }
+
+    fn since(&self, index: usize) -> Result<u64, Error> {
+        Ok(load_input_since(index, Input)?)
+    }
+
+    fn header(&self, index: usize, source: Source) -> Result<HeaderInfo, Error> {
+        let raw = load_header(index, source)?.raw();
+        Ok(HeaderInfo {
+            number: raw.number().unpack(),
+            epoch: raw.epoch().unpack(),
+            timestamp: raw.timestamp().unpack(),
+        })
+    }
+
+    fn witness_input_type(&self, index: usize) -> Result<Option<Vec<u8>>, Error> {
+        match load_witness_args(index, Input) {
+            Ok(witness_args) => Ok(witness_args
+                .input_type()
+                .to_opt()
+                .map(|input_type| input_type.raw_data().to_vec())),
+            Err(_) => Ok(None),
+        }
+    }
+
+    fn first_input(&self) -> Result<Vec<u8>, Error> {
+        Ok(load_input(0, Input)?.as_slice().to_vec())
+    }
+}
+
+/// A failed rule, with the cell and the field it is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Failure {
+    pub rule: Error,
+    pub source: Source,
+    pub index: usize,
+    pub field: &'static str,
+}
+
+impl Failure {
+    pub fn new(rule: Error, source: Source, index: usize, field: &'static str) -> Self {
+        Self {
+            rule,
+            source,
+            index,
+            field,
+        }
+    }
+}
+
+impl From<Failure> for Error {
+    fn from(failure: Failure) -> Self {
+        failure.rule
+    }
+}
+
+/// Locates the error of a rule on a cell
+trait Locate<T> {
+    /// at the field the rule is about, see `field_of`
+    fn at(self, source: Source, index: usize) -> Result<T, Failure>;
+    fn at_field(self, source: Source, index: usize, field: &'static str) -> Result<T, Failure>;
+}
+
+impl<T> Locate<T> for Result<T, Error> {
+    fn at(self, source: Source, index: usize) -> Result<T, Failure> {
+        self.map_err(|rule| Failure::new(rule, source, index, field_of(rule)))
+    }
+
+    fn at_field(self, source: Source, index: usize, field: &'static str) -> Result<T, Failure> {
+        self.map_err(|rule| Failure::new(rule, source, index, field))
+    }
+}
+
+/// The field of a cell a rule over cell data is about
+pub fn field_of(rule: Error) -> &'static str {
+    match rule {
+        Error::EmptyContent
+        | Error::InvalidMultipartContent
+        | Error::MultipartPartsExceeded
+        | Error::InvalidContentRef
+        | Error::InvalidSegmentedContent
+        | Error::SegmentNotFound
+        | Error::DuplicateSegment
+        | Error::SegmentDigestMismatch
+        | Error::Encoding => "content",
+        Error::InvalidContentType
+        | Error::Illformed
+        | Error::InvaliMainType
+        | Error::InvalidSubType
+        | Error::InvalidParams
+        | Error::InvalidParamValue
+        | Error::MutantIDNotValid
+        | Error::DestroyImmortalNFT
+        | Error::ClusterRequiresMutantApplied
+        | Error::MutantNotAllowed
+        | Error::ContentTypeNotAllowed
+        | Error::UnsupportedDigestAlgorithm
+        | Error::MutantCodeMismatch
+        | Error::MutantCodeNotValid
+        | Error::InvalidMeltPolicy
+        | Error::InvalidRoyalty
+        | Error::ClusterRequiresRoyalty
+        | Error::PinnedMutantCodeNotInDep
+        | Error::ExtensionCellNotInDep => "content_type",
+        Error::EmptyName => "name",
+        Error::InvalidClusterData
+        | Error::ClusterMintedCountMismatch
+        | Error::ClusterSupplyExceeded => "minted",
+        Error::ClusterClosed | Error::ReopenClosedCluster | Error::DestroyOpenCluster => "closed",
+        Error::AgentQuotaExceeded | Error::AgentQuotaMismatch => "quota",
+        Error::ImmutableProxyFieldModification
+        | Error::PaymentNotEnough
+        | Error::PaymentMethodNotSupport
+        | Error::InvalidExtensionArg
+        | Error::ExtensionPaymentNotEnough => "type.args",
+        _ => "data",
+    }
+}
+
+fn spore_transfer_field(input: &SporeData, output: &SporeData) -> &'static str {
+    if input.content_type().as_slice() != output.content_type().as_slice() {
+        "content_type"
+    } else if input.cluster_id().as_slice() != output.cluster_id().as_slice() {
+        "cluster_id"
+    } else {
+        "content"
+    }
+}
+
+fn cluster_transfer_field(input: &ClusterDataV2, output: &ClusterDataV2) -> &'static str {
+    if input.name().as_slice() != output.name().as_slice() {
+        "name"
+    } else if input.mutant_id().as_slice() != output.mutant_id().as_slice() {
+        "mutant_id"
+    } else if input.max_supply().as_slice() != output.max_supply().as_slice() {
+        "max_supply"
+    } else if input.allowed_content_types().as_slice() != output.allowed_content_types().as_slice()
+    {
+        "allowed_content_types"
+    } else if input.royalty().as_slice() != output.royalty().as_slice() {
+        "royalty"
+    } else if input.required_mutants().as_slice() != output.required_mutants().as_slice() {
+        "required_mutants"
+    } else if input.allowed_mutants().as_slice() != output.allowed_mutants().as_slice() {
+        "allowed_mutants"
+    } else {
+        "minted"
+    }
+}
+
+/// The field failing `verify_cluster_creation` with `InvalidClusterData`, other than minted
+fn invalid_cluster_field(cluster_data: &ClusterDataV2) -> &'static str {
+    let invalid_mutants = |mutants: BytesVecOpt, required: bool| {
+        mutants.to_opt().is_some_and(|mutants| {
+            (required && mutants.is_empty())
+                || mutants
+                    .into_iter()
+                    .any(|mutant_id| mutant_id.raw_data().len() != MUTANT_ID_LEN)
+        })
+    };
+    if invalid_mutants(cluster_data.required_mutants(), true) {
+        "required_mutants"
+    } else if invalid_mutants(cluster_data.allowed_mutants(), false) {
+        "allowed_mutants"
+    } else if cluster_data
+        .royalty()
+        .to_opt()
+        .is_some_and(|royalty| Royalty::parse(&royalty.raw_data()).is_err())
+    {
+        "royalty"
+    } else {
+        "allowed_content_types"
+    }
+}
+
+/// Items of the cells of a source in order, up to the first index out of bound
+fn query<T>(load: impl Fn(usize) -> Result<T, Error>) -> impl Iterator<Item = T> {
+    (0..).map_while(move |index| load(index).ok())
+}
+
+pub fn find_by_type_args(
+    cells: &impl Cells,
+    args: &[u8],
+    source: Source,
+    contract: Contract,
+) -> Option<usize> {
+    query(|index| cells.type_script(index, source)).position(|script| match script {
+        Some(script) => {
+            script.args().raw_data()[..] == args[..] && cells.is_contract(&script, contract)
+        }
+        None => false,
+    })
+}
+
+pub fn find_by_type_hash(cells: &impl Cells, type_hash: &[u8], source: Source) -> Option<usize> {
+    query(|index| cells.type_hash(index, source))
+        .position(|hash| hash.is_some_and(|hash| hash[..] == type_hash[..]))
+}
+
+pub fn find_by_lock_hash(
+    cells: &impl Cells,
+    lock_hash: &[u8; 32],
+    source: Source,
+) -> Option<usize> {
+    query(|index| cells.lock_hash(index, source)).position(|hash| &hash == lock_hash)
+}
+
+pub fn capacity_sum(cells: &impl Cells, lock_hash: &[u8; 32], source: Source) -> u128 {
+    query(|index| cells.lock_hash(index, source))
+        .enumerate()
+        .filter(|(_, hash)| hash == lock_hash)
+        .map(|(index, _)| cells.capacity(index, source).unwrap_or_default() as u128)
+        .sum()
+}
+
+pub fn udt_sum(
+    cells: &impl Cells,
+    lock_hash: &[u8; 32],
+    udt_type_hash: &[u8; 32],
+    source: Source,
+) -> u128 {
+    query(|index| cells.lock_hash(index, source))
+        .enumerate()
+        .filter(|(index, hash)| {
+            hash == lock_hash
+                && cells.type_hash(*index, source).unwrap_or_default() == Some(*udt_type_hash)
+        })
+        .map(|(index, _)| {
+            // xUDT amount is stored in the first 16 bytes of cell data
+            let data = cells.data(index, source).unwrap_or_default();
+            data.get(..16)
+                .map(|amount| u128::from_le_bytes(amount.try_into().unwrap_or_default()))
+                .unwrap_or_default()
+        })
+        .sum()
+}
+
+/// The type args of an output start with the type ID of its index
+pub fn verify_type_id(cells: &impl Cells, index: usize) -> bool {
+    let first_input = match cells.first_input() {
+        Ok(first_input) => first_input,
+        Err(_) => return false,
+    };
+    let type_id_args = cells
+        .type_script(index, Output)
+        .unwrap_or(None)
+        .unwrap_or_default()
+        .args()
+        .raw_data();
+    type_id_args.get(..32) == Some(&calc_type_id(&first_input, index)[..])
+}
+
+fn spore_data(cells: &impl Cells, index: usize, source: Source) -> Result<SporeData, Failure> {
+    let data = cells.data(index, source).at_field(source, index, "data")?;
+    SporeData::from_compatible_slice(&data)
+        .map_err(|_| Failure::new(Error::InvalidNFTData, source, index, "data"))
+}
+
+fn cluster_data(
+    cells: &impl Cells,
+    index: usize,
+    source: Source,
+) -> Result<ClusterDataV2, Failure> {
+    let data = cells.data(index, source).at_field(source, index, "data")?;
+    parse_cluster_data(&data).at_field(source, index, "data")
+}
+
+fn agent_data(cells: &impl Cells, index: usize, source: Source) -> Result<AgentData, Failure> {
+    let data = cells.data(index, source).at_field(source, index, "data")?;
+    AgentData::parse(&data).at_field(source, index, "data")
+}
+
+/// Data hash of the code a script runs, either its code hash or, when referenced by type, the data
+/// hash of the cell dep with that type hash
+fn code_data_hash(cells: &impl Cells, script: &Script) -> Option<[u8; 32]> {
+    let code_hash: [u8; 32] = script.code_hash().unpack();
+    if script.hash_type().as_slice() != [HASH_TYPE_TYPE] {
+        return Some(code_hash);
+    }
+    let dep_index = find_by_type_hash(cells, &code_hash, CellDep)?;
+    cells.data_hash(dep_index, CellDep).ok()
+}
+
+/// Whether a type script is a Spore of the running script. A Spore is recognized by the code it
+/// runs, so Spores referring to the same Spore code by data hash and by type are all counted
+fn is_spore(cells: &impl Cells, script: &Script, code: Option<[u8; 32]>, type_: &Script) -> bool {
+    (type_.code_hash().as_slice() == script.code_hash().as_slice()
+        && type_.hash_type().as_slice() == script.hash_type().as_slice())
+        || (code.is_some() && code_data_hash(cells, type_) == code)
+}
+
+/// Spores of the running script in a source, with their indices
+fn spores(cells: &impl Cells, source: Source) -> Result<Vec<(usize, Script)>, Error> {
+    let script = cells.script()?;
+    let code = code_data_hash(cells, &script);
+    Ok(query(|index| cells.type_script(index, source))
+        .enumerate()
+        .filter_map(|(index, type_)| {
+            Some((
+                index,
+                type_.filter(|type_| is_spore(cells, &script, code, type_))?,
+            ))
+        })
+        .collect())
+}
+
+/// Whether a Spore is minted at an output index lower than the given one, which only looks at the
+/// outputs up to the first Spore minted
+fn minted_before(cells: &impl Cells, index: usize) -> Result<bool, Error> {
+    let script = cells.script()?;
+    let code = code_data_hash(cells, &script);
+    for output_index in 0..index {
+        match cells.type_script(output_index, Output)? {
+            Some(type_) if is_spore(cells, &script, code, &type_) => {
+                let type_hash = cells.type_hash(output_index, Output)?.unwrap_or_default();
+                if find_by_type_hash(cells, &type_hash, Input).is_none() {
+                    return Ok(true);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(false)
+}
+
+/// Output indices of the Spores minted in this transaction
+pub fn collect_spore_creations(cells: &impl Cells) -> Result<Vec<usize>, Error> {
+    let spores_in_input = spores(cells, Input)?;
+    Ok(spores(cells, Output)?
+        .into_iter()
+        .filter(|(_, type_)| {
+            !spores_in_input
+                .iter()
+                .any(|(_, spore)| spore.as_slice() == type_.as_slice())
+        })
+        .map(|(index, _)| index)
+        .collect())
+}
+
+/// Input indices of the Spores melted in this transaction
+pub fn collect_spore_destructions(cells: &impl Cells) -> Result<Vec<usize>, Error> {
+    let spores_in_output = spores(cells, Output)?;
+    Ok(spores(cells, Input)?
+        .into_iter()
+        .filter(|(_, type_)| {
+            !spores_in_output
+                .iter()
+                .any(|(_, spore)| spore.as_slice() == type_.as_slice())
+        })
+        .map(|(index, _)| index)
+        .collect())
+}
+
+/// Input and output indices of the Spores transferred in this transaction
+pub fn collect_spore_transfers(cells: &impl Cells) -> Result<Vec<(usize, usize)>, Error> {
+    let spores_in_output = spores(cells, Output)?;
+    Ok(spores(cells, Input)?
+        .into_iter()
+        .filter_map(|(input_index, type_)| {
+            spores_in_output
+                .iter()
+                .find(|(_, spore)| spore.as_slice() == type_.as_slice())
+                .map(|(output_index, _)| (input_index, *output_index))
+        })
+        .collect())
+}
+
+/// A cluster verified for minting and how many Spores are minted into it
+struct VerifiedCluster {
+    id: Vec<u8>,
+    dep_index: usize,
+    data: ClusterDataV2,
+    minted: u64,
+    // input and output index of the cluster agent consumed to mint (Condition 2)
+    agent: Option<(usize, usize)>,
+}
+
+/// Lookups shared by every Spore minted in the same transaction
+#[derive(Default)]
+struct CreationCache {
+    verified_clusters: Vec<VerifiedCluster>,
+    paid_mutants: Vec<[u8; 32]>,
+}
+
+fn verify_agent(cells: &impl Cells, agent_index: usize, source: Source) -> Result<(), Failure> {
+    let data = cells
+        .data(agent_index, source)
+        .at_field(source, agent_index, "data")?;
+    let agent_data = AgentData::parse(&data).at_field(source, agent_index, "data")?;
+    let failure = |rule| Failure::new(rule, source, agent_index, field_of(rule));
+
+    // the cluster owner revokes an agent by destroying the proxy it was created from. Legacy
+    // agents holding only the proxy type hash were minted through without it and stay usable
+    if data.len() > LEGACY_AGENT_DATA_LEN {
+        find_by_type_hash(cells, &agent_data.proxy_type_hash, CellDep)
+            .ok_or(failure(Error::AgentRevoked))?;
+    }
+
+    // an agent with a mint quota has to be consumed to update its remaining counter
+    if source == CellDep && agent_data.quota.is_some() {
+        return Err(failure(Error::AgentQuotaMismatch));
+    }
+
+    let expiry = match agent_data.expiry() {
+        Some(expiry) => expiry,
+        None => return Ok(()),
+    };
+
+    // a transaction can only prove that time has passed, so the agent is refused once any of
+    // the since of a consumed agent, the agent cell header or the header deps reaches it. The
+    // minter chooses them and can leave out any later block, so the expiry is advisory and only
+    // revoking the proxy stops an agent
+    if source == Input {
+        let since = cells
+            .since(agent_index)
+            .at_field(source, agent_index, "since")?;
+        if since_reached(&expiry, since) {
+            return Err(failure(Error::AgentExpired));
+        }
+    }
+    let reached =
+        |header: HeaderInfo| header_reached(&expiry, header.number, header.epoch, header.timestamp);
+    let agent_header = cells
+        .header(agent_index, source)
+        .at_field(source, agent_index, "header")?;
+    if reached(agent_header) || query(|index| cells.header(index, HeaderDep)).any(reached) {
+        return Err(failure(Error::AgentExpired));
+    }
+    Ok(())
+}
+
+fn verify_cluster(
+    cells: &impl Cells,
+    spore_index: usize,
+    cluster_id: &[u8],
+    cache: &mut CreationCache,
+) -> Result<ClusterDataV2, Failure> {
+    if let Some(cluster) = cache
+        .verified_clusters
+        .iter_mut()
+        .find(|cluster| cluster.id[..] == cluster_id[..])
+    {
+        cluster.minted += 1;
+        return Ok(cluster.data.clone());
+    }
+
+    let failure = |rule| Failure::new(rule, Output, spore_index, "cluster_id");
+    let dep_index = find_by_type_args(cells, cluster_id, CellDep, Contract::Cluster)
+        .ok_or(failure(Error::ClusterCellNotInDep))?;
+    // the cluster contract guarantees the cluster data will always be correct once created
+    let cluster_data = cluster_data(cells, dep_index, CellDep).unwrap_or_default();
+    if bool::from(cluster_data.closed()) {
+        return Err(Failure::new(
+            Error::ClusterClosed,
+            CellDep,
+            dep_index,
+            "closed",
+        ));
+    }
+
+    // Condition 1: Check if cluster exist in Inputs & Outputs
+    let cluster_in_tx = find_by_type_args(cells, cluster_id, Input, Contract::Cluster).is_some()
+        && find_by_type_args(cells, cluster_id, Output, Contract::Cluster).is_some();
+
+    let mut agent = None;
+    if !cluster_in_tx {
+        // Condition 2: Check if cluster agent in Inputs & Outputs
+        match (
+            find_by_type_args(cells, cluster_id, Input, Contract::ClusterAgent),
+            find_by_type_args(cells, cluster_id, Output, Contract::ClusterAgent),
+        ) {
+            (Some(input_index), Some(output_index)) => {
+                verify_agent(cells, input_index, Input)?;
+                agent = Some((input_index, output_index));
+            }
+            _ => {
+                // Condition 3: Use cluster agent by lock proxy
+                let proxy_index =
+                    match find_by_type_args(cells, cluster_id, CellDep, Contract::ClusterAgent) {
+                        Some(agent_index) => {
+                            verify_agent(cells, agent_index, CellDep)?;
+                            agent_index
+                        }
+                        // Condition 4: Check if Lock Proxy exist in Inputs & Outputs
+                        None => dep_index,
+                    };
+                let proxy_lock_hash =
+                    cells
+                        .lock_hash(proxy_index, CellDep)
+                        .at_field(CellDep, proxy_index, "lock")?;
+                find_by_lock_hash(cells, &proxy_lock_hash, Output)
+                    .ok_or(failure(Error::ClusterOwnershipVerifyFailed))?;
+                find_by_lock_hash(cells, &proxy_lock_hash, Input)
+                    .ok_or(failure(Error::ClusterOwnershipVerifyFailed))?;
+            }
+        }
+    }
+
+    cache.verified_clusters.push(VerifiedCluster {
+        id: cluster_id.to_vec(),
+        dep_index,
+        data: cluster_data.clone(),
+        minted: 1,
+        agent,
+    });
+    Ok(cluster_data)
+}
+
+fn verify_cluster_supplies(cells: &impl Cells, cache: &CreationCache) -> Result<(), Failure> {
+    for cluster in cache.verified_clusters.iter() {
+        if cluster.data.max_supply().is_none() {
+            continue;
+        }
+        // a limited cluster has to be in Inputs & Outputs (Condition 1) to update its minted counter
+        let not_in_tx = Failure::new(
+            Error::ClusterMintedCountMismatch,
+            CellDep,
+            cluster.dep_index,
+            "minted",
+        );
+        let input_index =
+            find_by_type_args(cells, &cluster.id, Input, Contract::Cluster).ok_or(not_in_tx)?;
+        let output_index =
+            find_by_type_args(cells, &cluster.id, Output, Contract::Cluster).ok_or(not_in_tx)?;
+        verify_cluster_supply(
+            &cluster_data(cells, input_index, Input)?,
+            &cluster_data(cells, output_index, Output)?,
+            cluster.minted,
+        )
+        .at_field(Output, output_index, "minted")?;
+    }
+    Ok(())
+}
+
+fn verify_agent_quotas(cells: &impl Cells, cache: &CreationCache) -> Result<(), Failure> {
+    for cluster in cache.verified_clusters.iter() {
+        let (input_index, output_index) = match cluster.agent {
+            Some(agent) => agent,
+            None => continue,
+        };
+        verify_agent_quota(
+            &agent_data(cells, input_index, Input)?,
+            &agent_data(cells, output_index, Output)?,
+            cluster.minted,
+        )
+        .at_field(Output, output_index, "quota")?;
+    }
+    Ok(())
+}
+
+/// A segmented Spore commits to the segment cells in CellDeps, which have to complete its content
+fn verify_segments(
+    cells: &impl Cells,
+    mime: &MIME,
+    content: &[u8],
+    index: usize,
+) -> Result<(), Failure> {
+    if !is_segmented(mime) {
+        return Ok(());
+    }
+    let segmented = SegmentedContent::parse(mime, content).at(Output, index)?;
+    let segments_data: Vec<Vec<u8>> = query(|index| cells.type_script(index, CellDep))
+        .enumerate()
+        .filter(|(_, type_)| match type_ {
+            Some(type_) => {
+                cells.is_contract(type_, Contract::SporeSegment)
+                    && type_.args().raw_data()[..] == segmented.segments_id[..]
+            }
+            None => false,
+        })
+        .filter_map(|(index, _)| cells.data(index, CellDep).ok())
+        .collect();
+    let segments: Vec<Segment> = segments_data
+        .iter()
+        .filter_map(|data| Segment::parse(data).ok())
+        .collect();
+    segmented.verify(&segments).at(Output, index)?;
+    Ok(())
+}
+
+/// Position in CellDeps of the cell of a mutant. A Lua mutant is found by the ID its type args
+/// start with, only on cells of the Spore Lua extension which keeps the ID unique. Any binary can
+/// run a native mutant, so it is found by the type hash of its cell instead
+pub fn find_mutant(cells: &impl Cells, mutant: &[u8; 32]) -> Option<usize> {
+    query(|index| cells.type_script(index, CellDep))
+        .position(|script| {
+            script.is_some_and(|script| {
+                cells.is_contract(&script, Contract::SporeExtensionLua)
+                    && script.args().raw_data().get(..32) == Some(&mutant[..])
+            })
+        })
+        .or_else(|| find_by_type_hash(cells, mutant, CellDep))
+}
+
+/// The code running every mutant of a Spore in one `exec`, and the position in CellDeps of the
+/// code of each mutant, `None` without mutants
+pub fn resolve_mutants(
+    cells: &impl Cells,
+    mime: &MIME,
+    source: Source,
+    index: usize,
+) -> Result<Option<(Script, Vec<usize>)>, Failure> {
+    let failure = |rule| Failure::new(rule, source, index, "content_type");
+    let mut mutant_indices = Vec::new();
+    let mut mutant_code: Option<Script> = None;
+    for (position, mutant) in mime.mutants.iter().enumerate() {
+        let mutant_index =
+            find_mutant(cells, mutant).ok_or(failure(Error::ExtensionCellNotInDep))?;
+        // the code of the mutant cell's type script runs the mutant, Lua or native
+        let mutant_type = cells
+            .type_script(mutant_index, CellDep)
+            .at_field(CellDep, mutant_index, "type")?
+            .unwrap_or_default();
+        match &mutant_code {
+            Some(code)
+                if code.code_hash().as_slice() != mutant_type.code_hash().as_slice()
+                    || code.hash_type() != mutant_type.hash_type() =>
+            {
+                return Err(failure(Error::MutantCodeMismatch))
+            }
+            Some(_) => {}
+            None => mutant_code = Some(mutant_type),
+        }
+        // a pinned mutant runs the pinned data, kept by the mutant cell or by any other cell
+        let code_index = match mime.mutant_codes.get(position) {
+            Some(code)
+                if cells.data_hash(mutant_index, CellDep).at_field(
+                    CellDep,
+                    mutant_index,
+                    "data",
+                )? != *code =>
+            {
+                query(|index| cells.data_hash(index, CellDep))
+                    .position(|hash| hash == *code)
+                    .ok_or(failure(Error::PinnedMutantCodeNotInDep))?
+            }
+            _ => mutant_index,
+        };
+        mutant_indices.push(code_index);
+    }
+    Ok(mutant_code.map(|code| (code, mutant_indices)))
+}
+
+fn verify_mutant_payment(cells: &impl Cells, mutant_index: usize, op: u8) -> Result<(), Failure> {
+    let mutant_type = cells
+        .type_script(mutant_index, CellDep)
+        .at_field(CellDep, mutant_index, "type")?
+        .unwrap_or_default();
+    let mutant_args = mutant_type.args().raw_data();
+    if let Some(payment) = MutantArgs::parse(&mutant_args)
+        .at(CellDep, mutant_index)?
+        .payment(op)
+    {
+        // the mutant's lock receives the payment on top of what it puts in
+        let lock =
+            cells
+                .lock_hash(mutant_index, CellDep)
+                .at_field(CellDep, mutant_index, "lock")?;
+        verify_payment(cells, &payment, &lock)
+            .map_err(|_| Error::ExtensionPaymentNotEnough)
+            .at(CellDep, mutant_index)?;
+    }
+    Ok(())
+}
+
+/// Mutants charging for a transfer or a melt are paid once per transaction, as on mint
+fn verify_mutant_payments(
+    cells: &impl Cells,
+    mime: &MIME,
+    op: u8,
+    source: Source,
+    index: usize,
+) -> Result<(), Failure> {
+    for mutant in mime.mutants.iter() {
+        let mutant_index = find_mutant(cells, mutant).ok_or(Failure::new(
+            Error::ExtensionCellNotInDep,
+            source,
+            index,
+            "content_type",
+        ))?;
+        verify_mutant_payment(cells, mutant_index, op)?;
+    }
+    Ok(())
+}
+
+fn verify_creation(
+    cells: &impl Cells,
+    index: usize,
+    cache: &mut CreationCache,
+) -> Result<(), Failure> {
+    let spore_data = spore_data(cells, index, Output)?;
+    let mime = verify_spore_creation(&spore_data).at(Output, index)?;
+    verify_segments(cells, &mime, spore_data.content().unpack(), index)?;
+
+    // verify NFT ID
+    if !verify_type_id(cells, index) {
+        return Err(Failure::new(
+            Error::InvalidNFTID,
+            Output,
+            index,
+            "type.args",
+        ));
+    }
+
+    if let Some(cluster_id) = spore_data.cluster_id().to_opt() {
+        let cluster_data = verify_cluster(cells, index, &cluster_id.raw_data(), cache)?;
+        verify_cluster_mutant(&mime, &cluster_data).at(Output, index)?;
+        verify_cluster_content_type(&mime, &cluster_data).at(Output, index)?;
+        verify_cluster_royalty(&mime, &cluster_data).at(Output, index)?;
+        verify_cluster_melt(&mime, &cluster_data).at(Output, index)?;
+    }
+
+    for mutant in mime.mutants.iter() {
+        if cache.paid_mutants.contains(mutant) {
+            continue;
+        }
+        let mutant_index = find_mutant(cells, mutant).ok_or(Failure::new(
+            Error::ExtensionCellNotInDep,
+            Output,
+            index,
+            "content_type",
+        ))?;
+        verify_mutant_payment(cells, mutant_index, OP_MINT)?;
+        cache.paid_mutants.push(*mutant);
+    }
+    Ok(())
+}
+
+/// Batch mint: the Spore with the lowest output index verifies every Spore minted in this
+/// transaction in one pass, so the others only need to run their own mutants
+pub fn verify_minted_spore(cells: &impl Cells, index: usize) -> Result<(), Failure> {
+    if minted_before(cells, index).at(Output, index)? {
+        return Ok(());
+    }
+    let creations = collect_spore_creations(cells).at(Output, index)?;
+    let mut cache = CreationCache::default();
+    for output_index in creations {
+        verify_creation(cells, output_index, &mut cache)?;
+    }
+    verify_cluster_supplies(cells, &cache)?;
+    verify_agent_quotas(cells, &cache)
+}
+
+/// The lock a melted Spore refunds to. A Spore melted to its cluster needs the cluster cell, which
+/// can't be destroyed as it has a royalty
+fn melt_refund_lock(
+    cells: &impl Cells,
+    index: usize,
+    spore_data: &SporeData,
+    policy: &MeltPolicy,
+) -> Result<[u8; 32], Failure> {
+    match policy.target {
+        MeltTarget::Lock(lock_hash) => Ok(lock_hash),
+        MeltTarget::ClusterOwner => {
+            let cluster_id = spore_data
+                .cluster_id()
+                .to_opt()
+                .ok_or(Error::InvalidMeltPolicy)
+                .at(Input, index)?;
+            let cluster_index =
+                find_by_type_args(cells, &cluster_id.raw_data(), CellDep, Contract::Cluster)
+                    .ok_or(Failure::new(
+                        Error::ClusterCellNotInDep,
+                        Input,
+                        index,
+                        "cluster_id",
+                    ))?;
+            cells
+                .lock_hash(cluster_index, CellDep)
+                .at_field(CellDep, cluster_index, "lock")
+        }
+    }
+}
+
+/// Every refund lock receives the refunds of all the Spores melted to it in this transaction
+fn verify_melt_refunds(cells: &impl Cells, destructions: &[usize]) -> Result<(), Failure> {
+    // refund lock, refund and the first Spore melted to it
+    let mut refunds: Vec<([u8; 32], u128, usize)> = Vec::new();
+    for &index in destructions {
+        let spore_data = spore_data(cells, index, Input)?;
+        let mime = MIME::parse_existing(spore_data.content_type().unpack()).at(Input, index)?;
+        let policy = match MeltPolicy::parse(&mime).at(Input, index)? {
+            Some(policy) => policy,
+            None => continue,
+        };
+        let lock_hash = melt_refund_lock(cells, index, &spore_data, &policy)?;
+        let capacity = cells.capacity(index, Input).at(Input, index)?;
+        let refund = policy.refund(capacity);
+        match refunds.iter_mut().find(|(lock, _, _)| lock == &lock_hash) {
+            Some((_, total, _)) => *total += refund,
+            None => refunds.push((lock_hash, refund, index)),
+        }
+    }
+    for (lock_hash, refund, index) in refunds {
+        // the melted Spores don't count as capacity put in by the refund lock
+        let mut melted = 0u128;
+        for &index in destructions {
+            if cells.lock_hash(index, Input).at(Input, index)? == lock_hash {
+                melted += cells.capacity(index, Input).at(Input, index)? as u128;
+            }
+        }
+        verify_melt_refund(
+            refund,
+            capacity_sum(cells, &lock_hash, Input) - melted,
+            capacity_sum(cells, &lock_hash, Output),
+        )
+        .at_field(Input, index, "capacity")?;
+    }
+    Ok(())
+}
+
+/// Checks a Spore melted at an input index, returning its content-type. The Spore melted with the
+/// lowest input index verifies the refunds of every Spore melted
+pub fn verify_melted_spore(cells: &impl Cells, index: usize) -> Result<MIME, Failure> {
+    let mime = verify_spore_destruction(&spore_data(cells, index, Input)?).at(Input, index)?;
+    let destructions = collect_spore_destructions(cells).at(Input, index)?;
+    if destructions.first() == Some(&index) {
+        verify_melt_refunds(cells, &destructions)?;
+    }
+    verify_mutant_payments(cells, &mime, OP_MELT, Input, index)?;
+    Ok(mime)
+}
+
+/// Price in the witness of a Spore transferred by a marketplace trade
+fn load_price(cells: &impl Cells, input_index: usize) -> Result<Option<u64>, Failure> {
+    match cells
+        .witness_input_type(input_index)
+        .at_field(Input, input_index, "witness")?
+    {
+        Some(price) => {
+            parse_price_witness(&price)
+                .map(Some)
+                .at_field(Input, input_index, "witness")
+        }
+        None => Ok(None),
+    }
+}
+
+/// The royalty a Spore pays on transfer, from its own terms or its cluster's
+fn load_royalty(cells: &impl Cells, input_index: usize) -> Result<Option<Royalty>, Failure> {
+    let spore_data = spore_data(cells, input_index, Input)?;
+    let mime = MIME::parse_existing(spore_data.content_type().unpack()).at(Input, input_index)?;
+    match RoyaltyTerms::parse(&mime).at(Input, input_index)? {
+        Some(RoyaltyTerms::Spore(royalty)) => Ok(Some(royalty)),
+        Some(RoyaltyTerms::Cluster) => {
+            let cluster_id = spore_data
+                .cluster_id()
+                .to_opt()
+                .ok_or(Error::InvalidRoyalty)
+                .at(Input, input_index)?;
+            let cluster_index =
+                find_by_type_args(cells, &cluster_id.raw_data(), CellDep, Contract::Cluster)
+                    .ok_or(Failure::new(
+                        Error::ClusterCellNotInDep,
+                        Input,
+                        input_index,
+                        "cluster_id",
+                    ))?;
+            match cluster_data(cells, cluster_index, CellDep)?
+                .royalty()
+                .to_opt()
+            {
+                Some(royalty) => Royalty::parse(&royalty.raw_data()).map(Some).at_field(
+                    CellDep,
+                    cluster_index,
+                    "royalty",
+                ),
+                None => Ok(None),
+            }
+        }
+        None => Ok(None),
+    }
+}
+
+/// Every Spore changing hands pays its royalty, and the price of its trade to the previous owner
+fn verify_transfer_royalties(
+    cells: &impl Cells,
+    transfers: &[(usize, usize)],
+) -> Result<(), Failure> {
+    let lock_hash = |index: usize, source: Source| cells.lock_hash(index, source).at(source, index);
+    let capacity = |index: usize, source: Source| {
+        cells
+            .capacity(index, source)
+            .map(u128::from)
+            .at(source, index)
+    };
+    // lock hash, royalties and prices owed to it, and the first Spore owing it
+    let mut dues: Vec<([u8; 32], u128, u128, usize)> = Vec::new();
+    let mut owe = |lock_hash: [u8; 32], royalty: u128, price: u128, index: usize| match dues
+        .iter_mut()
+        .find(|(lock, _, _, _)| lock == &lock_hash)
+    {
+        Some((_, royalties, prices, _)) => {
+            *royalties += royalty;
+            *prices += price;
+        }
+        None => dues.push((lock_hash, royalty, price, index)),
+    };
+    for &(input_index, output_index) in transfers {
+        let seller = lock_hash(input_index, Input)?;
+        if seller == lock_hash(output_index, Output)? {
+            continue;
+        }
+        let price = load_price(cells, input_index)?;
+        if let Some(royalty) = load_royalty(cells, input_index)? {
+            let amount = royalty.amount(price.unwrap_or_default());
+            owe(royalty.recipient, amount, 0, input_index);
+        }
+        if let Some(price) = price {
+            owe(seller, 0, price as u128, input_index);
+        }
+    }
+    for (lock, royalty, price, index) in dues {
+        // the Spores transferred don't count as capacity put in or received by the lock
+        let (mut spores_in, mut spores_out) = (0u128, 0u128);
+        for &(input_index, output_index) in transfers {
+            if lock_hash(input_index, Input)? == lock {
+                spores_in += capacity(input_index, Input)?;
+            }
+            if lock_hash(output_index, Output)? == lock {
+                spores_out += capacity(output_index, Output)?;
+            }
+        }
+        verify_transfer_dues(
+            royalty,
+            price,
+            capacity_sum(cells, &lock, Input) - spores_in,
+            capacity_sum(cells, &lock, Output) - spores_out,
+        )
+        .at_field(Input, index, "capacity")?;
+    }
+    Ok(())
+}
+
+/// Checks a Spore transferred from an input to an output index, returning its content-type. The
+/// Spore transferred with the lowest input index verifies the dues of every transfer
+pub fn verify_transferred_spore(
+    cells: &impl Cells,
+    input_index: usize,
+    output_index: usize,
+) -> Result<MIME, Failure> {
+    let input_data = spore_data(cells, input_index, Input)?;
+    let output_data = spore_data(cells, output_index, Output)?;
+    let mime = verify_spore_transfer(&input_data, &output_data).map_err(|rule| {
+        let field = match rule {
+            Error::ModifySporePermanentField => spore_transfer_field(&input_data, &output_data),
+            rule => field_of(rule),
+        };
+        Failure::new(rule, Output, output_index, field)
+    })?;
+    if input_data.content().as_slice() != output_data.content().as_slice() {
+        verify_segments(cells, &mime, output_data.content().unpack(), output_index)?;
+    }
+
+    let transfers = collect_spore_transfers(cells).at(Input, input_index)?;
+    if transfers.first().map(|(input, _)| input) == Some(&input_index) {
+        verify_transfer_royalties(cells, &transfers)?;
+    }
+    verify_mutant_payments(cells, &mime, OP_TRANSFER, Input, input_index)?;
+    Ok(mime)
+}
+
+pub fn verify_created_cluster(cells: &impl Cells, index: usize) -> Result<(), Failure> {
+    let cluster_data = cluster_data(cells, index, Output)?;
+    verify_cluster_creation(&cluster_data).map_err(|rule| {
+        let field = match rule {
+            Error::InvalidClusterData if u64::from(cluster_data.minted()) == 0 => {
+                invalid_cluster_field(&cluster_data)
+            }
+            _ => field_of(rule),
+        };
+        Failure::new(rule, Output, index, field)
+    })?;
+    if !verify_type_id(cells, index) {
+        return Err(Failure::new(
+            Error::InvalidClusterID,
+            Output,
+            index,
+            "type.args",
+        ));
+    }
+
+    // Verify if mutant is set
+    if cluster_data.mutant_id().is_some() {
+        let args = cells
+            .script()
+            .at_field(Output, index, "type")?
+            .args()
+            .raw_data();
+        find_by_type_args(cells, &args, CellDep, Contract::SporeExtensionLua).ok_or(
+            Failure::new(Error::MutantNotInDeps, Output, index, "mutant_id"),
+        )?;
+    }
+
+    // listed mutants have to exist, found by their ID in CellDep like the Spore contract does, so
+    // native mutants can be listed as well as Lua ones
+    let listed_mutants = [
+        ("required_mutants", cluster_data.required_mutants()),
+        ("allowed_mutants", cluster_data.allowed_mutants()),
+    ];
+    for (field, mutants) in listed_mutants {
+        for mutant_id in mutants.to_opt().into_iter().flatten() {
+            let mutant_id: [u8; 32] = mutant_id.raw_data()[..].try_into().unwrap_or_default();
+            find_mutant(cells, &mutant_id).ok_or(Failure::new(
+                Error::MutantNotInDeps,
+                Output,
+                index,
+                field,
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+pub fn verify_transferred_cluster(
+    cells: &impl Cells,
+    input_index: usize,
+    output_index: usize,
+) -> Result<(), Failure> {
+    let input_data = cluster_data(cells, input_index, Input)?;
+    let output_data = cluster_data(cells, output_index, Output)?;
+    verify_cluster_transfer(&input_data, &output_data).map_err(|rule| {
+        let field = match rule {
+            Error::ModifyClusterPermanentField => cluster_transfer_field(&input_data, &output_data),
+            rule => field_of(rule),
+        };
+        Failure::new(rule, Output, output_index, field)
+    })
+}
+
+pub fn verify_destroyed_cluster(cells: &impl Cells, index: usize) -> Result<(), Failure> {
+    verify_cluster_destruction(&cluster_data(cells, index, Input)?).map_err(|rule| {
+        let field = match rule {
+            Error::ClusterRequiresRoyalty => "royalty",
+            rule => field_of(rule),
+        };
+        Failure::new(rule, Input, index, field)
+    })
+}
+
+pub fn verify_created_proxy(cells: &impl Cells, index: usize) -> Result<(), Failure> {
+    let failure = |rule| Failure::new(rule, Output, index, "data");
+    let cluster_id = cells.data(index, Output).at_field(Output, index, "data")?;
+    // check cluster in Deps
+    let dep_index = find_by_type_args(cells, &cluster_id, CellDep, Contract::Cluster)
+        .ok_or(failure(Error::ClusterCellNotInDep))?;
+
+    // verify Proxy ID
+    if !verify_type_id(cells, index) {
+        return Err(Failure::new(
+            Error::InvalidProxyID,
+            Output,
+            index,
+            "type.args",
+        ));
+    }
+
+    // Condition 1: Check if cluster exist in Inputs & Outputs
+    if find_by_type_args(cells, &cluster_id, Input, Contract::Cluster).is_some()
+        && find_by_type_args(cells, &cluster_id, Output, Contract::Cluster).is_some()
+    {
+        return Ok(());
+    }
+    // Condition 2: Check if Lock Proxy exist in Inputs & Outputs
+    let cluster_lock_hash = cells
+        .lock_hash(dep_index, CellDep)
+        .at_field(CellDep, dep_index, "lock")?;
+    find_by_lock_hash(cells, &cluster_lock_hash, Output)
+        .ok_or(failure(Error::ClusterOwnershipVerifyFailed))?;
+    find_by_lock_hash(cells, &cluster_lock_hash, Input)
+        .ok_or(failure(Error::ClusterOwnershipVerifyFailed))?;
+    Ok(())
+}
+
+pub fn verify_transferred_proxy(
+    cells: &impl Cells,
+    input_index: usize,
+    output_index: usize,
+) -> Result<(), Failure> {
+    let args = |index: usize, source: Source| {
+        cells
+            .type_script(index, source)
+            .map(|script| script.unwrap_or_default().args().raw_data())
+            .at_field(source, index, "type")
+    };
+    verify_proxy_transfer(&args(input_index, Input)?, &args(output_index, Output)?)
+        .at(Output, output_index)
+}
+
+pub fn verify_created_agent(cells: &impl Cells, index: usize) -> Result<(), Failure> {
+    let data = cells.data(index, Output).at_field(Output, index, "data")?;
+    let agent_data = AgentData::parse(&data).at_field(Output, index, "data")?;
+    // the legacy data of the proxy type hash alone can't be revoked, only existing agents keep it
+    if data.len() == LEGACY_AGENT_DATA_LEN {
+        return Err(Failure::new(Error::InvalidAgentData, Output, index, "data"));
+    }
+    let proxy_type_hash = &agent_data.proxy_type_hash;
+    // check cluster proxy in Deps
+    let dep_index = find_by_type_hash(cells, proxy_type_hash, CellDep).ok_or(Failure::new(
+        Error::ProxyCellNotInDep,
+        Output,
+        index,
+        "data",
+    ))?;
+    let proxy_type = cells
+        .type_script(dep_index, CellDep)
+        .at_field(CellDep, dep_index, "type")?
+        .unwrap_or_default();
+    if !cells.is_contract(&proxy_type, Contract::ClusterProxy) {
+        return Err(Failure::new(
+            Error::RefCellNotClusterProxy,
+            CellDep,
+            dep_index,
+            "type",
+        ));
+    }
+
+    // verify cluster ID
+    let cluster_id = cells
+        .data(dep_index, CellDep)
+        .at_field(CellDep, dep_index, "data")?;
+    let agent_type = cells
+        .type_script(index, Output)
+        .at_field(Output, index, "type")?
+        .unwrap_or_default();
+    if agent_type.args().raw_data()[..] != cluster_id[..] {
+        return Err(Failure::new(
+            Error::InvalidAgentArgs,
+            Output,
+            index,
+            "type.args",
+        ));
+    }
+
+    // Condition 1: Check if cluster proxy exist in Inputs & Outputs
+    if find_by_type_hash(cells, proxy_type_hash, Input).is_some()
+        && find_by_type_hash(cells, proxy_type_hash, Output).is_some()
+    {
+        return Ok(());
+    }
+    // Condition 2: Check for payment. The expiry and quota limit the agent for the cluster owner,
+    // who has to consume the proxy to set them
+    if agent_data.expiry != 0 || agent_data.quota.is_some() {
+        return Err(Failure::new(Error::InvalidAgentData, Output, index, "data"));
+    }
+    let proxy_args = proxy_type.args().raw_data();
+    let payment = Payment::parse_proxy(proxy_args.get(CLUSTER_PROXY_ID_LEN..).unwrap_or_default())
+        .at(CellDep, dep_index)?;
+    let lock = cells
+        .lock_hash(dep_index, CellDep)
+        .at_field(CellDep, dep_index, "lock")?;
+    verify_payment(cells, &payment, &lock).at(CellDep, dep_index)
+}
+
+pub fn verify_transferred_agent(
+    cells: &impl Cells,
+    input_index: usize,
+    output_index: usize,
+) -> Result<(), Failure> {
+    let input_data = cells
+        .data(input_index, Input)
+        .at_field(Input, input_index, "data")?;
+    let output_data = cells
+        .data(output_index, Output)
+        .at_field(Output, output_index, "data")?;
+    // only the mint quota can be decreased, the spore contract checks it matches new Spores
+    verify_agent_transfer(&input_data, &output_data).at(Output, output_index)
+}
+
+/// Segments are immutable and can't be destroyed, every input of the running script is transferred
+/// to an output with its data. New segments follow the previous one, and the last one completes
+/// the content
+pub fn verify_created_segments(cells: &impl Cells) -> Result<(), Failure> {
+    let script = cells.script().at_field(Output, 0, "type")?;
+    let is_segment = |type_: &Option<Script>| {
+        type_
+            .as_ref()
+            .is_some_and(|type_| type_.as_slice() == script.as_slice())
+    };
+    let segments_data = |source: Source| -> Vec<(usize, Vec<u8>)> {
+        query(|index| cells.type_script(index, source))
+            .enumerate()
+            .filter(|(_, type_)| is_segment(type_))
+            .filter_map(|(index, _)| Some((index, cells.data(index, source).ok()?)))
+            .collect()
+    };
+
+    let outputs_data = segments_data(Output);
+    let mut creations: Vec<&(usize, Vec<u8>)> = outputs_data.iter().collect();
+    for (index, data) in segments_data(Input) {
+        match creations.iter().position(|(_, output)| output == &data) {
+            Some(position) => {
+                creations.remove(position);
+            }
+            None => {
+                return Err(Failure::new(
+                    Error::DestroyImmortalNFT,
+                    Input,
+                    index,
+                    "data",
+                ))
+            }
+        }
+    }
+    let first = match creations.first() {
+        Some((index, _)) => *index,
+        None => return Ok(()),
+    };
+    if script.args().raw_data().len() != 32 {
+        return Err(Failure::new(
+            Error::InvalidSegmentID,
+            Output,
+            first,
+            "type.args",
+        ));
+    }
+
+    let siblings_data: Vec<(usize, Vec<u8>)> = segments_data(CellDep)
+        .into_iter()
+        .chain(outputs_data.iter().cloned())
+        .collect();
+    let siblings: Vec<Segment> = siblings_data
+        .iter()
+        .filter_map(|(_, data)| Segment::parse(data).ok())
+        .collect();
+    for (index, data) in creations {
+        let index = *index;
+        let segment = Segment::parse(data).at_field(Output, index, "data")?;
+        // the first segment sets the id of the content, the next ones follow it in order
+        if segment.index == 0 {
+            if !verify_type_id(cells, index) {
+                return Err(Failure::new(
+                    Error::InvalidSegmentID,
+                    Output,
+                    index,
+                    "type.args",
+                ));
+            }
+        } else if !siblings
+            .iter()
+            .any(|sibling| sibling.index + 1 == segment.index && sibling.is_sibling(&segment))
+        {
+            return Err(Failure::new(Error::SegmentNotFound, Output, index, "index"));
+        }
+
+        // the last segment completes the content, which has to match its digest
+        if segment.index + 1 == segment.total {
+            assemble_segments(&siblings, segment.total, segment.content_digest).at_field(
+                Output,
+                index,
+                "content_digest",
+            )?;
+        }
+    }
+    Ok(())
+}
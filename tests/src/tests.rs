@@ -1,18 +1,33 @@
+use ckb_testtool::builtin::ALWAYS_SUCCESS;
 use ckb_testtool::ckb_types::{
-    bytes::Bytes, core::TransactionBuilder, packed, packed::*, prelude::*,
+    bytes::Bytes, core::Capacity, core::EpochNumberWithFraction, core::HeaderBuilder,
+    core::TransactionBuilder, core::TransactionView, packed, packed::*, prelude::*,
 };
 use ckb_testtool::context::Context;
-use hex;
 use hex::encode;
 
-use spore_types::generated::spore_types::{ClusterData, SporeData};
+use spore_sdk::agent::ClusterAgentCreate;
+use spore_sdk::cluster::{ClusterCreate, ClusterUpdate, Royalty};
+use spore_sdk::content_ref::{
+    content_ref, file_digest, verify_spore_content, CONTENT_REF_CONTENT_TYPE,
+};
+use spore_sdk::mutant::MutantCreate;
+use spore_sdk::proxy::{ClusterProxyCreate, Payment};
+use spore_sdk::segment::{assemble_content, SegmentCreate, SegmentPlan, SEGMENTED_CONTENT_TYPE};
+use spore_sdk::spore::{AgentRef, SporeMelt, SporeMint, SporeTransfer};
+use spore_sdk::validator::{CellSource, Diagnostic, Rule};
+use spore_sdk::{Error as SdkError, LiveCell};
+use spore_types::generated::spore_types::{
+    BytesOpt, BytesVecOpt, ClusterData, ClusterDataV2, SporeData,
+};
 use spore_types::NativeNFTData;
+use spore_utils::extension::NO_PAYMENT;
+use spore_utils::multipart::MAX_MULTIPART_PARTS;
+use spore_utils::payment::to_descriptors;
 
 use crate::utils::*;
 use crate::Loader;
 
-const MAX_CYCLES: u64 = 10_000_000;
-
 #[test]
 fn test_simple_spore_mint() {
     let serialized = build_serialized_spore("Hello Spore!", "plain/text");
@@ -35,9 +50,9 @@ fn test_simple_spore_mint() {
         0
     );
     let spore_type_id = build_script_args(&input_cell, 0);
-    let type_ = build_spore_type_script(&mut context, &spore_out_point, spore_type_id.clone());
+    let type_ = build_spore_type_script(&mut context, &spore_out_point, spore_type_id);
 
-    let spore_out_cell = build_output_cell_with_type_id(&mut context, capacity, type_.clone());
+    let spore_out_cell = build_output_cell_with_type_id(&mut context, capacity, type_);
 
     let tx = TransactionBuilder::default()
         .input(input_cell)
@@ -71,8 +86,8 @@ fn test_simple_cluster_mint() {
         .build();
     let input_cell = build_normal_input(&mut context, capacity);
     let cluster_type_id = build_script_args(&input_cell, 0);
-    let type_ = build_spore_type_script(&mut context, &cluster_out_point, cluster_type_id.clone());
-    let cluster_out_cell = build_output_cell_with_type_id(&mut context, capacity, type_.clone());
+    let type_ = build_spore_type_script(&mut context, &cluster_out_point, cluster_type_id);
+    let cluster_out_cell = build_output_cell_with_type_id(&mut context, capacity, type_);
 
     let tx = TransactionBuilder::default()
         .input(input_cell)
@@ -110,15 +125,15 @@ fn test_simple_spore_mint_with_cluster() {
     let cluster_input_cell =
         build_cluster_input(&mut context, cluster.clone(), cluster_type.clone());
     let cluster_output_cell =
-        build_output_cell_with_type_id(&mut context, cluster_capacity, cluster_type.clone());
+        build_output_cell_with_type_id(&mut context, cluster_capacity, cluster_type);
 
     // spore
     let spore_content: Vec<u8> = "Hello Spore!".as_bytes().to_vec();
     let spore_type = String::from("plain/text");
     let spore_data: NativeNFTData = NativeNFTData {
-        content: spore_content.clone(),
-        content_type: spore_type.clone(),
-        cluster_id: Some(cluster_type_id.to_vec().clone()),
+        content: spore_content,
+        content_type: spore_type,
+        cluster_id: Some(cluster_type_id.to_vec()),
     };
     let serialized = SporeData::from(spore_data);
     let capacity = serialized.total_size() as u64;
@@ -133,8 +148,8 @@ fn test_simple_spore_mint_with_cluster() {
 
     let input_cell = build_normal_input(&mut context, capacity);
     let spore_type_id = build_script_args(&input_cell, 1);
-    let spore_type = build_spore_type_script(&mut context, &spore_out_point, spore_type_id.clone());
-    let spore_out_cell = build_output_cell_with_type_id(&mut context, capacity, spore_type.clone());
+    let spore_type = build_spore_type_script(&mut context, &spore_out_point, spore_type_id);
+    let spore_out_cell = build_output_cell_with_type_id(&mut context, capacity, spore_type);
 
     let tx = TransactionBuilder::default()
         .inputs(vec![input_cell, cluster_input_cell])
@@ -182,9 +197,9 @@ fn test_spore_mint_with_lock_proxy() {
     let spore_content: Vec<u8> = "Hello Spore!".as_bytes().to_vec();
     let spore_type = String::from("plain/text");
     let spore_data: NativeNFTData = NativeNFTData {
-        content: spore_content.clone(),
-        content_type: spore_type.clone(),
-        cluster_id: Some(cluster_type_id.to_vec().clone()),
+        content: spore_content,
+        content_type: spore_type,
+        cluster_id: Some(cluster_type_id.to_vec()),
     };
     let serialized = SporeData::from(spore_data);
     let capacity = serialized.total_size() as u64;
@@ -196,8 +211,8 @@ fn test_spore_mint_with_lock_proxy() {
 
     let input_cell = build_normal_input(&mut context, capacity);
     let spore_type_id = build_script_args(&input_cell, 0);
-    let spore_type = build_spore_type_script(&mut context, &spore_out_point, spore_type_id.clone());
-    let spore_out_cell = build_output_cell_with_type_id(&mut context, capacity, spore_type.clone());
+    let spore_type = build_spore_type_script(&mut context, &spore_out_point, spore_type_id);
+    let spore_out_cell = build_output_cell_with_type_id(&mut context, capacity, spore_type);
 
     let tx = TransactionBuilder::default()
         .inputs(vec![input_cell])
@@ -232,14 +247,14 @@ fn test_spore_mint_with_lock_proxy_failure() {
     let cluster_type_id = build_script_args(&input_cell, 0);
     let cluster_type =
         build_spore_type_script(&mut context, &cluster_out_point, cluster_type_id.clone());
-    let cluster_cell = build_cluster_input(&mut context, cluster.clone(), cluster_type.clone());
+    let cluster_cell = build_cluster_input(&mut context, cluster.clone(), cluster_type);
     // spore
     let spore_content: Vec<u8> = "Hello Spore!".as_bytes().to_vec();
     let spore_type = String::from("plain/text");
     let spore_data: NativeNFTData = NativeNFTData {
-        content: spore_content.clone(),
-        content_type: spore_type.clone(),
-        cluster_id: Some(cluster_type_id.to_vec().clone()),
+        content: spore_content,
+        content_type: spore_type,
+        cluster_id: Some(cluster_type_id.to_vec()),
     };
     let serialized = SporeData::from(spore_data);
     let capacity = serialized.total_size() as u64;
@@ -254,8 +269,8 @@ fn test_spore_mint_with_lock_proxy_failure() {
 
     let input_cell = build_normal_input(&mut context, capacity);
     let spore_type_id = build_script_args(&input_cell, 0);
-    let spore_type = build_spore_type_script(&mut context, &spore_out_point, spore_type_id.clone());
-    let spore_out_cell = build_output_cell_with_type_id(&mut context, capacity, spore_type.clone());
+    let spore_type = build_spore_type_script(&mut context, &spore_out_point, spore_type_id);
+    let spore_out_cell = build_output_cell_with_type_id(&mut context, capacity, spore_type);
 
     let tx = TransactionBuilder::default()
         .inputs(vec![input_cell])
@@ -309,8 +324,8 @@ fn test_spore_mint_with_cluster_proxy() {
 
     let input_cell = build_normal_input(&mut context, capacity);
     let proxy_type_id = build_script_args(&input_cell, 0);
-    let proxy_type = build_spore_type_script(&mut context, &proxy_out_point, proxy_type_id.clone());
-    let proxy_out_cell = build_output_cell_with_type_id(&mut context, capacity, proxy_type.clone());
+    let proxy_type = build_spore_type_script(&mut context, &proxy_out_point, proxy_type_id);
+    let proxy_out_cell = build_output_cell_with_type_id(&mut context, capacity, proxy_type);
 
     let tx = TransactionBuilder::default()
         .inputs(vec![input_cell])
@@ -378,16 +393,23 @@ fn test_cluster_agent() {
     let input_cell = build_normal_input(&mut context, agent_capacity);
 
     let agent_type = build_spore_type_script(&mut context, &agent_out_point, cluster_type_id);
-    let agent_out_cell = build_output_cell_with_type_id(&mut context, capacity, agent_type.clone());
+    let agent_out_cell = build_output_cell_with_type_id(&mut context, capacity, agent_type);
+    // the proxy asks for 10^1 shannons, paid to its lock shared by every cell here
+    let payment_cell = build_normal_output(&mut context, 10);
 
     let tx = TransactionBuilder::default()
         .inputs(vec![input_cell])
-        .outputs(vec![agent_out_cell])
-        .outputs_data(vec![proxy_type
-            .unwrap_or_default()
-            .calc_script_hash()
-            .as_slice()
-            .pack()])
+        .outputs(vec![agent_out_cell, payment_cell])
+        .outputs_data(vec![
+            // the proxy type hash and no expiry
+            [
+                proxy_type.unwrap_or_default().calc_script_hash().as_slice(),
+                &[0; 8],
+            ]
+            .concat()
+            .pack(),
+            Bytes::new().pack(),
+        ])
         .cell_deps(vec![
             cluster_script_dep,
             proxy_script_dep,
@@ -417,7 +439,7 @@ fn test_simple_spore_transfer() {
         &mut context,
         &spore_out_point,
         serialized.clone(),
-        spore_type_id.clone(),
+        spore_type_id,
     );
 
     let spore_output = build_output_cell_with_type_id(&mut context, capacity, spore_type);
@@ -455,8 +477,8 @@ fn test_simple_spore_mint3() {
     let spore_content: Vec<u8> = "Hello Spore!".as_bytes().to_vec();
     let spore_type = String::from("plain/text");
     let spore_data: NativeNFTData = NativeNFTData {
-        content: spore_content.clone(),
-        content_type: spore_type.clone(),
+        content: spore_content,
+        content_type: spore_type,
         cluster_id: None,
     };
     let serialized = SporeData::from(spore_data);
@@ -477,9 +499,9 @@ fn test_simple_spore_mint3() {
     let input_cell3 = build_normal_input(&mut context, capacity);
 
     let spore_type_id = build_script_args(&input_cell1, 0);
-    let spore_type = build_spore_type_script(&mut context, &spore_out_point, spore_type_id.clone());
+    let spore_type = build_spore_type_script(&mut context, &spore_out_point, spore_type_id);
 
-    let spore_out_cell = build_output_cell_with_type_id(&mut context, capacity, spore_type.clone());
+    let spore_out_cell = build_output_cell_with_type_id(&mut context, capacity, spore_type);
     let output_cell1 = build_normal_output(&mut context, capacity);
 
     let tx = TransactionBuilder::default()
@@ -502,8 +524,8 @@ fn test_simple_spore_mint4() {
     let spore_content: Vec<u8> = "Hello Spore!".as_bytes().to_vec();
     let spore_type = String::from("plain/text");
     let spore_data: NativeNFTData = NativeNFTData {
-        content: spore_content.clone(),
-        content_type: spore_type.clone(),
+        content: spore_content,
+        content_type: spore_type,
         cluster_id: None,
     };
     let serialized = SporeData::from(spore_data);
@@ -524,17 +546,15 @@ fn test_simple_spore_mint4() {
     let input_cell3 = build_normal_input(&mut context, capacity);
 
     let spore_type_id = build_script_args(&input_cell1, 0);
-    let spore_type = build_spore_type_script(&mut context, &spore_out_point, spore_type_id.clone());
+    let spore_type = build_spore_type_script(&mut context, &spore_out_point, spore_type_id);
 
-    let spore_out_cell = build_output_cell_with_type_id(&mut context, capacity, spore_type.clone());
+    let spore_out_cell = build_output_cell_with_type_id(&mut context, capacity, spore_type);
     let output_cell1 = build_normal_output(&mut context, capacity);
 
     let spore_type_id2 = build_script_args(&input_cell1, 2);
-    let spore_type2 =
-        build_spore_type_script(&mut context, &spore_out_point, spore_type_id2.clone());
+    let spore_type2 = build_spore_type_script(&mut context, &spore_out_point, spore_type_id2);
 
-    let spore_out_cell2 =
-        build_output_cell_with_type_id(&mut context, capacity, spore_type2.clone());
+    let spore_out_cell2 = build_output_cell_with_type_id(&mut context, capacity, spore_type2);
 
     let tx = TransactionBuilder::default()
         .inputs(vec![input_cell1, input_cell2, input_cell3])
@@ -554,14 +574,25 @@ fn test_simple_spore_mint4() {
         .expect("test simple spore mint 3");
 }
 
+const MULTIPART_CONTENT: &str = "THIS IS A TEST MULTIPART NFT\r\n\
+    --SporeDefaultBoundary\r\n\
+    Content-Type: text/plain\r\n\
+    \r\n\
+    This is an extra message I want to include\r\n\
+    --SporeDefaultBoundary\r\n\
+    Content-Type: image/svg+xml\r\n\
+    \r\n\
+    <svg xmlns=\"http://www.w3.org/2000/svg\"/>\r\n\
+    --SporeDefaultBoundary--\r\n";
+
 #[test]
 fn test_spore_multipart_mint() {
-    let (mut context, tx) =
-        simple_build_context("THIS IS A TEST MULTIPART NFT\n\n--SporeDefaultBoundary\nThis is an extra message I want to include".as_bytes().to_vec(),
-                             "multipart/mixed;boundary=SporeDefaultBoundary",
-                             None,
-                             0,
-        );
+    let (mut context, tx) = simple_build_context(
+        MULTIPART_CONTENT.as_bytes().to_vec(),
+        "multipart/mixed;boundary=SporeDefaultBoundary",
+        None,
+        0,
+    );
     let tx = context.complete_tx(tx);
     context
         .verify_tx(&tx, MAX_CYCLES)
@@ -598,6 +629,54 @@ fn test_spore_multipart_mint_failure02() {
         .expect_err("test multipart failure 02");
 }
 
+#[test]
+fn test_spore_multipart_mint_failure03() {
+    // no close delimiter
+    let content = MULTIPART_CONTENT.replace("--SporeDefaultBoundary--", "");
+    let (mut context, tx) = simple_build_context(
+        content.as_bytes().to_vec(),
+        "multipart/mixed;boundary=SporeDefaultBoundary",
+        None,
+        0,
+    );
+    let tx = context.complete_tx(tx);
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test multipart failure 03");
+}
+
+#[test]
+fn test_spore_multipart_mint_failure04() {
+    // a part without content-type
+    let content = MULTIPART_CONTENT.replace("Content-Type: text/plain\r\n", "");
+    let (mut context, tx) = simple_build_context(
+        content.as_bytes().to_vec(),
+        "multipart/mixed;boundary=SporeDefaultBoundary",
+        None,
+        0,
+    );
+    let tx = context.complete_tx(tx);
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test multipart failure 04");
+}
+
+#[test]
+fn test_spore_multipart_mint_failure05() {
+    let part = "--b\r\nContent-Type: text/plain\r\n\r\nSpore\r\n";
+    let content = part.repeat(MAX_MULTIPART_PARTS + 1) + "--b--";
+    let (mut context, tx) = simple_build_context(
+        content.as_bytes().to_vec(),
+        "multipart/mixed;boundary=b",
+        None,
+        0,
+    );
+    let tx = context.complete_tx(tx);
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test multipart failure 05");
+}
+
 #[test]
 fn test_simple_with_cluster() {
     let (mut context, tx) = build_simple_create_context_with_cluster(
@@ -630,9 +709,7 @@ fn test_read_file() {
     use std::fs::File;
     use std::io::BufReader;
     use std::io::Read;
-    let f = File::open("res/test.jpg");
-    if f.is_ok() {
-        let f = f.unwrap();
+    if let Ok(f) = File::open("res/test.jpg") {
         let mut reader = BufReader::new(f);
         let mut buffer = Vec::new();
         reader.read_to_end(&mut buffer).expect("Error read to end");
@@ -691,7 +768,7 @@ fn test_destroy() {
         &mut context,
         &spore_out_point,
         serialized.clone(),
-        spore_type_id.clone(),
+        spore_type_id,
     );
 
     let output = build_normal_output(&mut context, capacity);
@@ -718,12 +795,7 @@ fn test_destroy_immortal() {
     // always success lock
     let (spore_out_point, spore_script_dep) = build_spore_materials(&mut context);
     let spore_type_id = build_script_args(&build_normal_input(&mut context, capacity), 0);
-    let spore_input = build_spore_input(
-        &mut context,
-        &spore_out_point,
-        serialized.clone(),
-        spore_type_id.clone(),
-    );
+    let spore_input = build_spore_input(&mut context, &spore_out_point, serialized, spore_type_id);
 
     let output = build_normal_output(&mut context, capacity);
     let tx = build_simple_tx(
@@ -740,6 +812,60 @@ fn test_destroy_immortal() {
         .expect_err("try destroy immortal");
 }
 
+#[test]
+fn test_destroy_uppercase_immortal() {
+    // only `immortal=true` as written made a Spore immortal before content-types were checked
+    let serialized = build_serialized_spore("Hello Spore!", "plain/text;IMMORTAL=true");
+    let capacity = serialized.total_size() as u64;
+    let mut context = Context::default();
+
+    let (spore_out_point, spore_script_dep) = build_spore_materials(&mut context);
+    let spore_type_id = build_script_args(&build_normal_input(&mut context, capacity), 0);
+    let spore_input = build_spore_input(&mut context, &spore_out_point, serialized, spore_type_id);
+
+    let output = build_normal_output(&mut context, capacity);
+    let tx = build_simple_tx(
+        vec![spore_input],
+        vec![output],
+        vec![spore_script_dep],
+        vec![packed::Bytes::default()],
+    );
+
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect("try destroy uppercase immortal");
+}
+
+#[test]
+fn test_legacy_content_type_transfer() {
+    // minted before content-types were checked, the subtype isn't a valid token
+    let input_data = build_serialized_spore("Hello Spore!", "image/svg xml;immortal=true");
+    let output_data = input_data.clone();
+    let (mut context, tx) = build_transfer_context(input_data, output_data);
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect("test legacy content type transfer");
+}
+
+#[test]
+fn test_legacy_content_type_mint_failure() {
+    let (mut context, tx) = simple_build_context(
+        "Hello Spore!".as_bytes().to_vec(),
+        "image/svg xml;immortal=true",
+        None,
+        0,
+    );
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test legacy content type mint failure");
+}
+
 #[test]
 fn test_destroy_cluster() {
     let cluster = ClusterData::new_builder()
@@ -758,9 +884,9 @@ fn test_destroy_cluster() {
         .out_point(cluster_out_point.clone())
         .build();
     let cluster_type_id = build_script_args(&build_normal_input(&mut context, capacity), 0);
-    let type_ = build_spore_type_script(&mut context, &cluster_out_point, cluster_type_id.clone());
+    let type_ = build_spore_type_script(&mut context, &cluster_out_point, cluster_type_id);
 
-    let cluster_input = build_cluster_input(&mut context, cluster, type_.clone());
+    let cluster_input = build_cluster_input(&mut context, cluster, type_);
 
     let output_cell = build_normal_output(&mut context, capacity);
 
@@ -801,10 +927,8 @@ fn test_extension_1() {
     // always success lock
     let lua_lib_bin: Bytes = Loader::default().load_binary("libckblua.so");
     let lua_lib_out_point = context.deploy_cell(lua_lib_bin.clone());
-    let code_hash = CellOutput::calc_data_hash(&lua_lib_bin.clone());
-    let lua_lib_dep = CellDep::new_builder()
-        .out_point(lua_lib_out_point.clone())
-        .build();
+    let code_hash = CellOutput::calc_data_hash(&lua_lib_bin);
+    let lua_lib_dep = CellDep::new_builder().out_point(lua_lib_out_point).build();
 
     println!("lua lib hash: {}", encode(code_hash.as_slice()));
 
@@ -833,10 +957,10 @@ fn test_extension_1() {
     let type_ = build_spore_type_script(
         &mut context,
         &spore_extension_out_point,
-        spore_extension_type_id.clone(),
+        spore_extension_type_id,
     );
 
-    let spore_out_cell = build_output_cell_with_type_id(&mut context, capacity, type_.clone());
+    let spore_out_cell = build_output_cell_with_type_id(&mut context, capacity, type_);
 
     let tx = TransactionBuilder::default()
         .input(input_cell)
@@ -848,6 +972,2123 @@ fn test_extension_1() {
     let tx = context.complete_tx(tx);
 
     context
-        .verify_tx(&tx, MAX_CYCLES)
+        .verify_tx(&tx, MAX_LUA_CYCLES)
         .expect("test spore lua extension");
 }
+
+#[test]
+fn test_spore_batch_mint_with_cluster() {
+    let (mut context, tx) =
+        build_batch_mint_with_cluster(vec!["Hello Spore 1!", "Hello Spore 2!", "Hello Spore 3!"]);
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect("test spore batch mint with cluster");
+}
+
+#[test]
+fn test_spore_batch_mint_with_cluster_failure() {
+    // the last Spore is verified by the first one in batch mode
+    let (mut context, tx) =
+        build_batch_mint_with_cluster(vec!["Hello Spore 1!", "Hello Spore 2!", ""]);
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test spore batch mint with cluster failure");
+}
+
+#[test]
+fn test_spore_batch_mint_cycles() {
+    // minting five Spores at once costs less than minting them one by one
+    let contents = vec![
+        "Hello Spore 1!",
+        "Hello Spore 2!",
+        "Hello Spore 3!",
+        "Hello Spore 4!",
+        "Hello Spore 5!",
+    ];
+    let cycles = |contents: Vec<&str>| {
+        let (mut context, tx) = build_batch_mint_with_cluster(contents);
+        let tx = context.complete_tx(tx);
+        context
+            .verify_tx(&tx, u64::MAX)
+            .expect("test spore batch mint cycles")
+    };
+
+    let batch = cycles(contents.clone());
+    let singles: u64 = contents
+        .into_iter()
+        .map(|content| cycles(vec![content]))
+        .sum();
+    assert!(batch < singles);
+
+    // only the first Spore verifies the batch, so the cycles per Spore stay flat as it grows
+    let single = cycles(vec!["Hello Spore!"]);
+    let hundred = cycles(vec!["Hello Spore!"; 100]);
+    assert!(hundred / 100 <= single);
+}
+
+#[test]
+fn test_spore_mutable_content_transfer() {
+    let input_data = build_serialized_spore("Level 1", "plain/text;mutable=content");
+    let output_data = build_serialized_spore("Level 2", "plain/text;mutable=content");
+    let (mut context, tx) = build_transfer_context(input_data, output_data);
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect("test spore mutable content transfer");
+}
+
+#[test]
+fn test_spore_mutable_content_type_failure() {
+    let input_data = build_serialized_spore("Level 1", "plain/text;mutable=content");
+    let output_data = build_serialized_spore("Level 2", "plain/html;mutable=content");
+    let (mut context, tx) = build_transfer_context(input_data, output_data);
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test spore mutable content type failure");
+}
+
+#[test]
+fn test_spore_immutable_content_failure() {
+    let input_data = build_serialized_spore("Level 1", "plain/text");
+    let output_data = build_serialized_spore("Level 2", "plain/text");
+    let (mut context, tx) = build_transfer_context(input_data, output_data);
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test spore immutable content failure");
+}
+
+#[test]
+fn test_spore_unknown_mutable_mint_failure() {
+    let (mut context, tx) = simple_build_context(
+        "Level 1".as_bytes().to_vec(),
+        "plain/text;mutable=everything",
+        None,
+        0,
+    );
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test spore unknown mutable mint failure");
+}
+
+#[test]
+fn test_spore_unknown_mutable_transfer() {
+    // minted before `mutable` was checked, the Spore can still move but its content can't change
+    let input_data = build_serialized_spore("Level 1", "plain/text;mutable=everything");
+    let (mut context, tx) = build_transfer_context(input_data.clone(), input_data.clone());
+    let tx = context.complete_tx(tx);
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect("test spore unknown mutable transfer");
+
+    let output_data = build_serialized_spore("Level 2", "plain/text;mutable=everything");
+    let (mut context, tx) = build_transfer_context(input_data, output_data);
+    let tx = context.complete_tx(tx);
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test spore unknown mutable content failure");
+}
+
+#[test]
+fn test_close_cluster() {
+    let cluster = ClusterData::new_builder()
+        .name("Spore Cluster".as_bytes().into())
+        .description("Test Cluster".as_bytes().into())
+        .build();
+    let closed_cluster = build_cluster_data_v2(true);
+    let (mut context, tx) =
+        build_cluster_update(cluster.as_slice(), Some(closed_cluster.as_slice()));
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect("test close cluster");
+}
+
+#[test]
+fn test_reopen_cluster_failure() {
+    let closed_cluster = build_cluster_data_v2(true);
+    let open_cluster = build_cluster_data_v2(false);
+    let (mut context, tx) =
+        build_cluster_update(closed_cluster.as_slice(), Some(open_cluster.as_slice()));
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test reopen cluster failure");
+}
+
+#[test]
+fn test_destroy_closed_cluster() {
+    let closed_cluster = build_cluster_data_v2(true);
+    let (mut context, tx) = build_cluster_update(closed_cluster.as_slice(), None);
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect("test destroy closed cluster");
+}
+
+#[test]
+fn test_melt_to_cluster_without_cluster_failure() {
+    // the cluster cell is left out, so there is no cluster owner to refund
+    let serialized = build_serialized_spore_internal(
+        "Hello Spore!".as_bytes().to_vec(),
+        "plain/text;melt-to=cluster;melt-share=50",
+        Some([1u8; 32].to_vec()),
+    );
+    let capacity = serialized.total_size() as u64;
+    let mut context = Context::default();
+
+    let (spore_out_point, spore_script_dep) = build_spore_materials(&mut context);
+    let spore_type_id = build_script_args(&build_normal_input(&mut context, capacity), 0);
+    let spore_input = build_spore_input(&mut context, &spore_out_point, serialized, spore_type_id);
+
+    let output = build_normal_output(&mut context, capacity);
+    let tx = build_simple_tx(
+        vec![spore_input],
+        vec![output],
+        vec![spore_script_dep],
+        vec![packed::Bytes::default()],
+    );
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test melt to cluster without cluster failure");
+}
+
+#[test]
+fn test_spore_mint_with_closed_cluster_failure() {
+    let mut context = Context::default();
+    let cluster = build_cluster_cell(&mut context, build_cluster_data_v2(true).as_slice());
+    let (spore_out_point, spore_script_dep) = build_spore_materials(&mut context);
+    let input_cell = build_normal_input(&mut context, 1000);
+    let (spore_out_cell, spore_data) =
+        build_cluster_spore_output(&mut context, &spore_out_point, &input_cell, 0, &cluster.id);
+
+    let tx = build_simple_tx(
+        vec![input_cell],
+        vec![spore_out_cell],
+        vec![cluster.script_dep, spore_script_dep, cluster.dep],
+        vec![spore_data],
+    );
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test spore mint with closed cluster failure");
+}
+
+#[test]
+fn test_update_cluster_description() {
+    let cluster = ClusterData::new_builder()
+        .name("Spore Cluster".as_bytes().into())
+        .description("Test Cluster".as_bytes().into())
+        .build();
+    let updated_cluster = cluster
+        .clone()
+        .as_builder()
+        .description("Updated Test Cluster".as_bytes().into())
+        .build();
+    let (mut context, tx) =
+        build_cluster_update(cluster.as_slice(), Some(updated_cluster.as_slice()));
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect("test update cluster description");
+}
+
+#[test]
+fn test_update_cluster_name_failure() {
+    let cluster = build_cluster_data_v2(false);
+    let renamed_cluster = cluster
+        .clone()
+        .as_builder()
+        .name("Renamed Spore Cluster".as_bytes().into())
+        .build();
+    let (mut context, tx) =
+        build_cluster_update(cluster.as_slice(), Some(renamed_cluster.as_slice()));
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test update cluster name failure");
+}
+
+#[test]
+fn test_update_cluster_mutant_failure() {
+    let cluster = build_cluster_data_v2(false);
+    let mutant_cluster = cluster
+        .clone()
+        .as_builder()
+        .mutant_id(
+            BytesOpt::new_builder()
+                .set(Some([1u8; 32].as_slice().into()))
+                .build(),
+        )
+        .build();
+    let (mut context, tx) =
+        build_cluster_update(cluster.as_slice(), Some(mutant_cluster.as_slice()));
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test update cluster mutant failure");
+}
+
+#[test]
+fn test_spore_mint_with_limited_cluster() {
+    let (mut context, tx) = build_limited_cluster_mint(2, 1, 2);
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect("test spore mint with limited cluster");
+}
+
+#[test]
+fn test_spore_mint_with_limited_cluster_supply_failure() {
+    let (mut context, tx) = build_limited_cluster_mint(2, 2, 3);
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test spore mint with limited cluster supply failure");
+}
+
+#[test]
+fn test_spore_mint_through_two_references_with_limited_cluster() {
+    // one Spore refers to the Spore code by data hash and another by type, both are counted
+    let mint = |output_minted: u64| {
+        let (mut context, tx) = build_limited_cluster_mint(2, 0, output_minted);
+        let cluster_id = tx
+            .outputs()
+            .get(0)
+            .unwrap()
+            .type_()
+            .to_opt()
+            .unwrap()
+            .args();
+        let tx = append_spore_by_type(&mut context, tx, &cluster_id.raw_data());
+        let tx = context.complete_tx(tx);
+        context.verify_tx(&tx, MAX_CYCLES)
+    };
+
+    mint(1).expect_err("test spore mint through two references counted once");
+    mint(2).expect("test spore mint through two references with limited cluster");
+}
+
+#[test]
+fn test_spore_mint_with_limited_cluster_counter_failure() {
+    let (mut context, tx) = build_limited_cluster_mint(2, 0, 0);
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test spore mint with limited cluster counter failure");
+}
+
+#[test]
+fn test_cluster_agent_with_legacy_payment() {
+    // 10^4 shannons, overpaying included, which legacy proxies used to refuse
+    for paid in [10_000, 20_000] {
+        let (mut context, tx) =
+            build_cluster_agent_with_payment(PaymentMethod::PowerOfTen, 4, paid);
+        let tx = context.complete_tx(tx);
+
+        context
+            .verify_tx(&tx, MAX_CYCLES)
+            .expect("test cluster agent with legacy payment");
+    }
+    // only the first byte after the cluster ID is read
+    let (mut context, tx) =
+        build_cluster_agent_with_payment(PaymentMethod::PowerOfTenPadded, 4, 10_000);
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect("test cluster agent with padded legacy payment");
+}
+
+#[test]
+fn test_cluster_agent_with_legacy_payment_failure() {
+    // legacy proxies used to accept paying less
+    for method in [PaymentMethod::PowerOfTen, PaymentMethod::PowerOfTenPadded] {
+        let (mut context, tx) = build_cluster_agent_with_payment(method, 4, 9_999);
+        let tx = context.complete_tx(tx);
+
+        context
+            .verify_tx(&tx, MAX_CYCLES)
+            .expect_err("test cluster agent with legacy payment failure");
+    }
+}
+
+#[test]
+fn test_cluster_agent_with_exact_payment() {
+    let (mut context, tx) = build_cluster_agent_with_payment(PaymentMethod::Exact, 5000, 5000);
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect("test cluster agent with exact payment");
+}
+
+#[test]
+fn test_cluster_agent_with_exact_payment_failure() {
+    let (mut context, tx) = build_cluster_agent_with_payment(PaymentMethod::Exact, 5000, 4999);
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test cluster agent with exact payment failure");
+}
+
+#[test]
+fn test_cluster_agent_with_xudt_payment() {
+    let (mut context, tx) = build_cluster_agent_with_payment(PaymentMethod::Xudt, 100, 100);
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect("test cluster agent with xudt payment");
+}
+
+#[test]
+fn test_cluster_agent_with_xudt_payment_failure() {
+    let (mut context, tx) = build_cluster_agent_with_payment(PaymentMethod::Xudt, 100, 99);
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test cluster agent with xudt payment failure");
+}
+
+#[test]
+fn test_cluster_agent_with_legacy_data_failure() {
+    // only existing agents keep the proxy type hash alone, which can't be revoked
+    let (mut context, tx) = build_cluster_agent_with_payment(PaymentMethod::Exact, 5000, 5000);
+    let outputs_data = tx.outputs_data();
+    let legacy_data = outputs_data.get(0).unwrap().raw_data().slice(..32);
+    let tx = tx
+        .as_advanced_builder()
+        .set_outputs_data(vec![legacy_data.pack(), outputs_data.get(1).unwrap()])
+        .build();
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test cluster agent with legacy data failure");
+}
+
+#[test]
+fn test_cluster_agent_with_paid_limits_failure() {
+    // the expiry and quota limit the agent for the cluster owner, not for whoever pays for it
+    let (mut context, tx) = build_cluster_agent_with_payment(PaymentMethod::Exact, 5000, 5000);
+    let outputs_data = tx.outputs_data();
+    let proxy_type_hash = outputs_data.get(0).unwrap().raw_data().slice(..32);
+    let expiry = 100u64.to_le_bytes();
+    let quota = [&[0; 8][..], &5u64.to_le_bytes()].concat();
+    for limits in [&expiry[..], &quota] {
+        let agent_data = Bytes::from([&proxy_type_hash[..], limits].concat());
+        let tx = tx
+            .as_advanced_builder()
+            .set_outputs_data(vec![agent_data.pack(), outputs_data.get(1).unwrap()])
+            .build();
+        let tx = context.complete_tx(tx);
+
+        context
+            .verify_tx(&tx, MAX_CYCLES)
+            .expect_err("test cluster agent with paid limits failure");
+    }
+}
+
+#[test]
+fn test_spore_mint_with_cluster_agent() {
+    let (mut context, tx) = build_spore_mint_with_agent(100, 99, true, None);
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect("test spore mint with cluster agent");
+}
+
+#[test]
+fn test_spore_mint_with_expired_cluster_agent_failure() {
+    let (mut context, tx) = build_spore_mint_with_agent(100, 100, true, None);
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test spore mint with expired cluster agent failure");
+}
+
+#[test]
+fn test_spore_mint_with_expired_cluster_agent_bypass() {
+    // the chain is past the expiry, but the minter only shows the block the agent was created in
+    let (mut context, tx) = build_spore_mint_with_agent(100, 99, true, None);
+    let latest_header = HeaderBuilder::default()
+        .number(200u64.pack())
+        .epoch(EpochNumberWithFraction::new(2, 0, 1).pack())
+        .build();
+    context.insert_header(latest_header.clone());
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect("test spore mint with expired cluster agent bypass");
+
+    // the agent is only refused if the transaction shows a block past the expiry
+    let tx = tx
+        .as_advanced_builder()
+        .header_dep(latest_header.hash())
+        .build();
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test spore mint with expired cluster agent shown");
+}
+
+#[test]
+fn test_spore_mint_with_timestamp_expired_cluster_agent() {
+    // the agent expires at 1000 seconds, header timestamps are in milliseconds
+    let expiry = 0x4000_0000_0000_0000 | 1000;
+    for (timestamp, expired) in [(999_999u64, false), (1_000_000, true)] {
+        let (mut context, tx) = build_spore_mint_with_agent(expiry, 0, true, None);
+        let latest_header = HeaderBuilder::default().timestamp(timestamp.pack()).build();
+        context.insert_header(latest_header.clone());
+        let tx = tx
+            .as_advanced_builder()
+            .header_dep(latest_header.hash())
+            .build();
+        let tx = context.complete_tx(tx);
+
+        let result = context.verify_tx(&tx, MAX_CYCLES);
+        assert_eq!(result.is_err(), expired, "header timestamp {timestamp}");
+    }
+}
+
+#[test]
+fn test_spore_mint_with_revoked_cluster_agent_failure() {
+    let (mut context, tx) = build_spore_mint_with_agent(100, 99, false, None);
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test spore mint with revoked cluster agent failure");
+}
+
+#[test]
+fn test_spore_mint_with_legacy_cluster_agent_without_proxy() {
+    let (mut context, tx) = build_spore_mint_with_agent(0, 0, false, None);
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect("test spore mint with legacy cluster agent without proxy");
+}
+
+#[test]
+fn test_spore_mint_with_cluster_agent_quota() {
+    let (mut context, tx) = build_spore_mint_with_agent(0, 0, true, Some((5, 4)));
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect("test spore mint with cluster agent quota");
+}
+
+#[test]
+fn test_spore_mint_through_two_references_with_cluster_agent_quota() {
+    // one Spore refers to the Spore code by data hash and another by type, both use the quota
+    let mint = |output_quota: u64| {
+        let (mut context, tx) = build_spore_mint_with_agent(0, 0, true, Some((5, output_quota)));
+        let cluster_id = tx
+            .outputs()
+            .get(0)
+            .unwrap()
+            .type_()
+            .to_opt()
+            .unwrap()
+            .args();
+        let tx = append_spore_by_type(&mut context, tx, &cluster_id.raw_data());
+        let tx = context.complete_tx(tx);
+        context.verify_tx(&tx, MAX_CYCLES)
+    };
+
+    mint(4).expect_err("test spore mint through two references using the quota once");
+    mint(3).expect("test spore mint through two references with cluster agent quota");
+}
+
+#[test]
+fn test_spore_mint_with_cluster_agent_quota_exceeded_failure() {
+    let (mut context, tx) = build_spore_mint_with_agent(0, 0, true, Some((0, 0)));
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test spore mint with cluster agent quota exceeded failure");
+}
+
+#[test]
+fn test_spore_mint_with_cluster_agent_quota_mismatch_failure() {
+    let (mut context, tx) = build_spore_mint_with_agent(0, 0, true, Some((5, 5)));
+    let tx = context.complete_tx(tx);
+
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test spore mint with cluster agent quota mismatch failure");
+}
+
+#[test]
+fn test_sdk_cluster_spore_lifecycle() {
+    let mut context = Context::default();
+    let lock = sdk_lock(&mut context);
+    let cluster_script = sdk_script_info(&mut context, "cluster");
+    let spore_script = sdk_script_info(&mut context, "spore");
+
+    let funding = sdk_funding(&mut context, &lock);
+    let tx = ClusterCreate::new(
+        cluster_script.clone(),
+        lock.clone(),
+        "Spore Cluster",
+        "Test",
+    )
+    .max_supply(2)
+    .build(funding)
+    .expect("build cluster");
+    let cluster = sdk_commit(&mut context, tx).remove(0);
+
+    let funding = sdk_funding(&mut context, &lock);
+    let tx = SporeMint::new(
+        spore_script.clone(),
+        lock.clone(),
+        "plain/text",
+        b"Hello Spore!",
+    )
+    .cluster(cluster_script.clone(), cluster)
+    .build(funding)
+    .expect("build spore");
+    let mut outputs = sdk_commit(&mut context, tx);
+    let spore = outputs.remove(1);
+    let cluster = outputs.remove(0);
+
+    let funding = sdk_funding(&mut context, &lock);
+    let tx = ClusterUpdate::new(cluster_script, cluster)
+        .description("Updated")
+        .close()
+        .build(funding)
+        .expect("build cluster update");
+    sdk_commit(&mut context, tx);
+
+    let funding = sdk_funding(&mut context, &lock);
+    let tx = SporeTransfer::new(spore_script.clone(), spore, lock.clone())
+        .build(funding)
+        .expect("build spore transfer");
+    let spore = sdk_commit(&mut context, tx).remove(0);
+
+    let funding = sdk_funding(&mut context, &lock);
+    let tx = SporeMelt::new(spore_script, spore)
+        .build(funding)
+        .expect("build spore melt");
+    sdk_commit(&mut context, tx);
+}
+
+#[test]
+fn test_sdk_cluster_content_type_allowlist() {
+    let mut context = Context::default();
+    let lock = sdk_lock(&mut context);
+    let cluster_script = sdk_script_info(&mut context, "cluster");
+    let spore_script = sdk_script_info(&mut context, "spore");
+    let validator = build_validator(&mut context);
+
+    // a pattern with params can't be allowed
+    let funding = sdk_funding(&mut context, &lock);
+    let tx = ClusterCreate::new(
+        cluster_script.clone(),
+        lock.clone(),
+        "Spore Cluster",
+        "Test",
+    )
+    .allowed_content_types(&["image/png;immortal=true"])
+    .build(funding)
+    .expect("build cluster");
+    let tx = context.complete_tx(tx);
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test invalid content-type pattern");
+
+    let funding = sdk_funding(&mut context, &lock);
+    let tx = ClusterCreate::new(
+        cluster_script.clone(),
+        lock.clone(),
+        "Spore Cluster",
+        "Test",
+    )
+    .allowed_content_types(&["image/png", "Text/*"])
+    .build(funding)
+    .expect("build cluster");
+    let cluster = sdk_commit(&mut context, tx).remove(0);
+
+    for content_type in ["image/png;immortal=true", "text/plain", "text/markdown"] {
+        let funding = sdk_funding(&mut context, &lock);
+        let tx = SporeMint::new(
+            spore_script.clone(),
+            lock.clone(),
+            content_type,
+            b"Hello Spore!",
+        )
+        .cluster(cluster_script.clone(), cluster.clone())
+        .build(funding)
+        .expect("build spore");
+        sdk_commit(&mut context, tx);
+    }
+
+    for content_type in ["image/jpeg", "application/json"] {
+        let funding = sdk_funding(&mut context, &lock);
+        let tx = SporeMint::new(
+            spore_script.clone(),
+            lock.clone(),
+            content_type,
+            b"Hello Spore!",
+        )
+        .cluster(cluster_script.clone(), cluster.clone())
+        .build(funding)
+        .expect("build spore");
+        assert_eq!(
+            validator.validate(&sdk_resolve(&context, &tx)),
+            vec![Diagnostic::new(
+                Rule::ContentTypeNotAllowed,
+                CellSource::Output,
+                1,
+                "content_type"
+            )]
+        );
+        let tx = context.complete_tx(tx);
+        context
+            .verify_tx(&tx, MAX_CYCLES)
+            .expect_err("test content-type not allowed");
+    }
+}
+
+#[test]
+fn test_sdk_spore_mint_with_cluster_agent() {
+    let mut context = Context::default();
+    let always_success = context.deploy_cell(ALWAYS_SUCCESS.clone());
+    let lock = sdk_lock(&mut context);
+    let proxy_lock = context
+        .build_script(&always_success, Bytes::from(vec![1]))
+        .expect("proxy lock");
+    let cluster_script = sdk_script_info(&mut context, "cluster");
+    let proxy_script = sdk_script_info(&mut context, "cluster_proxy");
+    let agent_script = sdk_script_info(&mut context, "cluster_agent");
+    let spore_script = sdk_script_info(&mut context, "spore");
+
+    let funding = sdk_funding(&mut context, &lock);
+    let tx = ClusterCreate::new(
+        cluster_script.clone(),
+        lock.clone(),
+        "Spore Cluster",
+        "Test",
+    )
+    .build(funding)
+    .expect("build cluster");
+    let cluster = sdk_commit(&mut context, tx).remove(0);
+
+    let funding = sdk_funding(&mut context, &lock);
+    let tx = ClusterProxyCreate::new(
+        proxy_script.clone(),
+        cluster_script.clone(),
+        cluster.clone(),
+        proxy_lock,
+    )
+    .payment(Payment::Exact(500_000_000_000))
+    .build(funding)
+    .expect("build cluster proxy");
+    let proxy = sdk_commit(&mut context, tx).remove(1);
+
+    let funding = sdk_funding(&mut context, &lock);
+    let tx = ClusterAgentCreate::new(agent_script.clone(), proxy_script, proxy, lock.clone())
+        // only the cluster owner, consuming the proxy, can give the agent a quota
+        .quota(1)
+        .consume_proxy()
+        .build(funding)
+        .expect("build cluster agent");
+    let mut cells = sdk_commit(&mut context, tx);
+    let agent = cells.remove(1);
+    let proxy = cells.remove(0);
+
+    let funding = sdk_funding(&mut context, &lock);
+    let tx = SporeMint::new(
+        spore_script.clone(),
+        lock.clone(),
+        "plain/text",
+        b"Hello Spore!",
+    )
+    .cluster(cluster_script.clone(), cluster.clone())
+    .agent(AgentRef {
+        agent_script: agent_script.clone(),
+        agent,
+        proxy: proxy.clone(),
+    })
+    .build(funding)
+    .expect("build spore");
+    let agent = sdk_commit(&mut context, tx).remove(0);
+
+    // the agent without its cluster, then the quota used up with the agent given first
+    let agent = AgentRef {
+        agent_script,
+        agent,
+        proxy,
+    };
+    let funding = sdk_funding(&mut context, &lock);
+    let mint = SporeMint::new(spore_script, lock, "plain/text", b"Hello Spore!").agent(agent);
+    assert_eq!(
+        mint.clone().build(funding.clone()).unwrap_err(),
+        SdkError::MissingCluster
+    );
+    let result = mint.cluster(cluster_script, cluster).build(funding);
+    assert_eq!(result.unwrap_err(), SdkError::AgentQuotaExceeded);
+}
+
+#[test]
+fn test_sdk_spore_content_ref() {
+    let mut context = Context::default();
+    let lock = sdk_lock(&mut context);
+    let spore_script = sdk_script_info(&mut context, "spore");
+
+    let path = std::env::temp_dir().join("spore_content_ref_media");
+    std::fs::write(&path, vec![0x5a; 200_000]).expect("write media");
+    let digest = file_digest(&path).expect("media digest");
+    let content = content_ref(&digest, "https://example.com/media.mp4");
+
+    let funding = sdk_funding(&mut context, &lock);
+    let tx = SporeMint::new(
+        spore_script.clone(),
+        lock.clone(),
+        CONTENT_REF_CONTENT_TYPE,
+        &content,
+    )
+    .build(funding)
+    .expect("build spore");
+    let spore = sdk_commit(&mut context, tx).remove(0);
+    assert_eq!(verify_spore_content(&spore, &path), Ok(()));
+
+    std::fs::write(&path, b"tampered").expect("write media");
+    assert_eq!(
+        verify_spore_content(&spore, &path),
+        Err(SdkError::ContentDigestMismatch)
+    );
+    std::fs::remove_file(&path).expect("remove media");
+    assert_eq!(
+        verify_spore_content(&spore, &path),
+        Err(SdkError::ContentUnreadable(std::io::ErrorKind::NotFound))
+    );
+
+    for (content_type, content) in [
+        ("application/x-spore-ref;hash=sha256", content),
+        (CONTENT_REF_CONTENT_TYPE, digest.to_vec()),
+        (
+            CONTENT_REF_CONTENT_TYPE,
+            content_ref(&digest, "https://example.com/my media.mp4"),
+        ),
+    ] {
+        let funding = sdk_funding(&mut context, &lock);
+        let tx = SporeMint::new(spore_script.clone(), lock.clone(), content_type, &content)
+            .build(funding)
+            .expect("build spore");
+        let tx = context.complete_tx(tx);
+        context
+            .verify_tx(&tx, MAX_CYCLES)
+            .expect_err("test invalid content ref");
+    }
+}
+
+#[test]
+fn test_sdk_segmented_spore() {
+    let mut context = Context::default();
+    let lock = sdk_lock(&mut context);
+    let spore_script = sdk_script_info(&mut context, "spore");
+    let segment_script = sdk_script_info(&mut context, "spore_segment");
+    let validator = build_validator(&mut context);
+
+    let content: Vec<u8> = (0..2500u32).map(|i| (i % 251) as u8).collect();
+    let plan = SegmentPlan::new(&content, 1000);
+    assert_eq!(plan.total(), 3);
+
+    // the first two segments in one transaction
+    let funding = sdk_funding(&mut context, &lock);
+    let tx = SegmentCreate::new(segment_script.clone(), lock.clone(), plan.clone(), 0..2)
+        .build(funding)
+        .expect("build segments");
+    assert!(validator.validate(&sdk_resolve(&context, &tx)).is_empty());
+    let mut segments = sdk_commit(&mut context, tx);
+    segments.truncate(2);
+    let segments_id = segments[0].type_args().expect("segments id").to_vec();
+
+    // the last segment completes the content, so every segment has to be referenced
+    let funding = sdk_funding(&mut context, &lock);
+    let tx = SegmentCreate::new(segment_script.clone(), lock.clone(), plan.clone(), 2..3)
+        .segment(segments[1].clone())
+        .build(funding)
+        .expect("build segments");
+    assert_eq!(
+        validator.validate(&sdk_resolve(&context, &tx)),
+        vec![Diagnostic::new(
+            Rule::SegmentNotFound,
+            CellSource::Output,
+            0,
+            "content_digest"
+        )]
+    );
+    let tx = context.complete_tx(tx);
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test incomplete segments");
+
+    let funding = sdk_funding(&mut context, &lock);
+    let tx = SegmentCreate::new(segment_script.clone(), lock.clone(), plan.clone(), 2..3)
+        .segment(segments[1].clone())
+        .segment(segments[0].clone())
+        .build(funding)
+        .expect("build segments");
+    assert!(validator.validate(&sdk_resolve(&context, &tx)).is_empty());
+    segments.push(sdk_commit(&mut context, tx).remove(0));
+
+    let spore_content = plan.spore_content(&segments_id);
+    let funding = sdk_funding(&mut context, &lock);
+    let mint = SporeMint::new(
+        spore_script,
+        lock.clone(),
+        SEGMENTED_CONTENT_TYPE,
+        &spore_content,
+    )
+    .cell_dep(segments[0].cell_dep())
+    .cell_dep(segments[2].cell_dep());
+    let tx = mint.clone().build(funding).expect("build spore");
+    assert_eq!(
+        validator.validate(&sdk_resolve(&context, &tx)),
+        vec![Diagnostic::new(
+            Rule::SegmentNotFound,
+            CellSource::Output,
+            0,
+            "content"
+        )]
+    );
+    let tx = context.complete_tx(tx);
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test spore missing a segment");
+
+    let funding = sdk_funding(&mut context, &lock);
+    let tx = mint
+        .cell_dep(segments[1].cell_dep())
+        .build(funding)
+        .expect("build spore");
+    assert!(validator.validate(&sdk_resolve(&context, &tx)).is_empty());
+    sdk_commit(&mut context, tx);
+
+    // the Spore reads its segments from CellDeps, so they can only be transferred as they are
+    let segment_input = CellInput::new_builder()
+        .previous_output(segments[1].out_point.clone())
+        .build();
+    let melt = build_simple_tx(
+        vec![segment_input.clone()],
+        vec![build_normal_output(&mut context, 1000)],
+        vec![segment_script.cell_dep.clone()],
+        vec![packed::Bytes::default()],
+    );
+    assert_eq!(
+        validator.validate(&sdk_resolve(&context, &melt)),
+        vec![Diagnostic::new(
+            Rule::DestroyImmortalNFT,
+            CellSource::Input,
+            0,
+            "data"
+        )]
+    );
+    let melt = context.complete_tx(melt);
+    context
+        .verify_tx(&melt, MAX_CYCLES)
+        .expect_err("test melt segment");
+    for (data, immutable) in [(&segments[2].data, true), (&segments[1].data, false)] {
+        let tx = build_simple_tx(
+            vec![segment_input.clone()],
+            vec![segments[1].output.clone()],
+            vec![segment_script.cell_dep.clone()],
+            vec![data.pack()],
+        );
+        let tx = context.complete_tx(tx);
+        let result = context.verify_tx(&tx, MAX_CYCLES);
+        assert_eq!(result.is_err(), immutable, "test transfer segment");
+    }
+
+    assert_eq!(
+        assemble_content(SEGMENTED_CONTENT_TYPE.as_bytes(), &spore_content, &segments),
+        Ok(content)
+    );
+    assert_eq!(
+        assemble_content(
+            SEGMENTED_CONTENT_TYPE.as_bytes(),
+            &spore_content,
+            &segments[..2]
+        ),
+        Err(SdkError::InvalidSegments)
+    );
+}
+
+#[test]
+fn test_validator_diagnostics() {
+    let mut context = Context::default();
+    let lock = sdk_lock(&mut context);
+    let cluster_script = sdk_script_info(&mut context, "cluster");
+    let spore_script = sdk_script_info(&mut context, "spore");
+    let validator = build_validator(&mut context);
+
+    let funding = sdk_funding(&mut context, &lock);
+    let tx = ClusterCreate::new(
+        cluster_script.clone(),
+        lock.clone(),
+        "Spore Cluster",
+        "Test",
+    )
+    .max_supply(1)
+    .build(funding)
+    .expect("build cluster");
+    assert!(validator.validate(&sdk_resolve(&context, &tx)).is_empty());
+    let cluster = sdk_commit(&mut context, tx).remove(0);
+
+    let funding = sdk_funding(&mut context, &lock);
+    let tx = SporeMint::new(spore_script.clone(), lock.clone(), "plain/text", b"")
+        .cluster(cluster_script.clone(), cluster.clone())
+        .build(funding)
+        .expect("build spore");
+    let diagnostics = validator.validate(&sdk_resolve(&context, &tx));
+    assert_eq!(
+        diagnostics,
+        vec![Diagnostic::new(
+            Rule::EmptyContent,
+            CellSource::Output,
+            1,
+            "content"
+        )]
+    );
+    let tx = context.complete_tx(tx);
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test validator empty content");
+
+    // renaming the cluster while minting into it
+    let funding = sdk_funding(&mut context, &lock);
+    let tx = SporeMint::new(spore_script, lock, "plain/text", b"Hello Spore!")
+        .cluster(cluster_script, cluster)
+        .build(funding)
+        .expect("build spore");
+    assert!(validator.validate(&sdk_resolve(&context, &tx)).is_empty());
+    let cluster_data =
+        ClusterDataV2::from_compatible_slice(&tx.outputs_data().get(0).unwrap().raw_data())
+            .unwrap()
+            .as_builder()
+            .name("Renamed".as_bytes().into())
+            .build();
+    let tx = tx
+        .as_advanced_builder()
+        .set_outputs_data(
+            tx.outputs_data()
+                .into_iter()
+                .enumerate()
+                .map(|(index, data)| match index {
+                    0 => cluster_data.as_bytes().pack(),
+                    _ => data,
+                })
+                .collect(),
+        )
+        .build();
+    let diagnostics = validator.validate(&sdk_resolve(&context, &tx));
+    assert_eq!(
+        diagnostics,
+        vec![Diagnostic::new(
+            Rule::ModifyClusterPermanentField,
+            CellSource::Output,
+            0,
+            "name"
+        )]
+    );
+    let tx = context.complete_tx(tx);
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test validator modify cluster name");
+}
+
+#[test]
+fn test_sdk_spore_mutants_share_code() {
+    let mut context = Context::default();
+    let always_success = context.deploy_cell(ALWAYS_SUCCESS.clone());
+    let lock = sdk_lock(&mut context);
+    let spore_script = sdk_script_info(&mut context, "spore");
+    let validator = build_validator(&mut context);
+
+    // native mutants, run by the always success binary or by the cluster one, applied by the
+    // type hash of their cells
+    let cluster_bin: Bytes = Loader::default().load_binary("cluster");
+    let cluster_out_point = context.deploy_cell(cluster_bin);
+    let mut mutant = |out_point: &OutPoint, id: u8| {
+        let type_ = context
+            .build_script(out_point, Bytes::from(vec![id; 32]))
+            .expect("mutant type");
+        let mutant_id = encode(type_.calc_script_hash().as_slice());
+        let output = CellOutput::new_builder()
+            .lock(lock.clone())
+            .type_(Some(type_).pack())
+            .capacity(1_000_000_000_000u64.pack())
+            .build();
+        let out_point = context.create_cell(output, Bytes::new());
+        (
+            CellDep::new_builder().out_point(out_point).build(),
+            mutant_id,
+        )
+    };
+    let mutants = [
+        mutant(&always_success, 1),
+        mutant(&always_success, 2),
+        mutant(&cluster_out_point, 3),
+    ];
+    let mutant_deps: Vec<CellDep> = mutants.iter().map(|(dep, _)| dep.clone()).collect();
+
+    let mint = |context: &mut Context, applied: &[usize]| {
+        let mutant_ids: Vec<&str> = applied.iter().map(|&i| mutants[i].1.as_str()).collect();
+        let content_type = format!("text/plain;mutant[]={}", mutant_ids.join(","));
+        let funding = sdk_funding(context, &lock);
+        let tx = SporeMint::new(
+            spore_script.clone(),
+            lock.clone(),
+            &content_type,
+            b"Hello Spore!",
+        )
+        .build(funding)
+        .expect("build spore");
+        tx.as_advanced_builder()
+            .cell_deps(mutant_deps.clone())
+            .build()
+    };
+
+    let tx = mint(&mut context, &[0, 1]);
+    assert!(validator.validate(&sdk_resolve(&context, &tx)).is_empty());
+    sdk_commit(&mut context, tx);
+
+    let tx = mint(&mut context, &[0, 2]);
+    assert_eq!(
+        validator.validate(&sdk_resolve(&context, &tx)),
+        vec![Diagnostic::new(
+            Rule::MutantCodeMismatch,
+            CellSource::Output,
+            0,
+            "content_type"
+        )]
+    );
+    let tx = context.complete_tx(tx);
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test mutants run by different code");
+}
+
+#[test]
+fn test_sdk_spoofed_mutant_failure() {
+    let mut context = Context::default();
+    let always_success = context.deploy_cell(ALWAYS_SUCCESS.clone());
+    let lock = sdk_lock(&mut context);
+    let spore_script = sdk_script_info(&mut context, "spore");
+    let validator = build_validator(&mut context);
+
+    // a cell of any other code with the ID of a Lua mutant in its type args
+    let spoof_type = context
+        .build_script(&always_success, Bytes::from(vec![1u8; 32]))
+        .expect("spoofed mutant");
+    let spoof_output = CellOutput::new_builder()
+        .lock(lock.clone())
+        .type_(Some(spoof_type).pack())
+        .capacity(1_000_000_000_000u64.pack())
+        .build();
+    let spoof_out_point = context.create_cell(spoof_output.clone(), Bytes::new());
+    let spoof = LiveCell::new(spoof_out_point, spoof_output, Bytes::new());
+
+    let funding = sdk_funding(&mut context, &lock);
+    let content_type = format!("text/plain;mutant[]={}", encode([1u8; 32]));
+    let tx = SporeMint::new(spore_script, lock, &content_type, b"Hello Spore!")
+        .mutant(spoof)
+        .build(funding)
+        .expect("build spore");
+    assert_eq!(
+        validator.validate(&sdk_resolve(&context, &tx)),
+        vec![Diagnostic::new(
+            Rule::ExtensionCellNotInDep,
+            CellSource::Output,
+            0,
+            "content_type"
+        )]
+    );
+    let tx = context.complete_tx(tx);
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test spoofed mutant");
+}
+
+#[test]
+fn test_sdk_spore_pinned_mutant_code() {
+    let mut context = Context::default();
+    let always_success = context.deploy_cell(ALWAYS_SUCCESS.clone());
+    let lock = sdk_lock(&mut context);
+    let spore_script = sdk_script_info(&mut context, "spore");
+    let validator = build_validator(&mut context);
+
+    // an upgradable native mutant already updated to v2, and a cell keeping its v1
+    let authority = lock.calc_script_hash();
+    let mutant_args = [&[1u8; 32], authority.as_slice()].concat();
+    let mutant_type = context
+        .build_script(&always_success, Bytes::from(mutant_args))
+        .expect("mutant type");
+    let mutant_id = encode(mutant_type.calc_script_hash().as_slice());
+    let mutant_output = CellOutput::new_builder()
+        .lock(lock.clone())
+        .type_(Some(mutant_type).pack())
+        .capacity(1_000_000_000_000u64.pack())
+        .build();
+    let mutant = context.create_cell(mutant_output, Bytes::from("v2"));
+    let code_output = CellOutput::new_builder()
+        .lock(lock.clone())
+        .capacity(1_000_000_000_000u64.pack())
+        .build();
+    let code_v1 = context.create_cell(code_output, Bytes::from("v1"));
+    let dep = |out_point: &OutPoint| CellDep::new_builder().out_point(out_point.clone()).build();
+
+    let content_type = format!(
+        "text/plain;mutant[]={};mutant-code[]={}",
+        mutant_id,
+        encode(CellOutput::calc_data_hash(b"v1").as_slice())
+    );
+    let mint = |context: &mut Context, cell_deps: Vec<CellDep>| {
+        let funding = sdk_funding(context, &lock);
+        let tx = SporeMint::new(
+            spore_script.clone(),
+            lock.clone(),
+            &content_type,
+            b"Hello Spore!",
+        )
+        .build(funding)
+        .expect("build spore");
+        tx.as_advanced_builder().cell_deps(cell_deps).build()
+    };
+
+    let tx = mint(&mut context, vec![dep(&mutant)]);
+    assert_eq!(
+        validator.validate(&sdk_resolve(&context, &tx)),
+        vec![Diagnostic::new(
+            Rule::PinnedMutantCodeNotInDep,
+            CellSource::Output,
+            0,
+            "content_type"
+        )]
+    );
+    let tx = context.complete_tx(tx);
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test pinned mutant code not in deps");
+
+    let tx = mint(&mut context, vec![dep(&mutant), dep(&code_v1)]);
+    assert!(validator.validate(&sdk_resolve(&context, &tx)).is_empty());
+    sdk_commit(&mut context, tx);
+}
+
+#[test]
+fn test_sdk_spore_melt_refund() {
+    let mut context = Context::default();
+    let always_success = context.deploy_cell(ALWAYS_SUCCESS.clone());
+    let lock = sdk_lock(&mut context);
+    let refund_lock = context
+        .build_script(&always_success, Bytes::from("refund"))
+        .expect("refund lock");
+    let cluster_script = sdk_script_info(&mut context, "cluster");
+    let spore_script = sdk_script_info(&mut context, "spore");
+    let validator = build_validator(&mut context);
+
+    // refunding to the cluster owner needs a cluster
+    let funding = sdk_funding(&mut context, &lock);
+    let tx = SporeMint::new(
+        spore_script.clone(),
+        lock.clone(),
+        "text/plain;melt-to=cluster",
+        b"Hello Spore!",
+    )
+    .build(funding)
+    .expect("build spore");
+    assert_eq!(
+        validator.validate(&sdk_resolve(&context, &tx)),
+        vec![Diagnostic::new(
+            Rule::InvalidMeltPolicy,
+            CellSource::Output,
+            0,
+            "content_type"
+        )]
+    );
+    let tx = context.complete_tx(tx);
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test melt to cluster without cluster");
+
+    // a cluster without a royalty can be destroyed, leaving nothing to refund
+    let mut clusters = Vec::new();
+    for royalty in [None, Some(1000)] {
+        let funding = sdk_funding(&mut context, &lock);
+        let mut create = ClusterCreate::new(
+            cluster_script.clone(),
+            refund_lock.clone(),
+            "Spore Cluster",
+            "Test",
+        );
+        if let Some(basis_points) = royalty {
+            create = create.royalty(Royalty {
+                recipient: refund_lock.calc_script_hash().unpack().into(),
+                basis_points,
+                minimum: 0,
+            });
+        }
+        let tx = create.build(funding).expect("build cluster");
+        clusters.push(sdk_commit(&mut context, tx).remove(0));
+    }
+    let mint = |context: &mut Context, cluster: &LiveCell, content_type: &str| {
+        let funding = sdk_funding(context, &lock);
+        SporeMint::new(
+            spore_script.clone(),
+            lock.clone(),
+            content_type,
+            b"Hello Spore!",
+        )
+        .cluster(cluster_script.clone(), cluster.clone())
+        .build(funding)
+        .expect("build spore")
+    };
+    let tx = mint(&mut context, &clusters[0], "text/plain;melt-to=cluster");
+    assert_eq!(
+        validator.validate(&sdk_resolve(&context, &tx)),
+        vec![Diagnostic::new(
+            Rule::InvalidMeltPolicy,
+            CellSource::Output,
+            1,
+            "content_type"
+        )]
+    );
+    let tx = context.complete_tx(tx);
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test melt to cluster without royalty");
+
+    let tx = mint(
+        &mut context,
+        &clusters[1],
+        "text/plain;royalty-to=cluster;melt-to=cluster",
+    );
+    assert!(validator.validate(&sdk_resolve(&context, &tx)).is_empty());
+    let spore = sdk_commit(&mut context, tx).remove(1);
+    let funding = sdk_funding(&mut context, &lock);
+    let melt = SporeMelt::new(spore_script.clone(), spore);
+    assert_eq!(
+        melt.clone().build(funding.clone()).err(),
+        Some(SdkError::MissingCluster)
+    );
+    let tx = melt
+        .cluster(clusters[1].clone())
+        .build(funding)
+        .expect("build spore melt");
+    assert!(validator.validate(&sdk_resolve(&context, &tx)).is_empty());
+    sdk_commit(&mut context, tx);
+
+    let content_type = format!(
+        "text/plain;melt-to={};melt-share=50",
+        encode(refund_lock.calc_script_hash().as_slice())
+    );
+    let funding = sdk_funding(&mut context, &lock);
+    let tx = SporeMint::new(
+        spore_script.clone(),
+        lock.clone(),
+        &content_type,
+        b"Hello Spore!",
+    )
+    .build(funding)
+    .expect("build spore");
+    assert!(validator.validate(&sdk_resolve(&context, &tx)).is_empty());
+    let spore = sdk_commit(&mut context, tx).remove(0);
+
+    let funding = sdk_funding(&mut context, &lock);
+    let melt = SporeMelt::new(spore_script, spore.clone());
+    assert_eq!(
+        melt.clone().build(funding.clone()).err(),
+        Some(SdkError::MissingRefundLock)
+    );
+    let tx = melt
+        .refund_lock(refund_lock)
+        .build(funding)
+        .expect("build spore melt");
+    let spore_capacity: u64 = spore.output.capacity().unpack();
+    let refund: u64 = tx.outputs().get(0).unwrap().capacity().unpack();
+    assert_eq!(refund, spore_capacity / 2);
+
+    // one shannon short of the refund
+    let outputs: Vec<CellOutput> = tx
+        .outputs()
+        .into_iter()
+        .enumerate()
+        .map(|(index, output)| match index {
+            0 => output.as_builder().capacity((refund - 1).pack()).build(),
+            _ => output,
+        })
+        .collect();
+    let underpaid = tx.as_advanced_builder().set_outputs(outputs).build();
+    assert_eq!(
+        validator.validate(&sdk_resolve(&context, &underpaid)),
+        vec![Diagnostic::new(
+            Rule::PaymentNotEnough,
+            CellSource::Input,
+            0,
+            "capacity"
+        )]
+    );
+    let underpaid = context.complete_tx(underpaid);
+    context
+        .verify_tx(&underpaid, MAX_CYCLES)
+        .expect_err("test melt refund not enough");
+
+    assert!(validator.validate(&sdk_resolve(&context, &tx)).is_empty());
+    sdk_commit(&mut context, tx);
+}
+
+#[test]
+fn test_sdk_spore_transfer_royalty() {
+    let mut context = Context::default();
+    let always_success = context.deploy_cell(ALWAYS_SUCCESS.clone());
+    let mut lock = |args: &str| {
+        context
+            .build_script(&always_success, Bytes::from(args.to_owned()))
+            .expect("lock")
+    };
+    let (seller, buyer, recipient) = (lock(""), lock("buyer"), lock("royalty"));
+    let cluster_script = sdk_script_info(&mut context, "cluster");
+    let spore_script = sdk_script_info(&mut context, "spore");
+    let validator = build_validator(&mut context);
+
+    // 10% of the price, at least 100 CKB
+    let royalty = Royalty {
+        recipient: recipient.calc_script_hash().unpack().into(),
+        basis_points: 1000,
+        minimum: 10_000_000_000,
+    };
+    let funding = sdk_funding(&mut context, &seller);
+    let tx = ClusterCreate::new(
+        cluster_script.clone(),
+        seller.clone(),
+        "Spore Cluster",
+        "Test",
+    )
+    .royalty(royalty)
+    .build(funding)
+    .expect("build cluster");
+    let cluster = sdk_commit(&mut context, tx).remove(0);
+
+    let mint = |context: &mut Context, cluster: &LiveCell, content_type: &str| {
+        let funding = sdk_funding(context, &seller);
+        SporeMint::new(
+            spore_script.clone(),
+            seller.clone(),
+            content_type,
+            b"Hello Spore!",
+        )
+        .cluster(cluster_script.clone(), cluster.clone())
+        .build(funding)
+        .expect("build spore")
+    };
+    let tx = mint(&mut context, &cluster, "text/plain");
+    assert_eq!(
+        validator.validate(&sdk_resolve(&context, &tx)),
+        vec![Diagnostic::new(
+            Rule::ClusterRequiresRoyalty,
+            CellSource::Output,
+            1,
+            "content_type"
+        )]
+    );
+    let tx = context.complete_tx(tx);
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test spore not following cluster royalty");
+
+    // a cluster without a royalty can be destroyed, leaving nothing to follow
+    let funding = sdk_funding(&mut context, &seller);
+    let tx = ClusterCreate::new(
+        cluster_script.clone(),
+        seller.clone(),
+        "Spore Cluster",
+        "Test",
+    )
+    .build(funding)
+    .expect("build cluster");
+    let free_cluster = sdk_commit(&mut context, tx).remove(0);
+    let tx = mint(&mut context, &free_cluster, "text/plain;royalty-to=cluster");
+    assert_eq!(
+        validator.validate(&sdk_resolve(&context, &tx)),
+        vec![Diagnostic::new(
+            Rule::InvalidRoyalty,
+            CellSource::Output,
+            1,
+            "content_type"
+        )]
+    );
+    let tx = context.complete_tx(tx);
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test following the royalty of a cluster without one");
+
+    let tx = mint(&mut context, &cluster, "text/plain;royalty-to=cluster");
+    assert!(validator.validate(&sdk_resolve(&context, &tx)).is_empty());
+    let mut outputs = sdk_commit(&mut context, tx);
+    let spore = outputs.remove(1);
+    let cluster = outputs.remove(0);
+
+    let funding = sdk_funding(&mut context, &buyer);
+    let transfer = SporeTransfer::new(spore_script.clone(), spore, buyer.clone())
+        .cluster(cluster)
+        .price(500_000_000_000);
+    assert_eq!(
+        transfer.clone().build(funding.clone()).err(),
+        Some(SdkError::MissingRoyaltyLock)
+    );
+    let tx = transfer
+        .royalty_lock(recipient)
+        .build(funding)
+        .expect("build spore transfer");
+    let royalty: u64 = tx.outputs().get(0).unwrap().capacity().unpack();
+    assert_eq!(royalty, 50_000_000_000);
+
+    // one shannon short of the royalty, then of the price
+    let short = |index: usize| {
+        let outputs: Vec<CellOutput> = tx
+            .outputs()
+            .into_iter()
+            .enumerate()
+            .map(|(i, output)| match i == index {
+                true => {
+                    let capacity: u64 = output.capacity().unpack();
+                    output.as_builder().capacity((capacity - 1).pack()).build()
+                }
+                false => output,
+            })
+            .collect();
+        tx.as_advanced_builder().set_outputs(outputs).build()
+    };
+    for (index, rule) in [(0, Rule::RoyaltyNotPaid), (1, Rule::PriceNotPaid)] {
+        let underpaid = short(index);
+        assert_eq!(
+            validator.validate(&sdk_resolve(&context, &underpaid)),
+            vec![Diagnostic::new(rule, CellSource::Input, 0, "capacity")]
+        );
+        let underpaid = context.complete_tx(underpaid);
+        context
+            .verify_tx(&underpaid, MAX_CYCLES)
+            .expect_err("test transfer dues not paid");
+    }
+
+    assert!(validator.validate(&sdk_resolve(&context, &tx)).is_empty());
+    sdk_commit(&mut context, tx);
+}
+
+#[test]
+fn test_sdk_mutant_operation_payments() {
+    let mut context = Context::default();
+    let always_success = context.deploy_cell(ALWAYS_SUCCESS.clone());
+    let mut lock = |args: &str| {
+        context
+            .build_script(&always_success, Bytes::from(args.to_owned()))
+            .expect("lock")
+    };
+    let (owner, mutant_lock) = (lock(""), lock("mutant"));
+    let spore_script = sdk_script_info(&mut context, "spore");
+    let validator = build_validator(&mut context);
+
+    // a native mutant free to mint with, charging 10^10 shannons on transfer and 10^11 on melt
+    let mutant_args = [&[1u8; 32][..], &[NO_PAYMENT, 10, 11]].concat();
+    let mutant_type = context
+        .build_script(&always_success, Bytes::from(mutant_args))
+        .expect("mutant type");
+    let mutant_output = CellOutput::new_builder()
+        .lock(mutant_lock.clone())
+        .type_(Some(mutant_type).pack())
+        .capacity(1_000_000_000_000u64.pack())
+        .build();
+    let mutant_out_point = context.create_cell(mutant_output.clone(), Bytes::new());
+    let mutant = LiveCell::new(mutant_out_point, mutant_output, Bytes::new());
+
+    let funding = sdk_funding(&mut context, &owner);
+    let mutant_id = mutant.type_script().unwrap().calc_script_hash();
+    let content_type = format!("text/plain;mutant[]={}", encode(mutant_id.as_slice()));
+    let tx = SporeMint::new(
+        spore_script.clone(),
+        owner.clone(),
+        &content_type,
+        b"Hello Spore!",
+    )
+    .mutant(mutant.clone())
+    .build(funding)
+    .expect("build spore");
+    assert_eq!(tx.outputs().len(), 2);
+    let spore = sdk_commit(&mut context, tx).remove(0);
+
+    // the payment sent back to the owner instead of the mutant's lock
+    let unpaid = |context: &mut Context, tx: &TransactionView| {
+        let outputs: Vec<CellOutput> = tx
+            .outputs()
+            .into_iter()
+            .map(|output| match output.lock() == mutant_lock {
+                true => output.as_builder().lock(owner.clone()).build(),
+                false => output,
+            })
+            .collect();
+        let tx = tx.as_advanced_builder().set_outputs(outputs).build();
+        assert_eq!(
+            validator.validate(&sdk_resolve(context, &tx)),
+            vec![Diagnostic::new(
+                Rule::ExtensionPaymentNotEnough,
+                CellSource::CellDep,
+                0,
+                "type.args"
+            )]
+        );
+        context.complete_tx(tx)
+    };
+
+    let funding = sdk_funding(&mut context, &owner);
+    let tx = SporeTransfer::new(spore_script.clone(), spore, owner.clone())
+        .mutant(mutant.clone())
+        .build(funding)
+        .expect("build spore transfer");
+    let payment: u64 = tx.outputs().get(1).unwrap().capacity().unpack();
+    assert_eq!(payment, 10_000_000_000);
+    let tx_unpaid = unpaid(&mut context, &tx);
+    context
+        .verify_tx(&tx_unpaid, MAX_CYCLES)
+        .expect_err("test mutant transfer payment not enough");
+    assert!(validator.validate(&sdk_resolve(&context, &tx)).is_empty());
+    let spore = sdk_commit(&mut context, tx).remove(0);
+
+    let funding = sdk_funding(&mut context, &owner);
+    let tx = SporeMelt::new(spore_script, spore)
+        .mutant(mutant)
+        .build(funding)
+        .expect("build spore melt");
+    let payment: u64 = tx.outputs().get(0).unwrap().capacity().unpack();
+    assert_eq!(payment, 100_000_000_000);
+    let tx_unpaid = unpaid(&mut context, &tx);
+    context
+        .verify_tx(&tx_unpaid, MAX_CYCLES)
+        .expect_err("test mutant melt payment not enough");
+    assert!(validator.validate(&sdk_resolve(&context, &tx)).is_empty());
+    sdk_commit(&mut context, tx);
+}
+
+#[test]
+fn test_sdk_lua_mutant_spore_context() {
+    let mut context = Context::default();
+    context.set_capture_debug(true);
+    let lock = sdk_lock(&mut context);
+    let cluster_script = sdk_script_info(&mut context, "cluster");
+    let spore_script = sdk_script_info(&mut context, "spore");
+    let mutant_script = sdk_script_info(&mut context, "spore_extension_lua");
+    let lua_lib = sdk_lua_lib(&mut context);
+
+    // the mutant prints what it reads of the `spore` table on every operation
+    let code = r#"
+if spore_ext_mode ~= 0 then
+    local function hex(bytes)
+        return (bytes:gsub(".", function(c) return string.format("%02x", c:byte()) end))
+    end
+    print("mode " .. spore_ext_mode .. " id " .. hex(spore.id) .. " name "
+        .. spore.content_type.params.name .. " cluster " .. hex(spore.cluster_id))
+end
+"#;
+    let funding = sdk_funding(&mut context, &lock);
+    let tx = MutantCreate::new(
+        mutant_script.clone(),
+        lua_lib.clone(),
+        lock.clone(),
+        code.as_bytes(),
+    )
+    .build(funding)
+    .expect("build mutant");
+    let mutant = sdk_commit_with_cycles(&mut context, tx, MAX_LUA_CYCLES).remove(0);
+
+    let funding = sdk_funding(&mut context, &lock);
+    let tx = ClusterCreate::new(
+        cluster_script.clone(),
+        lock.clone(),
+        "Spore Cluster",
+        "Test",
+    )
+    .build(funding)
+    .expect("build cluster");
+    let cluster = sdk_commit(&mut context, tx).remove(0);
+    let cluster_id = encode(cluster.type_args().unwrap());
+
+    let funding = sdk_funding(&mut context, &lock);
+    let content_type = format!(
+        "text/plain;name=lua;mutant[]={}",
+        encode(&mutant.type_args().unwrap()[..32])
+    );
+    let tx = SporeMint::new(
+        spore_script.clone(),
+        lock.clone(),
+        &content_type,
+        b"Hello Spore!",
+    )
+    .cluster(cluster_script, cluster)
+    .mutant(mutant.clone())
+    .cell_dep(mutant_script.cell_dep.clone())
+    .cell_dep(lua_lib.clone())
+    .build(funding)
+    .expect("build spore");
+    let spore = sdk_commit_with_cycles(&mut context, tx, MAX_LUA_CYCLES).remove(1);
+    let spore_id = encode(spore.type_args().unwrap());
+
+    let funding = sdk_funding(&mut context, &lock);
+    let tx = SporeTransfer::new(spore_script.clone(), spore, lock.clone())
+        .mutant(mutant.clone())
+        .cell_dep(mutant_script.cell_dep.clone())
+        .cell_dep(lua_lib.clone())
+        .build(funding)
+        .expect("build spore transfer");
+    let spore = sdk_commit_with_cycles(&mut context, tx, MAX_LUA_CYCLES).remove(0);
+
+    let funding = sdk_funding(&mut context, &lock);
+    let tx = SporeMelt::new(spore_script, spore)
+        .mutant(mutant)
+        .cell_dep(mutant_script.cell_dep)
+        .cell_dep(lua_lib)
+        .build(funding)
+        .expect("build spore melt");
+    sdk_commit_with_cycles(&mut context, tx, MAX_LUA_CYCLES);
+
+    let messages: Vec<String> = context
+        .captured_messages()
+        .into_iter()
+        .map(|message| message.message)
+        .collect();
+    for mode in 1..=3 {
+        let expected = format!("mode {mode} id {spore_id} name lua cluster {cluster_id}");
+        assert!(messages.contains(&expected), "{expected} in {messages:?}");
+    }
+}
+
+#[test]
+fn test_sdk_lua_mutants_share_code_in_order() {
+    let mut context = Context::default();
+    context.set_capture_debug(true);
+    let lock = sdk_lock(&mut context);
+    let spore_script = sdk_script_info(&mut context, "spore");
+    let mutant_script = sdk_script_info(&mut context, "spore_extension_lua");
+    let lua_lib = sdk_lua_lib(&mut context);
+
+    // the second mutant is pinned to the code of the first one, which prints the running mutant
+    let code = r#"
+if spore_ext_mode ~= 0 then
+    print("mutant " .. (spore.mutant_id:gsub(".", function(c) return string.format("%02x", c:byte()) end)))
+end
+"#;
+    let mut create = |code: &str| {
+        let funding = sdk_funding(&mut context, &lock);
+        let tx = MutantCreate::new(
+            mutant_script.clone(),
+            lua_lib.clone(),
+            lock.clone(),
+            code.as_bytes(),
+        )
+        .build(funding)
+        .expect("build mutant");
+        sdk_commit_with_cycles(&mut context, tx, MAX_LUA_CYCLES).remove(0)
+    };
+    let first = create(code);
+    let second = create("if spore_ext_mode ~= 0 then print(\"unpinned\") end");
+    let mutant_id = |mutant: &LiveCell| encode(&mutant.type_args().unwrap()[..32]);
+    let code_hash = encode(CellOutput::calc_data_hash(code.as_bytes()).as_slice());
+
+    // listed in the reverse order of their cells in CellDeps
+    let funding = sdk_funding(&mut context, &lock);
+    let content_type = format!(
+        "text/plain;mutant[]={},{};mutant-code[]={code_hash},{code_hash}",
+        mutant_id(&second),
+        mutant_id(&first)
+    );
+    let tx = SporeMint::new(spore_script, lock.clone(), &content_type, b"Hello Spore!")
+        .mutant(first.clone())
+        .mutant(second.clone())
+        .cell_dep(mutant_script.cell_dep)
+        .cell_dep(lua_lib)
+        .build(funding)
+        .expect("build spore");
+    sdk_commit_with_cycles(&mut context, tx, MAX_LUA_CYCLES);
+
+    let messages: Vec<String> = context
+        .captured_messages()
+        .into_iter()
+        .map(|message| message.message)
+        .filter(|message| message.starts_with("mutant ") || message == "unpinned")
+        .collect();
+    assert_eq!(
+        messages,
+        vec![
+            format!("mutant {}", mutant_id(&second)),
+            format!("mutant {}", mutant_id(&first))
+        ]
+    );
+}
+
+#[test]
+fn test_sdk_mutant_payment_below_occupied_capacity() {
+    let mut context = Context::default();
+    let always_success = context.deploy_cell(ALWAYS_SUCCESS.clone());
+    let mut lock = |args: &str| {
+        context
+            .build_script(&always_success, Bytes::from(args.to_owned()))
+            .expect("lock")
+    };
+    let (owner, mutant_lock) = (lock(""), lock("mutant"));
+    let spore_script = sdk_script_info(&mut context, "spore");
+    let validator = build_validator(&mut context);
+
+    // a native mutant charging 10^3 shannons to mint with, far less than a cell occupies
+    let mutant_args = [&[1u8; 32][..], &[3, NO_PAYMENT, NO_PAYMENT]].concat();
+    let mutant_type = context
+        .build_script(&always_success, Bytes::from(mutant_args))
+        .expect("mutant type");
+    let mutant_output = CellOutput::new_builder()
+        .lock(mutant_lock.clone())
+        .type_(Some(mutant_type).pack())
+        .capacity(1_000_000_000_000u64.pack())
+        .build();
+    let mutant_out_point = context.create_cell(mutant_output.clone(), Bytes::new());
+    let mutant = LiveCell::new(mutant_out_point, mutant_output, Bytes::new());
+
+    let funding = sdk_funding(&mut context, &owner);
+    let mutant_id = mutant.type_script().unwrap().calc_script_hash();
+    let content_type = format!("text/plain;mutant[]={}", encode(mutant_id.as_slice()));
+    let tx = SporeMint::new(spore_script, owner, &content_type, b"Hello Spore!")
+        .mutant(mutant)
+        .build(funding)
+        .expect("build spore");
+
+    // the payment is raised to the occupied capacity of its cell
+    let payment = tx.outputs().get(0).unwrap();
+    assert_eq!(payment.lock(), mutant_lock);
+    let occupied = payment
+        .occupied_capacity(Capacity::zero())
+        .expect("occupied capacity");
+    let capacity: u64 = payment.capacity().unpack();
+    assert_eq!(capacity, occupied.as_u64());
+    assert!(capacity > 1_000);
+    assert!(validator.validate(&sdk_resolve(&context, &tx)).is_empty());
+    sdk_commit(&mut context, tx);
+}
+
+#[test]
+fn test_sdk_mutant_payment_descriptors() {
+    let mut context = Context::default();
+    let always_success = context.deploy_cell(ALWAYS_SUCCESS.clone());
+    let mut lock = |args: &str| {
+        context
+            .build_script(&always_success, Bytes::from(args.to_owned()))
+            .expect("lock")
+    };
+    let (owner, mutant_lock) = (lock(""), lock("mutant"));
+    let spore_script = sdk_script_info(&mut context, "spore");
+    let validator = build_validator(&mut context);
+
+    // a native mutant charging exactly 1234.5 CKB on mint and 10 xUDT on transfer
+    let payments = [
+        Some(Payment::Exact(123_450_000_000)),
+        Some(Payment::Xudt {
+            type_hash: [9; 32],
+            amount: 10,
+        }),
+        None,
+    ];
+    let mutant_args = [&[1u8; 32][..], &[0; 32], &to_descriptors(&payments)].concat();
+    let mutant_type = context
+        .build_script(&always_success, Bytes::from(mutant_args))
+        .expect("mutant type");
+    let mutant_output = CellOutput::new_builder()
+        .lock(mutant_lock.clone())
+        .type_(Some(mutant_type).pack())
+        .capacity(1_000_000_000_000u64.pack())
+        .build();
+    let mutant_out_point = context.create_cell(mutant_output.clone(), Bytes::new());
+    let mutant = LiveCell::new(mutant_out_point, mutant_output, Bytes::new());
+
+    let funding = sdk_funding(&mut context, &owner);
+    let mutant_id = mutant.type_script().unwrap().calc_script_hash();
+    let content_type = format!("text/plain;mutant[]={}", encode(mutant_id.as_slice()));
+    let tx = SporeMint::new(
+        spore_script.clone(),
+        owner.clone(),
+        &content_type,
+        b"Hello Spore!",
+    )
+    .mutant(mutant.clone())
+    .build(funding)
+    .expect("build spore");
+    let payment: u64 = tx.outputs().get(0).unwrap().capacity().unpack();
+    assert_eq!(payment, 123_450_000_000);
+
+    // the payment output shifted by `delta` shannons, the fee making up for it
+    let repay = |tx: &TransactionView, delta: i64| {
+        let outputs: Vec<CellOutput> = tx
+            .outputs()
+            .into_iter()
+            .map(|output| match output.lock() == mutant_lock {
+                true => {
+                    let capacity: u64 = output.capacity().unpack();
+                    let capacity = capacity.checked_add_signed(delta).unwrap();
+                    output.as_builder().capacity(capacity.pack()).build()
+                }
+                false => output,
+            })
+            .collect();
+        tx.as_advanced_builder().set_outputs(outputs).build()
+    };
+    let underpaid = repay(&tx, -1);
+    assert_eq!(
+        validator.validate(&sdk_resolve(&context, &underpaid)),
+        vec![Diagnostic::new(
+            Rule::ExtensionPaymentNotEnough,
+            CellSource::CellDep,
+            0,
+            "type.args"
+        )]
+    );
+    let underpaid = context.complete_tx(underpaid);
+    context
+        .verify_tx(&underpaid, MAX_CYCLES)
+        .expect_err("test mutant exact payment not enough");
+    let overpaid = repay(&tx, 1);
+    assert!(validator
+        .validate(&sdk_resolve(&context, &overpaid))
+        .is_empty());
+    let overpaid = context.complete_tx(overpaid);
+    context
+        .verify_tx(&overpaid, MAX_CYCLES)
+        .expect("test mutant exact payment overpaid");
+    assert!(validator.validate(&sdk_resolve(&context, &tx)).is_empty());
+    let spore = sdk_commit(&mut context, tx).remove(1);
+
+    // the SDK builders only pay mutants in capacity
+    let funding = sdk_funding(&mut context, &owner);
+    let error = SporeTransfer::new(spore_script, spore, owner)
+        .mutant(mutant)
+        .build(funding)
+        .expect_err("build spore transfer paying xUDT");
+    assert_eq!(error, SdkError::PaymentMethodNotSupport);
+}
+
+#[test]
+fn test_sdk_cluster_mutant_lists() {
+    let mut context = Context::default();
+    let always_success = context.deploy_cell(ALWAYS_SUCCESS.clone());
+    let lock = sdk_lock(&mut context);
+    let cluster_script = sdk_script_info(&mut context, "cluster");
+    let spore_script = sdk_script_info(&mut context, "spore");
+    let mutant_script = sdk_script_info(&mut context, "spore_extension_lua");
+    let validator = build_validator(&mut context);
+    let mut mutant = |id: u8| {
+        let output = CellOutput::new_builder()
+            .lock(lock.clone())
+            .type_(Some(mutant_script.script(&[id; 32])).pack())
+            .capacity(1_000_000_000_000u64.pack())
+            .build();
+        let data = Bytes::from("return 0");
+        let out_point = context.create_cell(output.clone(), data.clone());
+        LiveCell::new(out_point, output, data)
+    };
+    let (required, allowed, other) = (mutant(1), mutant(2), mutant(3));
+    // native mutants can be listed too, by the type hash of their cell
+    let native_type = context
+        .build_script(&always_success, Bytes::from(vec![4u8; 32]))
+        .expect("native mutant");
+    let output = CellOutput::new_builder()
+        .lock(lock.clone())
+        .type_(Some(native_type).pack())
+        .capacity(1_000_000_000_000u64.pack())
+        .build();
+    let out_point = context.create_cell(output.clone(), Bytes::new());
+    let native = LiveCell::new(out_point, output, Bytes::new());
+
+    // listed mutants are looked up in CellDeps
+    let funding = sdk_funding(&mut context, &lock);
+    let tx = ClusterCreate::new(
+        cluster_script.clone(),
+        lock.clone(),
+        "Spore Cluster",
+        "Test",
+    )
+    .required_mutant(required.clone())
+    .allowed_mutants(vec![allowed.clone(), native])
+    .extension_lua(mutant_script.clone())
+    .build(funding)
+    .expect("build cluster");
+    assert!(validator.validate(&sdk_resolve(&context, &tx)).is_empty());
+    let cell_deps: Vec<CellDep> = tx
+        .cell_deps()
+        .into_iter()
+        .filter(|cell_dep| cell_dep != &allowed.cell_dep())
+        .collect();
+    let tx_without_mutant = tx.as_advanced_builder().set_cell_deps(cell_deps).build();
+    assert_eq!(
+        validator.validate(&sdk_resolve(&context, &tx_without_mutant)),
+        vec![Diagnostic::new(
+            Rule::MutantNotInDeps,
+            CellSource::Output,
+            0,
+            "allowed_mutants"
+        )]
+    );
+    let tx_without_mutant = context.complete_tx(tx_without_mutant);
+    context
+        .verify_tx(&tx_without_mutant, MAX_CYCLES)
+        .expect_err("test listed mutant not in deps");
+
+    // a cell of another type script carrying the args of the required mutant can't stand in for it
+    let spoof_output = CellOutput::new_builder()
+        .lock(lock.clone())
+        .type_(
+            Some(
+                context
+                    .build_script(&always_success, Bytes::from(vec![1u8; 32]))
+                    .expect("spoofed mutant"),
+            )
+            .pack(),
+        )
+        .capacity(1_000_000_000_000u64.pack())
+        .build();
+    let spoof_out_point = context.create_cell(spoof_output, Bytes::from("return 0"));
+    let spoof = CellDep::new_builder().out_point(spoof_out_point).build();
+    let cell_deps: Vec<CellDep> = tx
+        .cell_deps()
+        .into_iter()
+        .map(|cell_dep| match cell_dep == required.cell_dep() {
+            true => spoof.clone(),
+            false => cell_dep,
+        })
+        .collect();
+    let tx_spoofed = tx.as_advanced_builder().set_cell_deps(cell_deps).build();
+    assert_eq!(
+        validator.validate(&sdk_resolve(&context, &tx_spoofed)),
+        vec![Diagnostic::new(
+            Rule::MutantNotInDeps,
+            CellSource::Output,
+            0,
+            "required_mutants"
+        )]
+    );
+    let tx_spoofed = context.complete_tx(tx_spoofed);
+    context
+        .verify_tx(&tx_spoofed, MAX_CYCLES)
+        .expect_err("test spoofed required mutant");
+    let cluster = sdk_commit(&mut context, tx).remove(0);
+
+    let mint = |context: &mut Context, mutants: &[&LiveCell]| {
+        let content_type = match mutants.is_empty() {
+            true => "text/plain".to_owned(),
+            false => {
+                let ids: Vec<String> = mutants
+                    .iter()
+                    .map(|mutant| encode(mutant.mutant_id(&mutant_script).unwrap()))
+                    .collect();
+                format!("text/plain;mutant[]={}", ids.join(","))
+            }
+        };
+        let funding = sdk_funding(context, &lock);
+        mutants
+            .iter()
+            .fold(
+                SporeMint::new(
+                    spore_script.clone(),
+                    lock.clone(),
+                    &content_type,
+                    b"Hello Spore!",
+                ),
+                |mint, mutant| mint.mutant((*mutant).clone()),
+            )
+            .cluster(cluster_script.clone(), cluster.clone())
+            .build(funding)
+            .expect("build spore")
+    };
+
+    // the required mutant missing, or a mutant out of the lists applied
+    let rejected = [
+        (Vec::new(), Rule::ClusterRequiresMutantApplied),
+        (vec![&allowed], Rule::ClusterRequiresMutantApplied),
+        (vec![&required, &other], Rule::MutantNotAllowed),
+        (vec![&required, &allowed, &other], Rule::MutantNotAllowed),
+    ];
+    for (mutants, rule) in rejected {
+        let tx = mint(&mut context, &mutants);
+        assert_eq!(
+            validator.validate(&sdk_resolve(&context, &tx)),
+            vec![Diagnostic::new(rule, CellSource::Output, 1, "content_type")]
+        );
+        let tx = context.complete_tx(tx);
+        context
+            .verify_tx(&tx, MAX_CYCLES)
+            .expect_err("test cluster mutant lists");
+    }
+    for mutants in [vec![&required], vec![&allowed, &required]] {
+        let tx = mint(&mut context, &mutants);
+        assert!(validator.validate(&sdk_resolve(&context, &tx)).is_empty());
+    }
+
+    // an empty list of required mutants is invalid
+    let funding = sdk_funding(&mut context, &lock);
+    let tx = ClusterCreate::new(cluster_script, lock.clone(), "Spore Cluster", "Test")
+        .allowed_mutants(Vec::new())
+        .build(funding)
+        .expect("build cluster");
+    let data = ClusterDataV2::from_slice(&tx.outputs_data().get(0).unwrap().raw_data()).unwrap();
+    let data = data
+        .as_builder()
+        .required_mutants(
+            BytesVecOpt::new_builder()
+                .set(Some(Default::default()))
+                .build(),
+        )
+        .build();
+    let tx = tx
+        .as_advanced_builder()
+        .set_outputs_data(vec![data.as_bytes().pack()])
+        .build();
+    assert_eq!(
+        validator.validate(&sdk_resolve(&context, &tx)),
+        vec![Diagnostic::new(
+            Rule::InvalidClusterData,
+            CellSource::Output,
+            0,
+            "required_mutants"
+        )]
+    );
+    let tx = context.complete_tx(tx);
+    context
+        .verify_tx(&tx, MAX_CYCLES)
+        .expect_err("test empty required mutants");
+}
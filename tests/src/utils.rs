@@ -4,17 +4,23 @@ use ckb_testtool::builtin::ALWAYS_SUCCESS;
 use ckb_testtool::ckb_hash::{new_blake2b, Blake2bBuilder};
 use ckb_testtool::ckb_types::core::ScriptHashType;
 use ckb_testtool::ckb_types::{
-    bytes::Bytes, core::TransactionBuilder, core::TransactionView, packed, packed::*, prelude::*,
-    H256,
+    bytes::Bytes, core::EpochNumberWithFraction, core::HeaderBuilder, core::TransactionBuilder,
+    core::TransactionView, packed, packed::*, prelude::*, H256,
 };
 use ckb_testtool::context::Context;
 use std::num::ParseIntError;
 
-use spore_types::generated::spore_types::{ClusterData, SporeData};
+use spore_sdk::validator::{ResolvedTransaction, Validator};
+use spore_sdk::{Funding, LiveCell, ScriptInfo};
+use spore_types::generated::spore_types::{ClusterData, ClusterDataV2, SporeData};
 use spore_types::NativeNFTData;
 
 use crate::Loader;
 
+pub const MAX_CYCLES: u64 = 10_000_000;
+// a Lua mutant loads the Lua lib and runs the interpreter on top of the Spore contract
+pub const MAX_LUA_CYCLES: u64 = 100_000_000;
+
 #[allow(non_snake_case)]
 mod Internal {
     use super::*;
@@ -108,7 +114,7 @@ pub fn build_spore_input(
     type_id: Bytes,
 ) -> CellInput {
     let input_ckb = spore_data.total_size() as u64;
-    let type_ = build_spore_type_script(context, &spore_out_point, type_id);
+    let type_ = build_spore_type_script(context, spore_out_point, type_id);
     Internal::build_input(
         context,
         input_ckb,
@@ -180,6 +186,153 @@ pub fn build_spore_materials(context: &mut Context) -> (OutPoint, CellDep) {
     (spore_out_point, spore_script_dep)
 }
 
+pub fn build_cluster_materials(context: &mut Context) -> (OutPoint, CellDep) {
+    let cluster_bin: Bytes = Loader::default().load_binary("cluster");
+    let cluster_out_point = context.deploy_cell(cluster_bin);
+    let cluster_script_dep = CellDep::new_builder()
+        .out_point(cluster_out_point.clone())
+        .build();
+    (cluster_out_point, cluster_script_dep)
+}
+
+/// A live cluster cell, spent through `input` or referenced through `dep`
+pub struct ClusterCell {
+    pub id: Bytes,
+    pub type_: Option<Script>,
+    pub script_dep: CellDep,
+    pub input: CellInput,
+    pub dep: CellDep,
+}
+
+pub fn build_cluster_cell(context: &mut Context, cluster_data: &[u8]) -> ClusterCell {
+    let (cluster_out_point, script_dep) = build_cluster_materials(context);
+    let id = build_script_args(&build_normal_input(context, 1), 0);
+    let type_ = build_spore_type_script(context, &cluster_out_point, id.clone());
+    let out_point = Internal::build_outpoint(
+        context,
+        cluster_data.len() as u64,
+        type_.clone(),
+        Bytes::copy_from_slice(cluster_data),
+    );
+    ClusterCell {
+        id,
+        type_,
+        script_dep,
+        input: CellInput::new_builder()
+            .previous_output(out_point.clone())
+            .build(),
+        dep: CellDep::new_builder().out_point(out_point).build(),
+    }
+}
+
+pub fn build_cluster_data_v2(closed: bool) -> ClusterDataV2 {
+    ClusterDataV2::new_builder()
+        .name("Spore Cluster".as_bytes().into())
+        .description("Test Cluster".as_bytes().into())
+        .closed(closed.into())
+        .build()
+}
+
+/// A cluster proxy cell dep of `lock`, with `payment` following the proxy ID in its type args
+pub fn build_cluster_proxy(
+    context: &mut Context,
+    cluster_id: &Bytes,
+    payment: &[u8],
+    lock: Script,
+) -> (CellDep, Option<Script>, CellDep) {
+    let proxy_bin: Bytes = Loader::default().load_binary("cluster_proxy");
+    let proxy_out_point = context.deploy_cell(proxy_bin);
+    let proxy_script_dep = CellDep::new_builder()
+        .out_point(proxy_out_point.clone())
+        .build();
+    let proxy_type_id = build_script_args(&build_normal_input(context, 1), 0);
+    let proxy_type_args = [&proxy_type_id[..], payment].concat();
+    let proxy_type =
+        build_spore_type_script(context, &proxy_out_point, Bytes::from(proxy_type_args));
+    let proxy_cell = CellOutput::new_builder()
+        .capacity((cluster_id.len() as u64).pack())
+        .lock(lock)
+        .type_(ScriptOpt::new_builder().set(proxy_type.clone()).build())
+        .build();
+    let proxy_dep = CellDep::new_builder()
+        .out_point(context.create_cell(proxy_cell, cluster_id.clone()))
+        .build();
+    (proxy_script_dep, proxy_type, proxy_dep)
+}
+
+pub fn build_agent_materials(context: &mut Context) -> (OutPoint, CellDep) {
+    let agent_bin: Bytes = Loader::default().load_binary("cluster_agent");
+    let agent_out_point = context.deploy_cell(agent_bin);
+    let agent_script_dep = CellDep::new_builder()
+        .out_point(agent_out_point.clone())
+        .build();
+    (agent_out_point, agent_script_dep)
+}
+
+/// A Spore minted into a cluster at an output index, its type ID derived from the first input
+pub fn build_cluster_spore_output(
+    context: &mut Context,
+    spore_out_point: &OutPoint,
+    first_input: &CellInput,
+    out_index: usize,
+    cluster_id: &[u8],
+) -> (CellOutput, packed::Bytes) {
+    build_cluster_spore_output_with_content(
+        context,
+        spore_out_point,
+        first_input,
+        out_index,
+        cluster_id,
+        "Hello Spore!",
+    )
+}
+
+pub fn build_cluster_spore_output_with_content(
+    context: &mut Context,
+    spore_out_point: &OutPoint,
+    first_input: &CellInput,
+    out_index: usize,
+    cluster_id: &[u8],
+    content: &str,
+) -> (CellOutput, packed::Bytes) {
+    let serialized = build_serialized_spore_internal(
+        content.as_bytes().to_vec(),
+        "plain/text",
+        Some(cluster_id.to_vec()),
+    );
+    let capacity = serialized.total_size() as u64;
+    let spore_type_id = build_script_args(first_input, out_index);
+    let spore_type = build_spore_type_script(context, spore_out_point, spore_type_id);
+    (
+        build_output_cell_with_type_id(context, capacity, spore_type),
+        serialized.as_slice().pack(),
+    )
+}
+
+/// Appends a Spore minted into a cluster whose type script refers to the Spore code by type
+pub fn append_spore_by_type(
+    context: &mut Context,
+    tx: TransactionView,
+    cluster_id: &[u8],
+) -> TransactionView {
+    let serialized = build_serialized_spore_internal(
+        "Hello Spore!".as_bytes().to_vec(),
+        "plain/text",
+        Some(cluster_id.to_vec()),
+    );
+    let capacity = serialized.total_size() as u64;
+    let (spore_out_point, spore_script_dep) = build_spore_materials(context);
+    let spore_type_id = build_script_args(&tx.inputs().get(0).unwrap(), tx.outputs().len());
+    let spore_type =
+        context.build_script_with_hash_type(&spore_out_point, ScriptHashType::Type, spore_type_id);
+    let spore_out_cell = build_output_cell_with_type_id(context, capacity, spore_type);
+    tx.as_advanced_builder()
+        .output(spore_out_cell)
+        .output_data(serialized.as_slice().pack())
+        .cell_dep(spore_script_dep)
+        .build()
+}
+
 pub fn simple_build_context(
     output_data: Vec<u8>,
     content_type: &str,
@@ -211,7 +364,7 @@ pub fn simple_build_context(
             (spore_input, spore_type_id)
         }
     };
-    let spore_type = build_spore_type_script(&mut context, &spore_out_point, type_id.clone());
+    let spore_type = build_spore_type_script(&mut context, &spore_out_point, type_id);
     let spore_output = build_output_cell_with_type_id(&mut context, capacity, spore_type);
     let tx = build_simple_tx(
         vec![input],
@@ -223,6 +376,31 @@ pub fn simple_build_context(
     (context, tx)
 }
 
+pub fn build_transfer_context(
+    input_data: SporeData,
+    output_data: SporeData,
+) -> (Context, TransactionView) {
+    let mut context = Context::default();
+
+    // always success lock
+    let (spore_out_point, spore_script_dep) = build_spore_materials(&mut context);
+    let input_capacity = input_data.total_size() as u64;
+    let spore_type_id = build_script_args(&build_normal_input(&mut context, input_capacity), 0);
+    let spore_type = build_spore_type_script(&mut context, &spore_out_point, spore_type_id.clone());
+    let spore_input = build_spore_input(&mut context, &spore_out_point, input_data, spore_type_id);
+
+    let output_capacity = output_data.total_size() as u64;
+    let spore_output = build_output_cell_with_type_id(&mut context, output_capacity, spore_type);
+    let tx = build_simple_tx(
+        vec![spore_input],
+        vec![spore_output],
+        vec![spore_script_dep],
+        vec![output_data.as_slice().pack()],
+    );
+
+    (context, tx)
+}
+
 pub fn decode_hex(s: &str) -> Result<Vec<u8>, ParseIntError> {
     (0..s.len())
         .step_by(2)
@@ -244,10 +422,10 @@ pub fn build_simple_create_context_with_cluster(
     cluster_id: String,
 ) -> (Context, TransactionView) {
     let nft_data: NativeNFTData = NativeNFTData {
-        content: nft_content.clone().into_bytes(),
-        content_type: nft_type.clone(),
+        content: nft_content.into_bytes(),
+        content_type: nft_type,
         cluster_id: Some(
-            H256::from_trimmed_str(cluster_id.clone().trim_start_matches("0x"))
+            H256::from_trimmed_str(cluster_id.trim_start_matches("0x"))
                 .expect("parse cluster id")
                 .as_bytes()
                 .to_vec(),
@@ -298,7 +476,7 @@ pub fn build_create_context_with_cluster_raw(
         Bytes::new(),
     );
 
-    let cluster_id = H256::from_trimmed_str(cluster_id.clone().trim_start_matches("0x"))
+    let cluster_id = H256::from_trimmed_str(cluster_id.trim_start_matches("0x"))
         .expect("parse cluster id")
         .pack();
 
@@ -331,7 +509,7 @@ pub fn build_create_context_with_cluster_raw(
         .previous_output(
             context.create_cell(
                 CellOutput::new_builder()
-                    .capacity((1000000 as u64).pack())
+                    .capacity(1000000u64.pack())
                     .lock(lock_script.clone())
                     .build(),
                 Bytes::new(),
@@ -348,7 +526,7 @@ pub fn build_create_context_with_cluster_raw(
             .personal(b"ckb-default-hash")
             .build();
         blake2b.update(input.as_slice());
-        blake2b.update(&(1 as u64).to_le_bytes());
+        blake2b.update(&1u64.to_le_bytes());
         let mut verify_id = [0; 32];
         blake2b.finalize(&mut verify_id);
         verify_id.to_vec().into()
@@ -363,18 +541,18 @@ pub fn build_create_context_with_cluster_raw(
     let output = CellOutput::new_builder()
         .capacity((output_ckb + cluster_data.total_size() as u64).pack())
         .lock(lock_script.clone())
-        .type_(Some(nft_script.clone()).pack())
+        .type_(Some(nft_script).pack())
         .build();
 
     let cluster_output = CellOutput::new_builder()
         .capacity(input_ckb.pack())
         .lock(lock_script.clone())
-        .type_(Some(cluster_script.clone()).pack())
+        .type_(Some(cluster_script).pack())
         .build();
 
     let normal_output = CellOutput::new_builder()
         .capacity(9999u64.pack())
-        .lock(lock_script.clone())
+        .lock(lock_script)
         .build();
 
     let tx = TransactionBuilder::default()
@@ -397,3 +575,348 @@ pub fn build_create_context_with_cluster_raw(
 
     (context, tx)
 }
+
+pub fn build_cluster_update(
+    input_data: &[u8],
+    output_data: Option<&[u8]>,
+) -> (Context, TransactionView) {
+    let mut context = Context::default();
+    let cluster = build_cluster_cell(&mut context, input_data);
+
+    let (output, output_data) = match output_data {
+        Some(output_data) => (
+            build_output_cell_with_type_id(&mut context, output_data.len() as u64, cluster.type_),
+            output_data.pack(),
+        ),
+        None => (
+            build_normal_output(&mut context, input_data.len() as u64),
+            packed::Bytes::default(),
+        ),
+    };
+
+    let tx = build_simple_tx(
+        vec![cluster.input],
+        vec![output],
+        vec![cluster.script_dep],
+        vec![output_data],
+    );
+    (context, tx)
+}
+
+/// Spores minted into a cluster spent and recreated as is (Condition 1)
+pub fn build_batch_mint_with_cluster(contents: Vec<&str>) -> (Context, TransactionView) {
+    let mut context = Context::default();
+    let cluster_data = ClusterData::new_builder()
+        .name("Spore Cluster".as_bytes().into())
+        .description("Test Cluster".as_bytes().into())
+        .build();
+    let cluster = build_cluster_cell(&mut context, cluster_data.as_slice());
+    let cluster_output = build_output_cell_with_type_id(
+        &mut context,
+        cluster_data.total_size() as u64,
+        cluster.type_,
+    );
+
+    let (spore_out_point, spore_script_dep) = build_spore_materials(&mut context);
+    let input_cell = build_normal_input(&mut context, 1000);
+    let mut outputs = vec![cluster_output];
+    let mut outputs_data = vec![cluster_data.as_slice().pack()];
+    for content in contents {
+        let (spore_output, spore_data) = build_cluster_spore_output_with_content(
+            &mut context,
+            &spore_out_point,
+            &input_cell,
+            outputs.len(),
+            &cluster.id,
+            content,
+        );
+        outputs.push(spore_output);
+        outputs_data.push(spore_data);
+    }
+
+    let tx = build_simple_tx(
+        vec![input_cell, cluster.input],
+        outputs,
+        vec![cluster.script_dep, cluster.dep, spore_script_dep],
+        outputs_data,
+    );
+    (context, tx)
+}
+
+/// A Spore minted into a cluster with `max_supply`, updating its minted counter
+pub fn build_limited_cluster_mint(
+    max_supply: u64,
+    input_minted: u64,
+    output_minted: u64,
+) -> (Context, TransactionView) {
+    let mut context = Context::default();
+    let cluster_data = build_cluster_data_v2(false)
+        .as_builder()
+        .max_supply(Some(max_supply).into())
+        .minted(input_minted.into())
+        .build();
+    let output_cluster_data = cluster_data
+        .clone()
+        .as_builder()
+        .minted(output_minted.into())
+        .build();
+    let cluster = build_cluster_cell(&mut context, cluster_data.as_slice());
+    let cluster_output = build_output_cell_with_type_id(
+        &mut context,
+        output_cluster_data.total_size() as u64,
+        cluster.type_,
+    );
+
+    let (spore_out_point, spore_script_dep) = build_spore_materials(&mut context);
+    let input_cell = build_normal_input(&mut context, 1000);
+    let (spore_output, spore_data) =
+        build_cluster_spore_output(&mut context, &spore_out_point, &input_cell, 1, &cluster.id);
+
+    let tx = build_simple_tx(
+        vec![input_cell, cluster.input],
+        vec![cluster_output, spore_output],
+        vec![cluster.script_dep, spore_script_dep, cluster.dep],
+        vec![output_cluster_data.as_slice().pack(), spore_data],
+    );
+    (context, tx)
+}
+
+/// How a cluster proxy asks for its payment, `price` being the exponent of a legacy payment
+#[derive(PartialEq)]
+pub enum PaymentMethod {
+    PowerOfTen,
+    /// legacy args followed by bytes that were never read
+    PowerOfTenPadded,
+    Exact,
+    Xudt,
+}
+
+/// A cluster agent created from a proxy by paying `paid` of its `price`
+pub fn build_cluster_agent_with_payment(
+    method: PaymentMethod,
+    price: u128,
+    paid: u128,
+) -> (Context, TransactionView) {
+    let mut context = Context::default();
+    let cluster = build_cluster_cell(&mut context, build_cluster_data_v2(false).as_slice());
+
+    // payments go to the lock of the proxy, which differs from the minter's lock
+    let always_success_out_point = context.deploy_cell(ALWAYS_SUCCESS.clone());
+    let proxy_lock = context
+        .build_script(&always_success_out_point, Bytes::from(vec![1]))
+        .expect("proxy lock");
+    let udt_type = context
+        .build_script(&always_success_out_point, Bytes::from(vec![2]))
+        .expect("udt type");
+
+    let payment = match method {
+        PaymentMethod::PowerOfTen => vec![price as u8],
+        PaymentMethod::PowerOfTenPadded => vec![price as u8, 0, 0],
+        PaymentMethod::Exact => [&[0u8][..], &(price as u64).to_le_bytes()].concat(),
+        PaymentMethod::Xudt => [
+            &[1u8][..],
+            udt_type.calc_script_hash().as_slice(),
+            &price.to_le_bytes(),
+        ]
+        .concat(),
+    };
+    let (proxy_script_dep, proxy_type, proxy_dep) =
+        build_cluster_proxy(&mut context, &cluster.id, &payment, proxy_lock.clone());
+
+    let payment_cell = CellOutput::new_builder().lock(proxy_lock);
+    let (payment_cell, payment_data) = match method {
+        PaymentMethod::Xudt => (
+            payment_cell
+                .capacity(1000u64.pack())
+                .type_(ScriptOpt::new_builder().set(Some(udt_type)).build())
+                .build(),
+            Bytes::copy_from_slice(&paid.to_le_bytes()),
+        ),
+        _ => (
+            payment_cell.capacity((paid as u64).pack()).build(),
+            Bytes::new(),
+        ),
+    };
+
+    let (agent_out_point, agent_script_dep) = build_agent_materials(&mut context);
+    let input_cell = build_normal_input(&mut context, 1_000_000);
+    let agent_type = build_spore_type_script(&mut context, &agent_out_point, cluster.id);
+    let agent_out_cell = build_output_cell_with_type_id(&mut context, 1000, agent_type);
+
+    let tx = build_simple_tx(
+        vec![input_cell],
+        vec![agent_out_cell, payment_cell],
+        vec![
+            cluster.script_dep,
+            proxy_script_dep,
+            agent_script_dep,
+            cluster.dep,
+            proxy_dep,
+        ],
+        vec![
+            // the proxy type hash and no expiry
+            [
+                proxy_type.unwrap_or_default().calc_script_hash().as_slice(),
+                &[0; 8],
+            ]
+            .concat()
+            .pack(),
+            payment_data.pack(),
+        ],
+    );
+    (context, tx)
+}
+
+/// A Spore minted through a cluster agent consumed and recreated (Condition 2), created in the
+/// block `agent_block_number` with an optional expiry and quota
+pub fn build_spore_mint_with_agent(
+    expiry: u64,
+    agent_block_number: u64,
+    proxy_alive: bool,
+    quota: Option<(u64, u64)>,
+) -> (Context, TransactionView) {
+    let mut context = Context::default();
+    let cluster = build_cluster_cell(&mut context, build_cluster_data_v2(false).as_slice());
+    let proxy_lock = Internal::build_always_success_script(&mut context);
+    let (_, proxy_type, proxy_dep) =
+        build_cluster_proxy(&mut context, &cluster.id, &[], proxy_lock);
+
+    // agent, consumed and recreated by the minting transaction
+    let (agent_out_point, agent_script_dep) = build_agent_materials(&mut context);
+    let mut agent_data = proxy_type
+        .unwrap_or_default()
+        .calc_script_hash()
+        .as_bytes()
+        .to_vec();
+    // without expiry nor quota, the agent has the legacy data of the proxy type hash only
+    if expiry != 0 || quota.is_some() {
+        agent_data.extend_from_slice(&expiry.to_le_bytes());
+    }
+    let mut output_agent_data = agent_data.clone();
+    if let Some((input_quota, output_quota)) = quota {
+        agent_data.extend_from_slice(&input_quota.to_le_bytes());
+        output_agent_data.extend_from_slice(&output_quota.to_le_bytes());
+    }
+    let agent_type = build_spore_type_script(&mut context, &agent_out_point, cluster.id.clone());
+    let agent_cell = build_output_cell_with_type_id(&mut context, 1000, agent_type);
+    let agent_input_out_point =
+        context.create_cell(agent_cell.clone(), Bytes::copy_from_slice(&agent_data));
+    let agent_header = HeaderBuilder::default()
+        .number(agent_block_number.pack())
+        .epoch(EpochNumberWithFraction::new(1, 0, 1).pack())
+        .build();
+    context.insert_header(agent_header.clone());
+    context.link_cell_with_block(agent_input_out_point.clone(), agent_header.hash(), 0);
+    let agent_input = CellInput::new_builder()
+        .previous_output(agent_input_out_point)
+        .build();
+
+    let (spore_out_point, spore_script_dep) = build_spore_materials(&mut context);
+    let input_cell = build_normal_input(&mut context, 1000);
+    let (spore_output, spore_data) =
+        build_cluster_spore_output(&mut context, &spore_out_point, &input_cell, 1, &cluster.id);
+
+    let mut cell_deps = vec![
+        cluster.script_dep,
+        agent_script_dep,
+        spore_script_dep,
+        cluster.dep,
+    ];
+    if proxy_alive {
+        cell_deps.push(proxy_dep);
+    }
+    let tx = build_simple_tx(
+        vec![input_cell, agent_input],
+        vec![agent_cell, spore_output],
+        cell_deps,
+        vec![output_agent_data.pack(), spore_data],
+    )
+    .as_advanced_builder()
+    .header_dep(agent_header.hash())
+    .build();
+    (context, tx)
+}
+
+/// A validator of the contracts deployed from the build directory
+pub fn build_validator(context: &mut Context) -> Validator {
+    Validator::new(
+        sdk_script_info(context, "spore"),
+        sdk_script_info(context, "cluster"),
+        sdk_script_info(context, "cluster_proxy"),
+        sdk_script_info(context, "cluster_agent"),
+        sdk_script_info(context, "spore_extension_lua"),
+        sdk_script_info(context, "spore_segment"),
+    )
+}
+
+pub fn sdk_script_info(context: &mut Context, name: &str) -> ScriptInfo {
+    let bin: Bytes = Loader::default().load_binary(name);
+    let code_hash = CellOutput::calc_data_hash(&bin);
+    let out_point = context.deploy_cell(bin);
+    let cell_dep = CellDep::new_builder().out_point(out_point).build();
+    ScriptInfo::new(code_hash, ScriptHashType::Data1, cell_dep)
+}
+
+pub fn sdk_lock(context: &mut Context) -> packed::Script {
+    Internal::build_always_success_script(context)
+}
+
+pub fn sdk_funding(context: &mut Context, lock: &packed::Script) -> Funding {
+    let output = CellOutput::new_builder()
+        .lock(lock.clone())
+        .capacity(100_000_000_000_000u64.pack())
+        .build();
+    let out_point = context.create_cell(output.clone(), Bytes::new());
+    Funding::new(
+        vec![LiveCell::new(out_point, output, Bytes::new())],
+        lock.clone(),
+        1000,
+    )
+}
+
+pub fn sdk_lua_lib(context: &mut Context) -> CellDep {
+    let lua_lib_bin: Bytes = Loader::default().load_binary("libckblua.so");
+    let out_point = context.deploy_cell(lua_lib_bin);
+    CellDep::new_builder().out_point(out_point).build()
+}
+
+// verifies the transaction and makes its outputs live
+pub fn sdk_commit(context: &mut Context, tx: TransactionView) -> Vec<LiveCell> {
+    sdk_commit_with_cycles(context, tx, MAX_CYCLES)
+}
+
+pub fn sdk_commit_with_cycles(
+    context: &mut Context,
+    tx: TransactionView,
+    max_cycles: u64,
+) -> Vec<LiveCell> {
+    let tx = context.complete_tx(tx);
+    context.verify_tx(&tx, max_cycles).expect("sdk transaction");
+    tx.outputs_with_data_iter()
+        .enumerate()
+        .map(|(index, (output, data))| {
+            let out_point = OutPoint::new(tx.hash(), index as u32);
+            context.create_cell_with_out_point(out_point.clone(), output.clone(), data.clone());
+            LiveCell::new(out_point, output, data)
+        })
+        .collect()
+}
+
+pub fn sdk_resolve(context: &Context, tx: &TransactionView) -> ResolvedTransaction {
+    let resolve = |out_point: OutPoint| {
+        let (output, data) = context.get_cell(&out_point).expect("live cell");
+        LiveCell::new(out_point, output, data)
+    };
+    ResolvedTransaction {
+        transaction: tx.clone(),
+        inputs: tx.input_pts_iter().map(resolve).collect(),
+        cell_deps: tx
+            .cell_deps_iter()
+            .map(|cell_dep| resolve(cell_dep.out_point()))
+            .collect(),
+        headers: tx
+            .header_deps_iter()
+            .map(|hash| context.headers[&hash].clone())
+            .collect(),
+    }
+}